@@ -0,0 +1,154 @@
+use std::sync::OnceLock;
+use crate::hardware::MOS6502;
+use crate::hardware::cpu::InstrDef;
+use AddressingMode::*;
+
+/// How an instruction's operand is interpreted, following the notation of the
+/// [6502 Instruction Set](https://www.masswerk.at/6502/6502_instruction_set.html) reference.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressingMode {
+    /// No operand
+    Implied,
+    /// Operates on the accumulator: `ASL A`
+    Accumulator,
+    /// `LDA #$05`
+    Immediate,
+    /// `LDA $10`
+    ZeroPage,
+    /// `LDA $10,X`
+    ZeroPageX,
+    /// `LDX $10,Y`
+    ZeroPageY,
+    /// `LDA $0200`
+    Absolute,
+    /// `LDA $0200,X`
+    AbsoluteX,
+    /// `LDA $0200,Y`
+    AbsoluteY,
+    /// `JMP ($0200)`
+    Indirect,
+    /// `LDA ($10,X)`
+    IndirectX,
+    /// `LDA ($10),Y`
+    IndirectY,
+    /// Signed offset from the next instruction: `BNE $C010`
+    Relative,
+}
+
+impl AddressingMode {
+    /// Number of operand bytes following the opcode.
+    pub fn operand_len(self) -> usize {
+        match self {
+            Implied | Accumulator => 0,
+            Immediate | ZeroPage | ZeroPageX | ZeroPageY | IndirectX | IndirectY | Relative => 1,
+            Absolute | AbsoluteX | AbsoluteY | Indirect => 2,
+        }
+    }
+}
+
+/// Mnemonic and addressing mode of the given opcode, as `MOS6502::instruction_table` defines
+/// them.
+pub fn decode(opcode: u8) -> (&'static str, AddressingMode) {
+    static INSTRUCTIONS: OnceLock<[InstrDef; 256]> = OnceLock::new();
+    let instr = &INSTRUCTIONS.get_or_init(MOS6502::instruction_table)[opcode as usize];
+    (instr.mnemonic, instr.mode)
+}
+
+/// Formats one instruction, given its opcode, operand bytes, and the address it's located at
+/// (which relative branches are resolved against).
+pub fn format_instruction(opcode: u8, operand: &[u8], address: u16) -> String {
+    let (mnemonic, mode) = decode(opcode);
+    let byte = operand.first().copied().unwrap_or(0);
+    let word = u16::from_le_bytes([byte, operand.get(1).copied().unwrap_or(0)]);
+    match mode {
+        Implied => mnemonic.to_string(),
+        Accumulator => format!("{} A", mnemonic),
+        Immediate => format!("{} #${:02X}", mnemonic, byte),
+        ZeroPage => format!("{} ${:02X}", mnemonic, byte),
+        ZeroPageX => format!("{} ${:02X},X", mnemonic, byte),
+        ZeroPageY => format!("{} ${:02X},Y", mnemonic, byte),
+        Absolute => format!("{} ${:04X}", mnemonic, word),
+        AbsoluteX => format!("{} ${:04X},X", mnemonic, word),
+        AbsoluteY => format!("{} ${:04X},Y", mnemonic, word),
+        Indirect => format!("{} (${:04X})", mnemonic, word),
+        IndirectX => format!("{} (${:02X},X)", mnemonic, byte),
+        IndirectY => format!("{} (${:02X}),Y", mnemonic, byte),
+        Relative => {
+            let target = address.wrapping_add(2).wrapping_add(byte as i8 as u16);
+            format!("{} ${:04X}", mnemonic, target)
+        }
+    }
+}
+
+/// Decodes machine code into instructions, given the address the code starts at. Returns each
+/// instruction's address along with its text, e.g. `(0xC000, "LDA $0200,X")`.
+///
+/// An instruction cut off by the end of `bytes` comes out as raw `.byte` data.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_nes::disasm::disassemble;
+///
+/// let code = [0xBD, 0x00, 0x02, 0xD0, 0xFB, 0x60];
+/// assert_eq!(disassemble(&code, 0xC000), vec![
+///     (0xC000, "LDA $0200,X".to_string()),
+///     (0xC003, "BNE $C000".to_string()),
+///     (0xC005, "RTS".to_string()),
+/// ]);
+/// ```
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<(u16, String)> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let address = origin.wrapping_add(offset as u16);
+        let opcode = bytes[offset];
+        let len = 1 + decode(opcode).1.operand_len();
+        let line = match bytes.get(offset + 1..offset + len) {
+            Some(operand) => format_instruction(opcode, operand, address),
+            None => {
+                let data: Vec<String> = bytes[offset..].iter().map(|byte| format!("${:02X}", byte)).collect();
+                format!(".byte {}", data.join(", "))
+            }
+        };
+        lines.push((address, line));
+        offset += len;
+    }
+    lines
+}
+
+/// Decodes the instructions starting anywhere from `start` to `end` (inclusive), reading memory
+/// through `read`. Yields each instruction's address, text and length in bytes, advancing by that
+/// length, so a frontend can lay out a disassembly view without tracking lengths itself.
+///
+/// `read` is called for every byte decoded, so it shouldn't have side effects (see `Bus::peek`).
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_nes::disasm::disassemble_range;
+///
+/// let mut memory = [0; 0x10000];
+/// memory[0x8000..0x8006].copy_from_slice(&[0xA9, 0x01, 0x8D, 0x00, 0x20, 0xE8]);
+/// let lines: Vec<_> = disassemble_range(|address| memory[address as usize], 0x8000, 0x8005).collect();
+/// assert_eq!(lines, vec![
+///     (0x8000, "LDA #$01".to_string(), 2),
+///     (0x8002, "STA $2000".to_string(), 3),
+///     (0x8005, "INX".to_string(), 1),
+/// ]);
+/// ```
+pub fn disassemble_range(read: impl Fn(u16) -> u8, start: u16, end: u16) -> impl Iterator<Item = (u16, String, u16)> {
+    // Kept wider than an address, so an instruction ending at $FFFF doesn't wrap back around
+    let mut next = start as u32;
+    std::iter::from_fn(move || {
+        if next > end as u32 {
+            return None;
+        }
+        let address = next as u16;
+        let opcode = read(address);
+        let len = 1 + decode(opcode).1.operand_len() as u16;
+        let operand: Vec<u8> = (1..len).map(|offset| read(address.wrapping_add(offset))).collect();
+        next += len as u32;
+        Some((address, format_instruction(opcode, &operand, address), len))
+    })
+}
@@ -1,10 +1,39 @@
+use std::fmt;
 
 /// Errors specific to the project
 #[derive(Debug, Eq, PartialEq)]
 pub enum RustNesError {
     InvalidHeader,
     Break,
-    OutOfBounds,
-    InvalidOpcode(u8),
+    /// Data didn't fit: `addr` is the first address past the end of what it was written into
+    OutOfBounds { addr: u16 },
+    /// The CPU fetched an opcode it doesn't define, at address `pc`
+    InvalidOpcode { opcode: u8, pc: u16 },
+    UnsupportedMapper(usize),
+    InvalidSaveState,
+    InvalidMovie,
+    /// The ROM file is shorter than its header says (sizes in bytes, header included)
+    RomTooSmall { expected: usize, actual: usize },
+    /// A palette file isn't the 192 bytes of a `.pal` file (its actual length)
+    InvalidPalette(usize),
 }
 
+impl fmt::Display for RustNesError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "invalid iNES header"),
+            Self::Break => write!(f, "execution was interrupted"),
+            Self::OutOfBounds { addr } => write!(f, "data runs past ${:04X}", addr),
+            Self::InvalidOpcode { opcode, pc } => write!(f, "invalid opcode ${:02X} at ${:04X}", opcode, pc),
+            Self::UnsupportedMapper(mapper) => write!(f, "unsupported mapper {}", mapper),
+            Self::InvalidSaveState => write!(f, "corrupt or incompatible save state"),
+            Self::InvalidMovie => write!(f, "corrupt or incompatible input movie"),
+            Self::RomTooSmall { expected, actual } => {
+                write!(f, "ROM is {} bytes, but its header says {}", actual, expected)
+            }
+            Self::InvalidPalette(len) => write!(f, "palette is {} bytes instead of 192", len),
+        }
+    }
+}
+
+impl std::error::Error for RustNesError {}
@@ -0,0 +1,206 @@
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+
+/// Timer periods (in CPU cycles), indexed by the low 4 bits of $4010.
+const RATE_TABLE: [u16; 16] = [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54];
+
+/// Delta modulation channel ($4010-$4013). Plays 1-bit delta-encoded samples straight out of
+/// CPU memory, fetched one byte at a time through DMA.
+#[derive(Default)]
+pub(crate) struct Dmc {
+    irq_enabled: bool,
+    looping: bool,
+    timer_period: u16,
+    timer: u16,
+    /// Output level (0-127)
+    level: u8,
+
+    // Memory reader
+    sample_address: u16,
+    sample_length: u16,
+    current_address: u16,
+    bytes_remaining: u16,
+    sample_buffer: Option<u8>,
+
+    // Output unit
+    shift: u8,
+    bits_remaining: u8,
+    silence: bool,
+
+    pub irq: bool,
+}
+
+impl Dmc {
+    /// Writes to one of the channel's four registers (0-3).
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.irq_enabled = value & 0x80 != 0;
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+                self.looping = value & 0x40 != 0;
+                self.timer_period = RATE_TABLE[(value & 0x0F) as usize];
+            }
+            1 => {
+                self.level = value & 0x7F;
+            }
+            2 => {
+                self.sample_address = 0xC000 | (value as u16) << 6;
+            }
+            3 => {
+                self.sample_length = ((value as u16) << 4) + 1;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Starts or stops sample playback ($4015 bit 4). Also acknowledges the DMC IRQ.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq = false;
+        if !enabled {
+            self.bytes_remaining = 0;
+        } else if self.bytes_remaining == 0 {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.current_address = self.sample_address;
+        self.bytes_remaining = self.sample_length;
+    }
+
+    /// Whether there are sample bytes left to be fetched.
+    pub fn active(&self) -> bool {
+        self.bytes_remaining > 0
+    }
+
+    /// The address the memory reader wants to fetch from, if its sample buffer needs refilling.
+    pub fn dma_address(&self) -> Option<u16> {
+        (self.sample_buffer.is_none() && self.bytes_remaining > 0).then_some(self.current_address)
+    }
+
+    /// Hands the memory reader the byte it requested through `dma_address`.
+    pub fn dma_fill(&mut self, value: u8) {
+        self.sample_buffer = Some(value);
+        // The address wraps around to $8000 rather than $0000
+        self.current_address = self.current_address.checked_add(1).unwrap_or(0x8000);
+        self.bytes_remaining -= 1;
+        if self.bytes_remaining == 0 {
+            if self.looping {
+                self.restart();
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    /// Clocks the channel's timer. Happens every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.timer_period.saturating_sub(1);
+
+        if !self.silence {
+            if self.shift & 1 == 1 {
+                if self.level <= 125 {
+                    self.level += 2;
+                }
+            } else if self.level >= 2 {
+                self.level -= 2;
+            }
+        }
+        self.shift >>= 1;
+
+        if self.bits_remaining > 0 {
+            self.bits_remaining -= 1;
+        }
+        if self.bits_remaining == 0 {
+            // Begin a new output cycle
+            self.bits_remaining = 8;
+            match self.sample_buffer.take() {
+                Some(byte) => {
+                    self.silence = false;
+                    self.shift = byte;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    /// Current output level of the channel (0-127).
+    pub fn output(&self) -> u8 {
+        self.level
+    }
+}
+
+impl Stateful for Dmc {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.irq_enabled);
+        state.bool(self.looping);
+        state.u16(self.timer_period);
+        state.u16(self.timer);
+        state.u8(self.level);
+        state.u16(self.sample_address);
+        state.u16(self.sample_length);
+        state.u16(self.current_address);
+        state.u16(self.bytes_remaining);
+        state.bool(self.sample_buffer.is_some());
+        state.u8(self.sample_buffer.unwrap_or(0));
+        state.u8(self.shift);
+        state.u8(self.bits_remaining);
+        state.bool(self.silence);
+        state.bool(self.irq);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.irq_enabled = state.bool()?;
+        self.looping = state.bool()?;
+        self.timer_period = state.u16()?;
+        self.timer = state.u16()?;
+        self.level = state.u8()?;
+        self.sample_address = state.u16()?;
+        self.sample_length = state.u16()?;
+        self.current_address = state.u16()?;
+        self.bytes_remaining = state.u16()?;
+        let buffered = state.bool()?;
+        let sample = state.u8()?;
+        self.sample_buffer = buffered.then_some(sample);
+        self.shift = state.u8()?;
+        self.bits_remaining = state.u8()?;
+        self.silence = state.bool()?;
+        self.irq = state.bool()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_remaining_counts_down_to_an_irq() {
+        let mut dmc = Dmc::default();
+        // IRQ enabled, fastest rate, a 17-byte sample at $C040
+        dmc.write(0, 0x8F);
+        dmc.write(2, 0x01);
+        dmc.write(3, 0x01);
+        dmc.set_enabled(true);
+        for fetched in 0..17 {
+            assert_eq!(dmc.bytes_remaining, 17 - fetched);
+            assert_eq!(dmc.dma_address(), Some(0xC040 + fetched));
+            assert!(!dmc.irq);
+            dmc.dma_fill(0x55);
+            // Nothing more is fetched until the output unit takes the byte
+            assert_eq!(dmc.dma_address(), None);
+            while dmc.sample_buffer.is_some() {
+                dmc.clock_timer();
+            }
+        }
+        assert_eq!(dmc.bytes_remaining, 0);
+        assert!(!dmc.active());
+        assert!(dmc.irq);
+    }
+}
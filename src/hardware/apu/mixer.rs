@@ -0,0 +1,120 @@
+use std::collections::VecDeque;
+
+/// Default output sample rate, in Hz.
+pub const DEFAULT_SAMPLE_RATE: f64 = 44_100.0;
+
+/// Combines the channel outputs using the NES's nonlinear DAC response, and downsamples the
+/// result from the CPU clock rate to an audio sample rate.
+pub(crate) struct Mixer {
+    pulse_table: [f32; 31],
+    tnd_table: [f32; 203],
+    /// Rate (Hz) at which `sample` gets called
+    clock_rate: f64,
+    sample_rate: f64,
+    /// Progress towards the next output sample, in clock ticks
+    phase: f64,
+    /// Sum and count of the mixed values since the last output sample, for averaging
+    accumulator: f32,
+    accumulated: u32,
+    /// Output samples waiting to be pulled, holding at most one second of audio
+    buffer: VecDeque<f32>,
+}
+
+impl Mixer {
+    pub fn new(clock_rate: f64, sample_rate: f64) -> Self {
+        let mut pulse_table = [0.0; 31];
+        for (n, entry) in pulse_table.iter_mut().enumerate().skip(1) {
+            *entry = 95.52 / (8128.0 / n as f32 + 100.0);
+        }
+        let mut tnd_table = [0.0; 203];
+        for (n, entry) in tnd_table.iter_mut().enumerate().skip(1) {
+            *entry = 163.67 / (24329.0 / n as f32 + 100.0);
+        }
+        Self {
+            pulse_table,
+            tnd_table,
+            clock_rate,
+            sample_rate,
+            phase: 0.0,
+            accumulator: 0.0,
+            accumulated: 0,
+            buffer: VecDeque::with_capacity(sample_rate as usize),
+        }
+    }
+
+    /// Mixes the current channel outputs (0.0-1.0) and feeds them to the resampler.
+    pub fn sample(&mut self, pulse: [u8; 2], tnd: [u8; 3]) {
+        let pulse_out = self.pulse_table[(pulse[0] + pulse[1]) as usize];
+        let tnd_out = self.tnd_table[3 * tnd[0] as usize + 2 * tnd[1] as usize + tnd[2] as usize];
+        self.accumulator += pulse_out + tnd_out;
+        self.accumulated += 1;
+
+        self.phase += self.sample_rate;
+        if self.phase >= self.clock_rate {
+            self.phase -= self.clock_rate;
+            if self.buffer.len() >= self.sample_rate as usize {
+                self.buffer.pop_front();
+            }
+            self.buffer.push_back(self.accumulator / self.accumulated as f32);
+            self.accumulator = 0.0;
+            self.accumulated = 0;
+        }
+    }
+
+    pub fn set_clock_rate(&mut self, clock_rate: f64) {
+        self.clock_rate = clock_rate;
+        self.phase = 0.0;
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.sample_rate = sample_rate;
+        self.phase = 0.0;
+    }
+
+    pub fn samples_available(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Moves as many buffered samples as fit into `out`, oldest first. Returns how many were moved.
+    pub fn pull_samples(&mut self, out: &mut [f32]) -> usize {
+        let count = out.len().min(self.buffer.len());
+        for (slot, sample) in out.iter_mut().zip(self.buffer.drain(..count)) {
+            *slot = sample;
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::Region;
+    use crate::Nes;
+    use crate::testing::nrom;
+
+    #[test]
+    fn frames_of_a_tone_fill_the_buffer_at_the_sample_rate() {
+        // Pulse 1 at constant volume 15 and a period of $FD (about 440 Hz), then loop forever
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&[
+            0xA9, 0x01, 0x8D, 0x15, 0x40, // LDA #$01, STA $4015
+            0xA9, 0xBF, 0x8D, 0x00, 0x40, // LDA #$BF, STA $4000
+            0xA9, 0xFD, 0x8D, 0x02, 0x40, // LDA #$FD, STA $4002
+            0xA9, 0x00, 0x8D, 0x03, 0x40, // LDA #$00, STA $4003
+            0x4C, 0x14, 0x80,             // JMP $8014
+        ])).unwrap();
+        nes.reset().unwrap();
+        nes.next_frame().unwrap();
+        let mut samples = vec![0.0; DEFAULT_SAMPLE_RATE as usize];
+        nes.pull_samples(&mut samples);
+
+        let per_frame = DEFAULT_SAMPLE_RATE / Region::Ntsc.frame_rate();
+        for _ in 0..5 {
+            nes.next_frame().unwrap();
+            let count = nes.pull_samples(&mut samples);
+            assert!((count as f64 - per_frame).abs() <= 1.0, "{} samples in a frame", count);
+            let (min, max) = samples[..count].iter().fold((1.0f32, 0.0f32), |(min, max), &s| (min.min(s), max.max(s)));
+            assert!(max - min > 0.1, "no tone: samples range from {} to {}", min, max);
+        }
+    }
+}
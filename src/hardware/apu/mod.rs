@@ -0,0 +1,278 @@
+pub(crate) mod dmc;
+pub(crate) mod mixer;
+pub(crate) mod noise;
+pub(crate) mod pulse;
+pub(crate) mod triangle;
+pub(crate) mod units;
+
+use crate::hardware::apu::dmc::Dmc;
+use crate::hardware::apu::mixer::{Mixer, DEFAULT_SAMPLE_RATE};
+use crate::hardware::apu::noise::Noise;
+use crate::hardware::apu::pulse::Pulse;
+use crate::hardware::apu::triangle::Triangle;
+use crate::RustNesError;
+use crate::hardware::Region;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+
+/// CPU cycles at which the frame sequencer steps in 4-step mode. The last step also raises the
+/// frame IRQ, and the sequence restarts one cycle later.
+const FOUR_STEP_SEQUENCE: [u32; 4] = [7457, 14913, 22371, 29829];
+/// CPU cycles at which the frame sequencer steps in 5-step mode. The 4th step does nothing.
+const FIVE_STEP_SEQUENCE: [u32; 5] = [7457, 14913, 22371, 29829, 37281];
+/// `FOUR_STEP_SEQUENCE` for PAL consoles, whose slower CPU clock is divided differently
+const PAL_FOUR_STEP_SEQUENCE: [u32; 4] = [8313, 16627, 24939, 33253];
+/// `FIVE_STEP_SEQUENCE` for PAL consoles
+const PAL_FIVE_STEP_SEQUENCE: [u32; 5] = [8313, 16627, 24939, 33253, 41565];
+
+/// Audio Processing Unit, part of the 2A03.
+///
+/// Channels are clocked by their own timers, while their envelopes, sweeps and length counters are
+/// clocked by the frame sequencer in quarter and half frames.
+pub struct APU {
+    pulse1: Pulse, // $4000-$4003
+    pulse2: Pulse, // $4004-$4007
+    triangle: Triangle, // $4008-$400B
+    noise: Noise, // $400C-$400F
+    dmc: Dmc, // $4010-$4013
+    mixer: Mixer,
+    region: Region,
+    five_step: bool,
+    irq_inhibit: bool,
+    frame_irq: bool,
+    /// CPU cycles elapsed in the current frame sequence
+    cycle: u32,
+    /// APU cycles happen every other CPU cycle
+    odd_cycle: bool,
+}
+
+impl Default for APU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl APU {
+    pub fn new() -> Self {
+        Self {
+            pulse1: Pulse::new(true),
+            pulse2: Pulse::new(false),
+            triangle: Triangle::default(),
+            noise: Noise::default(),
+            dmc: Dmc::default(),
+            mixer: Mixer::new(Region::default().cpu_clock_speed(), DEFAULT_SAMPLE_RATE),
+            region: Region::default(),
+            five_step: false,
+            irq_inhibit: false,
+            frame_irq: false,
+            cycle: 0,
+            odd_cycle: false,
+        }
+    }
+
+    /// Advances the APU by one CPU cycle.
+    pub fn tick(&mut self) {
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+        if self.odd_cycle {
+            self.pulse1.clock_timer();
+            self.pulse2.clock_timer();
+        }
+        self.odd_cycle = !self.odd_cycle;
+
+        self.cycle += 1;
+        let (four_step_sequence, five_step_sequence) = match self.region {
+            Region::Pal => (&PAL_FOUR_STEP_SEQUENCE, &PAL_FIVE_STEP_SEQUENCE),
+            Region::Ntsc | Region::Dendy => (&FOUR_STEP_SEQUENCE, &FIVE_STEP_SEQUENCE),
+        };
+        if self.five_step {
+            match five_step_sequence.iter().position(|&c| c == self.cycle) {
+                Some(0 | 2) => self.quarter_frame(),
+                Some(1) => self.half_frame(),
+                Some(4) => {
+                    self.half_frame();
+                    self.cycle = 0;
+                }
+                _ => {}
+            }
+        } else {
+            match four_step_sequence.iter().position(|&c| c == self.cycle) {
+                Some(0 | 2) => self.quarter_frame(),
+                Some(1) => self.half_frame(),
+                Some(3) => {
+                    self.half_frame();
+                    if !self.irq_inhibit {
+                        self.frame_irq = true;
+                    }
+                    self.cycle = 0;
+                }
+                _ => {}
+            }
+        }
+
+        self.mixer.sample(self.pulse_output(), self.tnd_output());
+    }
+
+    /// Switches to the frame sequencer rate and CPU clock speed of the given region.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.mixer.set_clock_rate(region.cpu_clock_speed());
+    }
+
+    /// Sets the rate (in Hz) of the audio samples the APU produces. Defaults to 44100 Hz.
+    pub fn set_sample_rate(&mut self, sample_rate: f64) {
+        self.mixer.set_sample_rate(sample_rate)
+    }
+
+    /// Number of audio samples waiting to be pulled.
+    pub fn samples_available(&self) -> usize {
+        self.mixer.samples_available()
+    }
+
+    /// Moves buffered audio samples (mono, 0.0-1.0) into `out`, oldest first. Returns how many
+    /// samples were written. At most one second of audio is buffered; older samples get dropped.
+    pub fn pull_samples(&mut self, out: &mut [f32]) -> usize {
+        self.mixer.pull_samples(out)
+    }
+
+    /// Clocks envelopes and the triangle's linear counter.
+    fn quarter_frame(&mut self) {
+        self.pulse1.envelope.clock();
+        self.pulse2.envelope.clock();
+        self.noise.envelope.clock();
+        self.triangle.clock_linear();
+    }
+
+    /// Clocks envelopes, length counters and sweeps.
+    fn half_frame(&mut self) {
+        self.quarter_frame();
+        self.pulse1.length.clock();
+        self.pulse2.length.clock();
+        self.triangle.length.clock();
+        self.noise.length.clock();
+        self.pulse1.clock_sweep();
+        self.pulse2.clock_sweep();
+    }
+
+    /// Whether the APU is asserting the CPU's IRQ line.
+    pub fn irq(&self) -> bool {
+        self.frame_irq || self.dmc.irq
+    }
+
+    /// The address the DMC wants to read a sample byte from, if it needs one. Whoever services
+    /// the request must hand the byte over through `dmc_dma_fill` and stall the CPU.
+    pub fn dmc_dma_address(&self) -> Option<u16> {
+        self.dmc.dma_address()
+    }
+
+    /// Completes a DMC sample fetch requested through `dmc_dma_address`.
+    pub fn dmc_dma_fill(&mut self, value: u8) {
+        self.dmc.dma_fill(value)
+    }
+
+    /// Current output levels (0-15) of the two pulse channels, for mixing.
+    pub fn pulse_output(&self) -> [u8; 2] {
+        [self.pulse1.output(), self.pulse2.output()]
+    }
+
+    /// Current output levels of the triangle (0-15), noise (0-15) and DMC (0-127) channels,
+    /// for mixing.
+    pub fn tnd_output(&self) -> [u8; 3] {
+        [self.triangle.output(), self.noise.output(), self.dmc.output()]
+    }
+
+    /// Returns what reading an APU register would, without acknowledging the frame IRQ. Returns
+    /// `None` for the write-only registers.
+    pub fn peek(&self, address: u16) -> Option<u8> {
+        match address {
+            0x4015 => Some(
+                self.pulse1.length.active() as u8
+                    | (self.pulse2.length.active() as u8) << 1
+                    | (self.triangle.length.active() as u8) << 2
+                    | (self.noise.length.active() as u8) << 3
+                    | (self.dmc.active() as u8) << 4
+                    | (self.frame_irq as u8) << 6
+                    | (self.dmc.irq as u8) << 7,
+            ),
+            _ => None,
+        }
+    }
+
+    /// Reads from an APU register. Returns `None` for the write-only registers, which leave the
+    /// bus open.
+    pub fn read(&mut self, address: u16) -> Option<u8> {
+        let result = self.peek(address);
+        if address == 0x4015 {
+            self.frame_irq = false;
+        }
+        result
+    }
+
+    /// Silences every channel, as the reset button does (the same as writing 0 to $4015).
+    pub fn reset(&mut self) {
+        self.write(0x4015, 0);
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            0x4000..=0x4003 => self.pulse1.write(address - 0x4000, value),
+            0x4004..=0x4007 => self.pulse2.write(address - 0x4004, value),
+            0x4008..=0x400B => self.triangle.write(address - 0x4008, value),
+            0x400C..=0x400F => self.noise.write(address - 0x400C, value),
+            0x4010..=0x4013 => self.dmc.write(address - 0x4010, value),
+            0x4015 => {
+                self.pulse1.length.set_enabled(value & 0x01 != 0);
+                self.pulse2.length.set_enabled(value & 0x02 != 0);
+                self.triangle.length.set_enabled(value & 0x04 != 0);
+                self.noise.length.set_enabled(value & 0x08 != 0);
+                self.dmc.set_enabled(value & 0x10 != 0);
+            }
+            0x4017 => {
+                self.five_step = value & 0x80 != 0;
+                self.irq_inhibit = value & 0x40 != 0;
+                if self.irq_inhibit {
+                    self.frame_irq = false;
+                }
+                self.cycle = 0;
+                if self.five_step {
+                    self.half_frame();
+                }
+            }
+            _ => {
+                // Unused or not implemented yet, so the write goes nowhere
+                if cfg!(debug_assertions) {
+                    eprintln!("WARNING: write to unimplemented APU address: {:#06x}", address);
+                }
+            }
+        }
+    }
+}
+
+impl Stateful for APU {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.pulse1.save_state(state);
+        self.pulse2.save_state(state);
+        self.triangle.save_state(state);
+        self.noise.save_state(state);
+        self.dmc.save_state(state);
+        state.bool(self.five_step);
+        state.bool(self.irq_inhibit);
+        state.bool(self.frame_irq);
+        state.u32(self.cycle);
+        state.bool(self.odd_cycle);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.pulse1.load_state(state)?;
+        self.pulse2.load_state(state)?;
+        self.triangle.load_state(state)?;
+        self.noise.load_state(state)?;
+        self.dmc.load_state(state)?;
+        self.five_step = state.bool()?;
+        self.irq_inhibit = state.bool()?;
+        self.frame_irq = state.bool()?;
+        self.cycle = state.u32()?;
+        self.odd_cycle = state.bool()?;
+        Ok(())
+    }
+}
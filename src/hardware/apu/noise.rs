@@ -0,0 +1,126 @@
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+use super::units::{Envelope, LengthCounter};
+
+/// Timer periods (in CPU cycles), indexed by the low 4 bits of $400E.
+const PERIOD_TABLE: [u16; 16] = [4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
+
+/// Noise channel ($400C-$400F). Outputs pseudo-random bits from a 15-bit linear feedback shift
+/// register.
+pub(crate) struct Noise {
+    /// Mode 1 taps bit 6 instead of bit 1, producing a much shorter (metallic sounding) sequence
+    mode: bool,
+    shift: u16,
+    timer_period: u16,
+    timer: u16,
+    pub envelope: Envelope,
+    pub length: LengthCounter,
+}
+
+impl Default for Noise {
+    fn default() -> Self {
+        Self {
+            mode: false,
+            shift: 1, // The shift register is loaded with 1 on power-up
+            timer_period: PERIOD_TABLE[0],
+            timer: 0,
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+        }
+    }
+}
+
+impl Noise {
+    /// Writes to one of the channel's four registers (0-3).
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.length.halt = value & 0x20 != 0;
+                self.envelope.looping = value & 0x20 != 0;
+                self.envelope.constant = value & 0x10 != 0;
+                self.envelope.volume = value & 0x0F;
+            }
+            1 => {}
+            2 => {
+                self.mode = value & 0x80 != 0;
+                self.timer_period = PERIOD_TABLE[(value & 0x0F) as usize];
+            }
+            3 => {
+                self.length.load(value >> 3);
+                self.envelope.start = true;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocks the channel's timer. Happens every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period - 1;
+            self.clock_shift();
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Shifts the LFSR by one bit, feeding back bit 0 XOR either bit 1 or bit 6.
+    pub fn clock_shift(&mut self) {
+        let tap = if self.mode { 6 } else { 1 };
+        let feedback = (self.shift ^ (self.shift >> tap)) & 1;
+        self.shift = (self.shift >> 1) | (feedback << 14);
+    }
+
+    /// Current output level of the channel (0-15).
+    pub fn output(&self) -> u8 {
+        if !self.length.active() || self.shift & 1 == 1 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+impl Stateful for Noise {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.mode);
+        state.u16(self.shift);
+        state.u16(self.timer_period);
+        state.u16(self.timer);
+        self.envelope.save_state(state);
+        self.length.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.mode = state.bool()?;
+        self.shift = state.u16()?;
+        self.timer_period = state.u16()?;
+        self.timer = state.u16()?;
+        self.envelope.load_state(state)?;
+        self.length.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How many shifts it takes the LFSR to get back to its power-up state
+    fn lfsr_period(mode: u8) -> u32 {
+        let mut noise = Noise::default();
+        noise.write(2, mode << 7);
+        let mut shifts = 0;
+        loop {
+            noise.clock_shift();
+            shifts += 1;
+            if noise.shift == 1 {
+                return shifts;
+            }
+        }
+    }
+
+    #[test]
+    fn lfsr_periods() {
+        assert_eq!(lfsr_period(0), 32767);
+        assert_eq!(lfsr_period(1), 93);
+    }
+}
@@ -0,0 +1,226 @@
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+use super::units::{Envelope, LengthCounter};
+
+/// Waveforms for each of the 4 duty cycle settings.
+const DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0], // 12.5%
+    [0, 1, 1, 0, 0, 0, 0, 0], // 25%
+    [0, 1, 1, 1, 1, 0, 0, 0], // 50%
+    [1, 0, 0, 1, 1, 1, 1, 1], // 25% negated
+];
+
+/// Periodically adjusts a pulse channel's period, for pitch bends.
+#[derive(Default)]
+pub(crate) struct Sweep {
+    pub enabled: bool,
+    pub period: u8,
+    pub negate: bool,
+    pub shift: u8,
+    pub reload: bool,
+    divider: u8,
+}
+
+/// Pulse (square wave) channel. The APU has two of them: $4000-$4003 and $4004-$4007.
+pub(crate) struct Pulse {
+    /// Pulse 1 negates its sweep with one's complement, pulse 2 with two's complement
+    ones_complement: bool,
+    duty: u8,
+    duty_step: u8,
+    timer_period: u16,
+    timer: u16,
+    pub envelope: Envelope,
+    pub sweep: Sweep,
+    pub length: LengthCounter,
+}
+
+impl Pulse {
+    pub fn new(ones_complement: bool) -> Self {
+        Self {
+            ones_complement,
+            duty: 0,
+            duty_step: 0,
+            timer_period: 0,
+            timer: 0,
+            envelope: Envelope::default(),
+            sweep: Sweep::default(),
+            length: LengthCounter::default(),
+        }
+    }
+
+    /// Writes to one of the channel's four registers (0-3).
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.duty = value >> 6;
+                self.length.halt = value & 0x20 != 0;
+                self.envelope.looping = value & 0x20 != 0;
+                self.envelope.constant = value & 0x10 != 0;
+                self.envelope.volume = value & 0x0F;
+            }
+            1 => {
+                self.sweep.enabled = value & 0x80 != 0;
+                self.sweep.period = (value >> 4) & 0x07;
+                self.sweep.negate = value & 0x08 != 0;
+                self.sweep.shift = value & 0x07;
+                self.sweep.reload = true;
+            }
+            2 => {
+                self.timer_period = (self.timer_period & 0x700) | value as u16;
+            }
+            3 => {
+                self.timer_period = (self.timer_period & 0xFF) | ((value as u16 & 0x07) << 8);
+                self.length.load(value >> 3);
+                self.envelope.start = true;
+                self.duty_step = 0;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocks the channel's timer. Happens every APU cycle (every other CPU cycle).
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            self.duty_step = (self.duty_step + 1) % 8;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// The period the sweep unit is currently aiming for.
+    fn sweep_target(&self) -> u16 {
+        let change = self.timer_period >> self.sweep.shift;
+        if self.sweep.negate {
+            let change = change + self.ones_complement as u16;
+            self.timer_period.saturating_sub(change)
+        } else {
+            self.timer_period + change
+        }
+    }
+
+    /// The sweep unit silences the channel when the current period is too low or the target
+    /// period overflows 11 bits, even if the sweep unit is disabled.
+    pub fn sweep_muting(&self) -> bool {
+        self.timer_period < 8 || self.sweep_target() > 0x7FF
+    }
+
+    /// Clocks the sweep unit. Happens every half frame.
+    pub fn clock_sweep(&mut self) {
+        if self.sweep.divider == 0 && self.sweep.enabled && self.sweep.shift > 0 && !self.sweep_muting() {
+            self.timer_period = self.sweep_target();
+        }
+        if self.sweep.divider == 0 || self.sweep.reload {
+            self.sweep.divider = self.sweep.period;
+            self.sweep.reload = false;
+        } else {
+            self.sweep.divider -= 1;
+        }
+    }
+
+    /// Current output level of the channel (0-15).
+    pub fn output(&self) -> u8 {
+        if !self.length.active() || self.sweep_muting() || DUTY_TABLE[self.duty as usize][self.duty_step as usize] == 0 {
+            0
+        } else {
+            self.envelope.output()
+        }
+    }
+}
+
+impl Stateful for Sweep {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.enabled);
+        state.u8(self.period);
+        state.bool(self.negate);
+        state.u8(self.shift);
+        state.bool(self.reload);
+        state.u8(self.divider);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.enabled = state.bool()?;
+        self.period = state.u8()?;
+        self.negate = state.bool()?;
+        self.shift = state.u8()?;
+        self.reload = state.bool()?;
+        self.divider = state.u8()?;
+        Ok(())
+    }
+}
+
+impl Stateful for Pulse {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.duty);
+        state.u8(self.duty_step);
+        state.u16(self.timer_period);
+        state.u16(self.timer);
+        self.envelope.save_state(state);
+        self.sweep.save_state(state);
+        self.length.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.duty = state.u8()?;
+        self.duty_step = state.u8()?;
+        self.timer_period = state.u16()?;
+        self.timer = state.u16()?;
+        self.envelope.load_state(state)?;
+        self.sweep.load_state(state)?;
+        self.length.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pulse channel at constant volume 15 with the 25% negated duty, which is high on its first
+    /// step, playing the given timer period with the given sweep setup ($4001)
+    fn pulse(period: u16, sweep: u8) -> Pulse {
+        let mut pulse = Pulse::new(true);
+        pulse.length.set_enabled(true);
+        pulse.write(0, 0xDF);
+        pulse.write(1, sweep);
+        pulse.write(2, period as u8);
+        pulse.write(3, (period >> 8) as u8);
+        pulse
+    }
+
+    #[test]
+    fn halt_freezes_the_length_counter() {
+        let mut pulse = pulse(0x100, 0x00);
+        pulse.write(0, 0xFF);
+        // Length index 0: 10 half frames
+        assert_eq!(pulse.length.counter, 10);
+        for _ in 0..20 {
+            pulse.length.clock();
+        }
+        assert_eq!(pulse.length.counter, 10);
+        assert_eq!(pulse.output(), 15);
+
+        pulse.write(0, 0xDF);
+        for _ in 0..10 {
+            pulse.length.clock();
+        }
+        assert_eq!(pulse.length.counter, 0);
+        assert_eq!(pulse.output(), 0);
+    }
+
+    #[test]
+    fn sweep_mutes_out_of_range_periods() {
+        // The sweep unit is disabled throughout, which doesn't stop it muting
+        assert!(!pulse(8, 0x00).sweep_muting());
+        assert!(pulse(7, 0x00).sweep_muting());
+        assert_eq!(pulse(7, 0x00).output(), 0);
+
+        // Shift 0 doubles the period, past $7FF. Shift 1 only gets it to $600.
+        assert!(pulse(0x400, 0x00).sweep_muting());
+        assert_eq!(pulse(0x400, 0x00).output(), 0);
+        assert!(!pulse(0x400, 0x01).sweep_muting());
+        assert_eq!(pulse(0x400, 0x01).output(), 15);
+        // A negated sweep can't overflow
+        assert!(pulse(0x7FF, 0x01).sweep_muting());
+        assert!(!pulse(0x7FF, 0x09).sweep_muting());
+    }
+}
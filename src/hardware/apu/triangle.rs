@@ -0,0 +1,99 @@
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+use super::units::LengthCounter;
+
+/// The triangle channel's 32-step output sequence.
+const SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10,  9,  8,  7,  6,  5,  4,  3,  2,  1,  0,
+     0,  1,  2,  3,  4,  5,  6,  7,  8,  9, 10, 11, 12, 13, 14, 15,
+];
+
+/// Triangle wave channel ($4008-$400B). Has no volume control, but a linear counter on top of its
+/// length counter.
+#[derive(Default)]
+pub(crate) struct Triangle {
+    control: bool,
+    linear_reload_value: u8,
+    linear_reload: bool,
+    linear_counter: u8,
+    timer_period: u16,
+    timer: u16,
+    step: u8,
+    pub length: LengthCounter,
+}
+
+impl Triangle {
+    /// Writes to one of the channel's four registers (0-3).
+    pub fn write(&mut self, register: u16, value: u8) {
+        match register {
+            0 => {
+                self.control = value & 0x80 != 0;
+                self.length.halt = self.control;
+                self.linear_reload_value = value & 0x7F;
+            }
+            1 => {}
+            2 => {
+                self.timer_period = (self.timer_period & 0x700) | value as u16;
+            }
+            3 => {
+                self.timer_period = (self.timer_period & 0xFF) | ((value as u16 & 0x07) << 8);
+                self.length.load(value >> 3);
+                self.linear_reload = true;
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Clocks the channel's timer. Happens every CPU cycle.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.timer_period;
+            if self.length.active() && self.linear_counter > 0 {
+                self.step = (self.step + 1) % 32;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Clocks the linear counter. Happens every quarter frame.
+    pub fn clock_linear(&mut self) {
+        if self.linear_reload {
+            self.linear_counter = self.linear_reload_value;
+        } else if self.linear_counter > 0 {
+            self.linear_counter -= 1;
+        }
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    /// Current output level of the channel (0-15).
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.step as usize]
+    }
+}
+
+impl Stateful for Triangle {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.control);
+        state.u8(self.linear_reload_value);
+        state.bool(self.linear_reload);
+        state.u8(self.linear_counter);
+        state.u16(self.timer_period);
+        state.u16(self.timer);
+        state.u8(self.step);
+        self.length.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.control = state.bool()?;
+        self.linear_reload_value = state.u8()?;
+        self.linear_reload = state.bool()?;
+        self.linear_counter = state.u8()?;
+        self.timer_period = state.u16()?;
+        self.timer = state.u16()?;
+        self.step = state.u8()?;
+        self.length.load_state(state)
+    }
+}
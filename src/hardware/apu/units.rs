@@ -0,0 +1,111 @@
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+
+/// Length counter load values, indexed by the upper 5 bits written to a channel's length register.
+const LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20,  2, 40,  4, 80,  6, 160,  8, 60, 10, 14, 12, 26, 14,
+    12,  16, 24, 18, 48, 20, 96, 22, 192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+/// Automatically silences a channel once it runs out. Clocked by the frame sequencer's half frames.
+#[derive(Default)]
+pub(crate) struct LengthCounter {
+    pub enabled: bool,
+    pub halt: bool,
+    pub counter: u8,
+}
+
+impl LengthCounter {
+    /// Loads the counter from the length table. Ignored while the channel is disabled.
+    pub fn load(&mut self, index: u8) {
+        if self.enabled {
+            self.counter = LENGTH_TABLE[(index & 0x1F) as usize];
+        }
+    }
+    /// Enables or disables the channel. Disabling immediately clears the counter.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.counter = 0;
+        }
+    }
+    pub fn clock(&mut self) {
+        if !self.halt && self.counter > 0 {
+            self.counter -= 1;
+        }
+    }
+    pub fn active(&self) -> bool {
+        self.counter > 0
+    }
+}
+
+/// Volume envelope generator, shared by the pulse and noise channels. Clocked by the frame
+/// sequencer's quarter frames.
+#[derive(Default)]
+pub(crate) struct Envelope {
+    pub start: bool,
+    pub looping: bool,
+    pub constant: bool,
+    /// Doubles as the constant volume and the divider period
+    pub volume: u8,
+    divider: u8,
+    decay: u8,
+}
+
+impl Envelope {
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.volume;
+        } else if self.divider == 0 {
+            self.divider = self.volume;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+    pub fn output(&self) -> u8 {
+        if self.constant { self.volume } else { self.decay }
+    }
+}
+
+impl Stateful for LengthCounter {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.enabled);
+        state.bool(self.halt);
+        state.u8(self.counter);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.enabled = state.bool()?;
+        self.halt = state.bool()?;
+        self.counter = state.u8()?;
+        Ok(())
+    }
+}
+
+impl Stateful for Envelope {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.start);
+        state.bool(self.looping);
+        state.bool(self.constant);
+        state.u8(self.volume);
+        state.u8(self.divider);
+        state.u8(self.decay);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.start = state.bool()?;
+        self.looping = state.bool()?;
+        self.constant = state.bool()?;
+        self.volume = state.u8()?;
+        self.divider = state.u8()?;
+        self.decay = state.u8()?;
+        Ok(())
+    }
+}
@@ -1,24 +1,129 @@
+use crate::RustNesError;
 use crate::hardware::*;
-use std::cell::RefCell;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+use std::cell::{Cell, RefCell};
+
+/// Kind of access to an address on the bus, for watchpoints.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BusAccess {
+    Read,
+    Write,
+}
+
+/// The signature bytes a Four Score reports through $4016 and $4017 after both controllers, which
+/// games check to tell it's plugged in.
+const FOUR_SCORE_SIGNATURES: [u8; 2] = [0x10, 0x20];
+
+/// Whatever the CPU is wired to: it fetches instructions and reads and writes data through this,
+/// and nothing else. The NES's `Bus` (shared as a `RefCell<Bus>`) is one, but any memory map will
+/// do, which makes `MOS6502` usable beyond the NES.
+///
+/// Like `Bus`, every method takes `&self`, so implementations that change on reads or writes need
+/// interior mutability.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+/// use rust_nes::hardware::*;
+///
+/// /// 64KB of RAM, and nothing else
+/// struct FlatMemory(RefCell<Vec<u8>>);
+///
+/// impl MemoryBus for FlatMemory {
+///     fn read(&self, address: u16) -> u8 {
+///         self.0.borrow()[address as usize]
+///     }
+///     fn write(&self, address: u16, value: u8) {
+///         self.0.borrow_mut()[address as usize] = value;
+///     }
+/// }
+///
+/// let memory = Rc::new(FlatMemory(RefCell::new(vec![0; 0x10000])));
+/// // LDA #$42, STA $1234
+/// for (address, byte) in [0xA9, 0x42, 0x8D, 0x34, 0x12].into_iter().enumerate() {
+///     memory.write(address as u16, byte);
+/// }
+/// let mut my_cpu = MOS6502::new(memory.clone());
+/// my_cpu.step_instruction().unwrap();
+/// my_cpu.step_instruction().unwrap();
+/// assert_eq!(memory.read(0x1234), 0x42);
+/// ```
+pub trait MemoryBus {
+    /// Reads a byte, with whatever side effects that has.
+    fn read(&self, address: u16) -> u8;
+    /// Writes a byte.
+    fn write(&self, address: u16, value: u8);
+    /// Returns what reading an address would, without any side effects. Defaults to `read`,
+    /// which suits memory whose reads have none.
+    fn peek(&self, address: u16) -> u8 {
+        self.read(address)
+    }
+    /// Returns whether something is signaling an NMI, acknowledging it in the process. Defaults
+    /// to never.
+    fn poll_nmi(&self) -> bool {
+        false
+    }
+    /// Returns whether something is holding the IRQ line low. Unlike NMI, IRQ is level-triggered:
+    /// the line stays asserted until the source is acknowledged. Defaults to never.
+    fn irq(&self) -> bool {
+        false
+    }
+}
 
 /// NES MEMORY BUS
 ///
 /// Determines the hardware to access when given an address, serves as the linking point between
 /// all hardware on the system.
+///
+/// Reads from addresses nothing responds to return "open bus": the last value that was driven on
+/// the data lines, since nothing else changes it. This covers $4018-$401F, the write-only APU
+/// registers, and cartridge space the cartridge doesn't respond to (or all of it, with no
+/// cartridge inserted). Registers that only drive some of their bits fill in the rest the same
+/// way: bit 5 of $4015 and bits 5-7 of $4016/$4017. The PPU keeps its own data bus latch for its
+/// unused register bits.
+///
+/// Both reads and writes take `&self`. Each component sits in its own `RefCell` and is only
+/// borrowed for the duration of a single access, so the CPU never needs a mutable borrow of the
+/// whole bus, and a side-effecting read (like $2002 clearing vblank) can't collide with another
+/// borrow of the bus held during interrupt polling or DMA.
 pub struct Bus {
-    mem: WorkMemory, // $0000-$1FFF (mirrored three times)
-    ppu: RefCell<PPU>, // $2000-3FFF (mirrored every 8 bytes)
+    mem: RefCell<WorkMemory>, // $0000-$1FFF (mirrored three times)
+    ppu: RefCell<PPU>, // $2000-$3FFF (mirrored every 8 bytes)
     apu: RefCell<APU>, // $4000-401F ($4018-1F unused)
-    cart: Option<RefCell<Cart>>,
+    controllers: [RefCell<Controller>; 4], // $4016-$4017 (3 and 4 through a Four Score)
+    controller_port: ControllerPort,
+    zapper: RefCell<Zapper>,
+    expansion: Option<RefCell<Box<dyn ExpansionDevice>>>,
+    /// Bits read from each port since the last strobe, to step through a Four Score's report
+    four_score_reads: [Cell<u8>; 2],
+    cart: Option<RefCell<Cart>>, // $4020-$FFFF
+    last_bus_value: Cell<u8>,
+    /// Page a write to $4014 asked OAM DMA to copy, until the 2A03 takes it
+    oam_dma: Cell<Option<u8>>,
+    /// Accesses to watch out for, for debugging
+    watchpoints: Vec<(u16, BusAccess)>,
+    /// The most recent access that matched a watchpoint, until taken
+    watchpoint_hit: Cell<Option<(u16, BusAccess)>>,
 }
 
 impl Bus {
     pub fn new(ppu: RefCell<PPU>, apu: RefCell<APU>) -> Self {
         Self {
-            mem: WorkMemory::new(),
+            mem: RefCell::new(WorkMemory::new()),
             ppu,
             apu,
+            controllers: Default::default(),
+            controller_port: ControllerPort::default(),
+            zapper: RefCell::new(Zapper::new()),
+            expansion: None,
+            four_score_reads: Default::default(),
             cart: None,
+            last_bus_value: Cell::new(0),
+            oam_dma: Cell::new(None),
+            watchpoints: Vec::new(),
+            watchpoint_hit: Cell::new(None),
         }
     }
 
@@ -26,37 +131,334 @@ impl Bus {
         self.cart = Some(cart);
     }
 
+    /// The currently loaded cartridge, if any.
+    pub fn cart(&self) -> Option<&RefCell<Cart>> {
+        self.cart.as_ref()
+    }
+
+    /// The console's internal RAM, mapped to $0000-$1FFF.
+    pub fn work_memory(&self) -> &RefCell<WorkMemory> {
+        &self.mem
+    }
+
+    /// The PPU connected to the bus.
+    pub fn ppu(&self) -> &RefCell<PPU> {
+        &self.ppu
+    }
+
+    /// The APU connected to the bus.
+    pub fn apu(&self) -> &RefCell<APU> {
+        &self.apu
+    }
+
+    /// The controller plugged into the given port (0 for $4016, 1 for $4017). With a Four Score,
+    /// controllers 3 and 4 are ports 2 and 3. `None` for any other port.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()));
+    /// my_bus.controller(0).unwrap().borrow_mut().set_buttons(Buttons::A | Buttons::START);
+    /// my_bus.controller(1).unwrap().borrow_mut().set_buttons(Buttons::B | Buttons::LEFT);
+    /// my_bus.write(0x4016, 1);
+    /// my_bus.write(0x4016, 0);
+    /// let read_out = |address| (0..8).fold(0, |buttons, bit| buttons | (my_bus.read(address) & 1) << bit);
+    /// assert_eq!(read_out(0x4016), (Buttons::A | Buttons::START).bits());
+    /// assert_eq!(read_out(0x4017), (Buttons::B | Buttons::LEFT).bits());
+    /// ```
+    pub fn controller(&self, port: usize) -> Option<&RefCell<Controller>> {
+        self.controllers.get(port)
+    }
+
+    /// The Zapper, read through $4017 when plugged in (see `set_controller_port`).
+    pub fn zapper(&self) -> &RefCell<Zapper> {
+        &self.zapper
+    }
+
+    /// Changes what's plugged into the controller ports.
+    pub fn set_controller_port(&mut self, controller_port: ControllerPort) {
+        self.controller_port = controller_port;
+    }
+
+    /// Plugs a device into the expansion port, replacing any that was there.
+    pub fn connect_expansion(&mut self, device: Box<dyn ExpansionDevice>) {
+        self.expansion = Some(RefCell::new(device));
+    }
+
+    /// Unplugs the expansion port device, if any.
+    pub fn disconnect_expansion(&mut self) {
+        self.expansion = None;
+    }
+
+    /// The low bits of a read from $4016 (port 0) or $4017 (port 1), from whatever's plugged in,
+    /// there and in the expansion port. Unless `peek`ing, the port moves on to the next bit.
+    fn read_port(&self, port: usize, peek: bool) -> u8 {
+        let read = |controller: &RefCell<Controller>| {
+            if peek { controller.borrow().peek() } else { controller.borrow_mut().read() }
+        };
+        let expansion = self.expansion.as_ref().map_or(0, |device| {
+            if peek { device.borrow().peek(port) } else { device.borrow_mut().read(port) }
+        });
+        let value = match self.controller_port {
+            ControllerPort::Zapper if port == 1 => {
+                let zapper = self.zapper.borrow();
+                let light_sensed = zapper.target().is_some_and(|(x, y)| self.ppu.borrow().light_sensed(x, y));
+                zapper.read(light_sensed)
+            }
+            ControllerPort::Standard | ControllerPort::Zapper => read(&self.controllers[port]),
+            ControllerPort::FourScore => {
+                let reads = self.four_score_reads[port].get();
+                let value = match reads {
+                    0..=7 => read(&self.controllers[port]),
+                    8..=15 => read(&self.controllers[port + 2]),
+                    // Read out most significant bit first
+                    16..=23 => FOUR_SCORE_SIGNATURES[port] >> (23 - reads) & 1,
+                    _ => 1,
+                };
+                if !peek && !self.controllers[port].borrow().strobe() {
+                    self.four_score_reads[port].set(reads.saturating_add(1));
+                }
+                value
+            }
+        };
+        value | expansion & 0x1E
+    }
+
+    /// Advances the PPU by one dot, letting the cartridge watch its address bus. When that
+    /// completes a frame, the controllers are told, for turbo.
+    pub fn tick_ppu(&self) {
+        let mut ppu = self.ppu.borrow_mut();
+        let frame = ppu.frame_count();
+        ppu.tick(self.cart.as_ref());
+        if ppu.frame_count() != frame {
+            for controller in &self.controllers {
+                controller.borrow_mut().set_frame(ppu.frame_count());
+            }
+        }
+    }
+
+    /// Advances the APU by one CPU cycle, servicing any DMC sample fetch it requests.
+    ///
+    /// Returns the number of cycles the CPU must be stalled for, to account for the DMA.
+    pub fn tick_apu(&self) -> u16 {
+        let request = {
+            let mut apu = self.apu.borrow_mut();
+            apu.tick();
+            apu.dmc_dma_address()
+        };
+        match request {
+            Some(address) => {
+                let value = self.read(address);
+                self.apu.borrow_mut().dmc_dma_fill(value);
+                4
+            }
+            None => 0,
+        }
+    }
+
+    /// Returns the page ($XX00-$XXFF) a write to $4014 asked to copy to OAM, if one is waiting
+    /// to start, taking the request in the process.
+    pub fn take_oam_dma(&self) -> Option<u8> {
+        self.oam_dma.take()
+    }
+
+    /// Returns whether an NMI is being signaled to the CPU, acknowledging it in the process.
+    pub fn poll_nmi(&self) -> bool {
+        self.ppu.borrow_mut().take_nmi()
+    }
+
+    /// Returns whether the APU or the cartridge is holding the CPU's IRQ line low.
+    pub fn irq(&self) -> bool {
+        self.apu.borrow().irq() || self.cart.as_ref().is_some_and(|cart| cart.borrow().irq())
+    }
+
+    /// Starts watching for the given kind of access to an address. See `take_watchpoint_hit`.
+    pub fn add_watchpoint(&mut self, address: u16, access: BusAccess) {
+        if !self.watchpoints.contains(&(address, access)) {
+            self.watchpoints.push((address, access));
+        }
+    }
+
+    /// Stops watching for the given kind of access to an address.
+    pub fn remove_watchpoint(&mut self, address: u16, access: BusAccess) {
+        self.watchpoints.retain(|&watchpoint| watchpoint != (address, access));
+    }
+
+    /// Returns the last access that matched a watchpoint since this was last called, if any.
+    pub fn take_watchpoint_hit(&self) -> Option<(u16, BusAccess)> {
+        self.watchpoint_hit.take()
+    }
+
+    fn watch(&self, address: u16, access: BusAccess) {
+        if self.watchpoints.contains(&(address, access)) {
+            self.watchpoint_hit.set(Some((address, access)));
+        }
+    }
+
     pub fn read(&self, address: u16) -> u8 {
-        match address {
+        self.watch(address, BusAccess::Read);
+        let value = match address {
             0x0000..=0x1FFF => {
-                self.mem.read(address & 0x07FF)
+                Some(self.mem.borrow().read(address & 0x07FF))
             }
             0x2000..=0x3FFF => {
-                self.ppu.borrow_mut().read(address & 0x200F)
+                Some(self.ppu.borrow_mut().read(address & 0x2007, self.cart.as_ref()))
+            }
+            0x4016 | 0x4017 => {
+                // Only the low 5 bits are driven, the rest are left open
+                let value = self.read_port(address as usize - 0x4016, false);
+                Some(value & 0x1F | self.last_bus_value.get() & 0xE0)
             }
             0x4000..=0x401F => {
-                self.apu.borrow_mut().read(address)
+                // Bit 5 of $4015 isn't driven
+                self.apu.borrow_mut().read(address).map(|value| value & !0x20 | self.last_bus_value.get() & 0x20)
             }
             0x4020..=0xFFFF => {
-                match &self.cart {
-                    Some(rom) => rom.borrow_mut().read(address),
-                    None => 0
-                }
+                self.cart.as_ref().and_then(|cart| cart.borrow_mut().read(address))
             }
-        }
+        }.unwrap_or(self.last_bus_value.get());
+        self.last_bus_value.set(value);
+        value
+    }
+
+    /// Reads a little-endian 16-bit value: the low byte from `address`, the high byte from the
+    /// next address (wrapping from $FFFF to $0000), like the interrupt vectors are read.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()));
+    /// my_bus.write(0x02FF, 0x34);
+    /// my_bus.write(0x0300, 0x12);
+    /// assert_eq!(my_bus.read16(0x02FF), 0x1234);
+    /// ```
+    pub fn read16(&self, address: u16) -> u16 {
+        u16::from_le_bytes([self.read(address), self.read(address.wrapping_add(1))])
+    }
+
+    /// Reads a little-endian 16-bit value like `read16`, except the high byte comes from the same
+    /// page as the low byte: at $xxFF, it's read from $xx00. That's the 6502's `JMP ($xxFF)` bug,
+    /// and how pointers at $FF wrap around zero page for the `($zz,X)` and `($zz),Y` modes.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()));
+    /// my_bus.write(0x02FF, 0x34);
+    /// my_bus.write(0x0300, 0x12);
+    /// my_bus.write(0x0200, 0x56);
+    /// assert_eq!(my_bus.read16_wrap(0x02FF), 0x5634);
+    /// assert_eq!(my_bus.read16_wrap(0x02FE), my_bus.read16(0x02FE));
+    /// ```
+    pub fn read16_wrap(&self, address: u16) -> u16 {
+        let high = address & 0xFF00 | address.wrapping_add(1) & 0x00FF;
+        u16::from_le_bytes([self.read(address), self.read(high)])
     }
 
-    pub fn write(&mut self, address: u16, value: u8) {
+    /// Returns what reading an address would, without any of the side effects: registers aren't
+    /// acknowledged or advanced, watchpoints don't trigger, and the open bus value stays put.
+    pub fn peek(&self, address: u16) -> u8 {
         match address {
             0x0000..=0x1FFF => {
-                self.mem.write(address & 0x07FF, value)
+                Some(self.mem.borrow().read(address & 0x07FF))
             }
             0x2000..=0x3FFF => {
-                self.ppu.borrow_mut().write(address & 0x200F, value)
+                Some(self.ppu.borrow().peek(address & 0x2007))
+            }
+            0x4016 | 0x4017 => {
+                let value = self.read_port(address as usize - 0x4016, true);
+                Some(value & 0x1F | self.last_bus_value.get() & 0xE0)
+            }
+            0x4000..=0x401F => {
+                self.apu.borrow().peek(address).map(|value| value & !0x20 | self.last_bus_value.get() & 0x20)
+            }
+            0x4020..=0xFFFF => {
+                self.cart.as_ref().and_then(|cart| cart.borrow().peek(address))
+            }
+        }.unwrap_or(self.last_bus_value.get())
+    }
+
+    /// Formats `len` bytes starting at `start` like a hex editor would: 16 to a row, each row
+    /// starting with its address and ending with the bytes as ASCII (`.` for anything not
+    /// printable). Bytes are read with `peek`, so dumping registers doesn't disturb them.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()));
+    /// for (offset, byte) in b"Hello, NES!".iter().enumerate() {
+    ///     my_bus.write(0x0300 + offset as u16, *byte);
+    /// }
+    /// assert_eq!(
+    ///     my_bus.hexdump(0x0300, 20),
+    ///     "0300  48 65 6C 6C 6F 2C 20 4E 45 53 21 00 00 00 00 00  |Hello, NES!.....|\n\
+    ///      0310  00 00 00 00                                      |....|\n",
+    /// );
+    /// ```
+    pub fn hexdump(&self, start: u16, len: usize) -> String {
+        let bytes: Vec<u8> = (0..len).map(|offset| self.peek(start.wrapping_add(offset as u16))).collect();
+        let mut dump = String::new();
+        for (row, chunk) in bytes.chunks(16).enumerate() {
+            let hex: Vec<String> = chunk.iter().map(|byte| format!("{:02X}", byte)).collect();
+            let ascii: String = chunk.iter()
+                .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' })
+                .collect();
+            let address = start.wrapping_add(row as u16 * 16);
+            dump += &format!("{:04X}  {:<47}  |{}|\n", address, hex.join(" "), ascii);
+        }
+        dump
+    }
+
+    pub fn write(&self, address: u16, value: u8) {
+        self.watch(address, BusAccess::Write);
+        self.last_bus_value.set(value);
+        match address {
+            0x2000..=0x3FFF => {
+                self.ppu.borrow_mut().write(address & 0x2007, value, self.cart.as_ref())
+            }
+            0x4014 => self.oam_dma.set(Some(value)),
+            0x4016 => {
+                // The strobe goes out to every controller, and restarts a Four Score's report
+                for controller in &self.controllers {
+                    controller.borrow_mut().write(value);
+                }
+                if value & 1 == 1 {
+                    for reads in &self.four_score_reads {
+                        reads.set(0);
+                    }
+                }
+                if let Some(device) = &self.expansion {
+                    device.borrow_mut().write(value);
+                }
             }
             0x4000..=0x401F => {
                 self.apu.borrow_mut().write(address, value)
             }
+            _ => self.poke(address, value),
+        }
+    }
+
+    /// Writes to memory for a debugger: RAM and the cartridge get written like `write` would, but
+    /// watchpoints don't trigger, the open bus value stays put, and the I/O registers at
+    /// $2000-$401F are left alone, so the PPU, APU, controllers and DMA don't notice.
+    pub fn poke(&self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => {
+                self.mem.borrow_mut().write(address & 0x07FF, value)
+            }
+            0x2000..=0x401F => {}
             0x4020..=0xFFFF => {
                 if let Some(cart) = &self.cart {
                     cart.borrow_mut().write(address, value)
@@ -64,5 +466,267 @@ impl Bus {
             }
         }
     }
+
+    /// Reads from the PPU's own address space ($0000-$3FFF), as PPUDATA would but without its
+    /// side effects (see `PPU::read_memory`).
+    pub fn ppu_read(&self, address: u16) -> u8 {
+        self.ppu.borrow_mut().read_memory(address, self.cart.as_ref())
+    }
+
+    /// Writes to the PPU's own address space ($0000-$3FFF). See `ppu_read`.
+    pub fn ppu_write(&self, address: u16, value: u8) {
+        self.ppu.borrow_mut().write_memory(address, value, self.cart.as_ref())
+    }
+}
+
+/// Borrows the bus for the duration of each access, as the CPU shares it with the rest of the
+/// console.
+impl MemoryBus for RefCell<Bus> {
+    fn read(&self, address: u16) -> u8 {
+        self.borrow().read(address)
+    }
+    fn write(&self, address: u16, value: u8) {
+        self.borrow().write(address, value)
+    }
+    fn peek(&self, address: u16) -> u8 {
+        self.borrow().peek(address)
+    }
+    fn poll_nmi(&self) -> bool {
+        self.borrow().poll_nmi()
+    }
+    fn irq(&self) -> bool {
+        self.borrow().irq()
+    }
+}
+
+impl Stateful for Bus {
+    fn save_state(&self, state: &mut StateWriter) {
+        self.mem.borrow().save_state(state);
+        self.ppu.borrow().save_state(state);
+        self.apu.borrow().save_state(state);
+        for controller in &self.controllers {
+            controller.borrow().save_state(state);
+        }
+        for reads in &self.four_score_reads {
+            state.u8(reads.get());
+        }
+        self.zapper.borrow().save_state(state);
+        state.bool(self.cart.is_some());
+        if let Some(cart) = &self.cart {
+            cart.borrow().save_state(state);
+        }
+        state.u8(self.last_bus_value.get());
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.mem.borrow_mut().load_state(state)?;
+        self.ppu.borrow_mut().load_state(state)?;
+        self.apu.borrow_mut().load_state(state)?;
+        for controller in &self.controllers {
+            controller.borrow_mut().load_state(state)?;
+        }
+        for reads in &self.four_score_reads {
+            reads.set(state.u8()?);
+        }
+        self.zapper.borrow_mut().load_state(state)?;
+        match (state.bool()?, &self.cart) {
+            (true, Some(cart)) => cart.borrow_mut().load_state(state)?,
+            (false, None) => {}
+            // Made with a cartridge in when there isn't one now, or vice versa
+            _ => return Err(RustNesError::InvalidSaveState),
+        }
+        self.last_bus_value.set(state.u8()?);
+        Ok(())
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::nrom;
+
+    fn bus() -> Bus {
+        Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))
+    }
+
+    /// Strobes the controllers through $4016
+    fn strobe(bus: &Bus) {
+        bus.write(0x4016, 1);
+        bus.write(0x4016, 0);
+    }
+
+    /// Reads bit 0 of `address` `count` times, first read in the lowest bit
+    fn read_bits(bus: &Bus, address: u16, count: u32) -> u32 {
+        (0..count).fold(0, |bits, bit| bits | ((bus.read(address) & 1) as u32) << bit)
+    }
+
+    #[test]
+    fn peeking_ppustatus_leaves_vblank_set() {
+        let bus = bus();
+        while !bus.ppu().borrow().in_vblank() {
+            bus.tick_ppu();
+        }
+        assert_eq!(bus.peek(0x2002) & 0x80, 0x80);
+        assert_eq!(bus.peek(0x2002) & 0x80, 0x80);
+        assert_eq!(bus.read(0x2002) & 0x80, 0x80);
+        assert_eq!(bus.read(0x2002) & 0x80, 0x00);
+    }
+
+    #[test]
+    fn ports_read_their_own_controller() {
+        let bus = bus();
+        bus.controller(0).unwrap().borrow_mut().set_buttons(Buttons::A | Buttons::START);
+        bus.controller(1).unwrap().borrow_mut().set_buttons(Buttons::B | Buttons::LEFT);
+        strobe(&bus);
+        // Interleaved, to show the ports shift independently
+        for bit in 0..8 {
+            assert_eq!(bus.read(0x4016) & 1, (Buttons::A | Buttons::START).bits() >> bit & 1);
+            assert_eq!(bus.read(0x4017) & 1, (Buttons::B | Buttons::LEFT).bits() >> bit & 1);
+        }
+        // Then both report 1s
+        assert_eq!(read_bits(&bus, 0x4016, 8), 0xFF);
+        assert_eq!(read_bits(&bus, 0x4017, 8), 0xFF);
+    }
+
+    /// Drives bits 1-4 of $4016 with $0A and of $4017 with $14
+    struct Constant;
+
+    impl ExpansionDevice for Constant {
+        fn peek(&self, port: usize) -> u8 {
+            [0x0A, 0x14][port]
+        }
+    }
+
+    #[test]
+    fn expansion_bits_leave_the_controllers_alone() {
+        let mut bus = bus();
+        bus.connect_expansion(Box::new(Constant));
+        bus.controller(0).unwrap().borrow_mut().set_buttons(Buttons::UP);
+        bus.controller(1).unwrap().borrow_mut().set_buttons(Buttons::DOWN | Buttons::A);
+        strobe(&bus);
+        for bit in 0..8 {
+            assert_eq!(bus.read(0x4016) & 0x1F, 0x0A | Buttons::UP.bits() >> bit & 1);
+            assert_eq!(bus.read(0x4017) & 0x1F, 0x14 | (Buttons::DOWN | Buttons::A).bits() >> bit & 1);
+        }
+    }
+
+    #[test]
+    fn four_score_reads_two_controllers_then_a_signature() {
+        let mut bus = bus();
+        bus.set_controller_port(ControllerPort::FourScore);
+        let buttons = [Buttons::A, Buttons::B | Buttons::SELECT, Buttons::START | Buttons::UP, Buttons::RIGHT];
+        for (port, buttons) in buttons.into_iter().enumerate() {
+            bus.controller(port).unwrap().borrow_mut().set_buttons(buttons);
+        }
+        strobe(&bus);
+        // Controller 1 then 3 on $4016, controller 2 then 4 on $4017. The signatures come most
+        // significant bit first: $10 sets the 4th bit read, $20 the 3rd.
+        let expected = |first: Buttons, second: Buttons, signature_bit: u32| {
+            first.bits() as u32 | (second.bits() as u32) << 8 | 1 << (16 + signature_bit)
+        };
+        assert_eq!(read_bits(&bus, 0x4016, 24), expected(buttons[0], buttons[2], 3));
+        assert_eq!(read_bits(&bus, 0x4017, 24), expected(buttons[1], buttons[3], 2));
+        // Then 1s
+        assert_eq!(read_bits(&bus, 0x4016, 8), 0xFF);
+        assert_eq!(read_bits(&bus, 0x4017, 8), 0xFF);
+    }
+
+    #[test]
+    fn zapper_sees_a_freshly_drawn_bright_pixel() {
+        let mut bus = bus();
+        bus.set_controller_port(ControllerPort::Zapper);
+        bus.zapper().borrow_mut().aim(100, 50);
+        // A white backdrop, with rendering off, then the VRAM address moved out of the palettes
+        for (address, value) in [(0x2006, 0x3F), (0x2006, 0x00), (0x2007, 0x30), (0x2006, 0x20), (0x2006, 0x00)] {
+            bus.write(address, value);
+        }
+        let run_to = |scanline, dot| {
+            while (bus.ppu().borrow().scanline(), bus.ppu().borrow().dot()) != (scanline, dot) {
+                bus.tick_ppu();
+            }
+        };
+        let light_sensed = || bus.read(0x4017) & 0x08 == 0;
+
+        run_to(50, 100);
+        assert!(!light_sensed());
+        // Pixel x is drawn at dot x + 1
+        run_to(50, 101);
+        assert!(light_sensed());
+        // The photodiode only reacts to the beam passing by
+        run_to(80, 0);
+        assert!(!light_sensed());
+    }
+
+    #[test]
+    fn open_bus_reads_return_the_last_value() {
+        let bus = bus();
+        bus.write(0x0000, 0xA5);
+        bus.write(0x0001, 0x3C);
+        // The write left $3C on the bus, until the read drives $A5
+        assert_eq!(bus.read(0x4018), 0x3C);
+        assert_eq!(bus.read(0x0000), 0xA5);
+        // No cartridge, an unused APU address and a write-only APU register
+        for address in [0x8000, 0x401F, 0x4000, 0x8000] {
+            assert_eq!(bus.read(address), 0xA5);
+        }
+        // Peeks don't touch the bus
+        assert_eq!(bus.peek(0x0001), 0x3C);
+        assert_eq!(bus.read(0x4018), 0xA5);
+    }
+
+    #[test]
+    fn undriven_register_bits_come_from_the_open_bus() {
+        let bus = bus();
+        bus.controller(0).unwrap().borrow_mut().set_buttons(Buttons::A);
+        strobe(&bus);
+        bus.write(0x0000, 0xFF);
+        bus.write(0x0001, 0x40);
+
+        // Bit 5 of $4015
+        bus.read(0x0000);
+        assert_eq!(bus.read(0x4015) & 0x20, 0x20);
+        bus.read(0x0001);
+        assert_eq!(bus.read(0x4015) & 0x20, 0x00);
+
+        // Bits 5-7 of $4016/$4017, kept from one read to the next as the reads drive them again
+        bus.read(0x0001);
+        assert_eq!(bus.read(0x4016), 0x41);
+        assert_eq!(bus.read(0x4016), 0x40);
+        assert_eq!(bus.read(0x4017), 0x40);
+        bus.read(0x0000);
+        assert_eq!(bus.read(0x4017), 0xE0);
+    }
+
+    #[test]
+    fn reading_any_ppu_or_apu_register_is_harmless() {
+        let bus = bus();
+        bus.write(0x0000, 0x77);
+        for address in (0x2000..=0x2007).chain(0x4000..=0x401F) {
+            bus.read(0x0000);
+            bus.read(address);
+            bus.peek(address);
+        }
+        // Write-only and unused APU addresses are open bus
+        for address in (0x4000..=0x4014).chain(0x4018..=0x401F) {
+            bus.read(0x0000);
+            assert_eq!(bus.read(address), 0x77, "{:#06x}", address);
+        }
+    }
+
+    #[test]
+    fn pokes_leave_the_io_registers_alone() {
+        let mut bus = bus();
+        bus.load_cart(RefCell::new(Cart::from_rom(&nrom(&[])).unwrap()));
+        for (address, value) in [(0x2006, 0x21), (0x2006, 0x00), (0x2007, 0x55), (0x4014, 0x02), (0x0000, 0x42), (0x6000, 0x24)] {
+            bus.poke(address, value);
+        }
+        assert_eq!((bus.peek(0x0000), bus.peek(0x6000)), (0x42, 0x24));
+        assert_eq!(bus.take_oam_dma(), None);
+        assert_eq!(bus.ppu_read(0x2100), 0x00);
+        // The address latch wasn't touched either, so a real write sequence lands where expected
+        for (address, value) in [(0x2006, 0x20), (0x2006, 0x00), (0x2007, 0x77)] {
+            bus.write(address, value);
+        }
+        assert_eq!(bus.ppu_read(0x2000), 0x77);
+    }
+}
@@ -1,31 +1,225 @@
+use crate::RustNesError;
 use crate::header::NESHeader;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+use crate::hardware::mapper::{self, Mapper, Mirroring};
+
+/// Size of the optional trainer found between the header and PRG-ROM.
+pub const TRAINER_SIZE: usize = 512;
+/// Size of the PRG-RAM (SRAM) mapped to $6000-$7FFF.
+pub const PRG_RAM_SIZE: usize = 0x2000;
+
+/// Builds a cartridge from the contents of an iNES or NES 2.0 ROM file. Same as `Cart::from_rom`.
+pub fn load_cartridge(bytes: &[u8]) -> Result<Cart, RustNesError> {
+    Cart::from_rom(bytes)
+}
 
 pub struct Cart {
     header: NESHeader,
-    data: Vec<u8>,
+    mapper: Box<dyn Mapper>,
+    prg_ram: Vec<u8>, // $6000-$7FFF, battery-backed if `header.battery` is set
 }
 
 impl Cart {
-    pub fn new(header: NESHeader, data: &[u8]) -> Self {
-        Self {
+    /// Builds a cartridge from the contents of an iNES or NES 2.0 ROM file: parses the header,
+    /// checks the file is as long as the header says, skips the trainer and hands PRG-ROM and
+    /// CHR-ROM to the matching mapper.
+    ///
+    /// Returns `RustNesError::InvalidHeader` if the header can't be parsed, `RomTooSmall` if the
+    /// file is truncated, or `UnsupportedMapper` if the mapper isn't implemented.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_nes::hardware::Cart;
+    ///
+    /// // NROM with 16KB of PRG-ROM and CHR-RAM, starting with LDA #$42
+    /// let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    /// rom.extend([0xA9, 0x42]);
+    /// rom.resize(16 + 0x4000, 0);
+    /// let mut cart = Cart::from_rom(&rom).unwrap();
+    /// assert_eq!(cart.read(0x8001), Some(0x42));
+    /// // 16KB carts are mirrored into $C000-$FFFF
+    /// assert_eq!(cart.read(0xC000), Some(0xA9));
+    /// ```
+    pub fn from_rom(bytes: &[u8]) -> Result<Self, RustNesError> {
+        let header = NESHeader::from_bytes(bytes).ok_or(RustNesError::InvalidHeader)?;
+        Self::new(header, &bytes[NESHeader::SIZE..])
+    }
+
+    /// Constructs a `Cart` from its header and the ROM data following the header.
+    ///
+    /// The data is split into the (optional) trainer, PRG-ROM and CHR-ROM regions. A `chr_size` of
+    /// 0 means the cartridge has 8KB of CHR-RAM instead.
+    ///
+    /// Returns `RustNesError::RomTooSmall` if the data is shorter than the header says.
+    pub fn new(header: NESHeader, data: &[u8]) -> Result<Self, RustNesError> {
+        let expected = header.file_size();
+        let actual = NESHeader::SIZE + data.len();
+        if actual < expected {
+            return Err(RustNesError::RomTooSmall { expected, actual });
+        }
+        let prg_start = if header.trainer { TRAINER_SIZE } else { 0 };
+        let chr_start = prg_start + header.prg_size;
+        let prg = data[prg_start..chr_start].to_vec();
+        let chr = data[chr_start..chr_start + header.chr_size].to_vec();
+        let mut prg_ram = vec![0; PRG_RAM_SIZE];
+        if header.trainer {
+            // The trainer gets loaded into $7000-$71FF
+            prg_ram[0x1000..0x1000 + TRAINER_SIZE].copy_from_slice(&data[..TRAINER_SIZE]);
+        }
+        Ok(Self {
+            mapper: mapper::new_mapper(header.mapper_number, prg, chr, header.mirroring())?,
             header,
-            data: data.to_vec(),
+            prg_ram,
+        })
+    }
+    /// The header the cartridge was loaded with.
+    pub fn header(&self) -> &NESHeader {
+        &self.header
+    }
+    /// Contents of the battery-backed PRG-RAM, for persisting to a save file.
+    ///
+    /// Returns `None` if the cartridge has no battery.
+    pub fn save_ram(&self) -> Option<&[u8]> {
+        self.header.battery.then_some(self.prg_ram.as_slice())
+    }
+    /// Restores PRG-RAM from a previously persisted save file.
+    ///
+    /// Returns `RustNesError::OutOfBounds` if the data is larger than the PRG-RAM.
+    pub fn load_save_ram(&mut self, data: &[u8]) -> Result<(), RustNesError> {
+        let end = 0x6000 + self.prg_ram.len() as u16;
+        self.prg_ram
+            .get_mut(..data.len())
+            .ok_or(RustNesError::OutOfBounds { addr: end })?
+            .copy_from_slice(data);
+        Ok(())
+    }
+    /// Read byte from given (mapped) address. Returns `None` if nothing is mapped there.
+    pub fn read(&mut self, address: u16) -> Option<u8> {
+        if let 0x6000..=0x7FFF = address {
+            return Some(self.prg_ram[address as usize - 0x6000]);
         }
+        self.mapper.cpu_read(address)
     }
-    /// Read byte from given (mapped) address.
-    pub fn read(&self, address: u16) -> u8 {
-        match self.data.get(address as usize - 0x8000) {
-            Some(byte) => {*byte},
-            None => {
-                eprintln!("WARNING: attempted to read unmapped address: {}", address);
-                0
-            }
+    /// Read byte from given (mapped) address without side effects. Returns `None` if nothing is
+    /// mapped there.
+    pub fn peek(&self, address: u16) -> Option<u8> {
+        if let 0x6000..=0x7FFF = address {
+            return Some(self.prg_ram[address as usize - 0x6000]);
+        }
+        self.mapper.cpu_peek(address)
+    }
+    /// Write byte to given (mapped) address. Anything outside of PRG-RAM is handled by the
+    /// mapper, which is where bank switching registers live.
+    pub fn write(&mut self, address: u16, value: u8) {
+        if let 0x6000..=0x7FFF = address {
+            self.prg_ram[address as usize - 0x6000] = value;
+            return;
         }
+        self.mapper.cpu_write(address, value)
+    }
+    /// Read byte from given PPU address ($0000-$1FFF), i.e. from CHR-ROM/CHR-RAM.
+    pub fn ppu_read(&mut self, address: u16) -> u8 {
+        self.mapper.ppu_read(address)
+    }
+    /// Read byte from given PPU address ($0000-$1FFF) without side effects.
+    pub fn ppu_peek(&self, address: u16) -> u8 {
+        self.mapper.ppu_peek(address)
     }
-    /// Write byte from given (mapped) address.
-    pub fn write(&self, _address: u16, _value: u8) {
-        eprintln!("Cartridge writing not implemented");
-        todo!()
+    /// Write byte to given PPU address ($0000-$1FFF). Only has an effect on CHR-RAM.
+    pub fn ppu_write(&mut self, address: u16, value: u8) {
+        self.mapper.ppu_write(address, value)
+    }
+    /// The nametable mirroring the mapper currently has selected.
+    pub fn mirroring(&self) -> Mirroring {
+        self.mapper.mirroring()
+    }
+    /// Tells the mapper A12 rose on the PPU's address bus (see `Mapper::ppu_a12_rise`).
+    pub fn ppu_a12_rise(&mut self) {
+        self.mapper.ppu_a12_rise()
+    }
+    /// Whether the cartridge is holding the CPU's IRQ line low.
+    pub fn irq(&self) -> bool {
+        self.mapper.irq()
     }
 }
 
+impl Stateful for Cart {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.prg_ram);
+        self.mapper.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        state.bytes(&mut self.prg_ram)?;
+        self.mapper.load_state(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An iNES header for an NROM cart with 16KB of PRG-ROM and `chr_banks` 8KB banks of CHR-ROM
+    fn header(chr_banks: u8, trainer: bool) -> Vec<u8> {
+        vec![b'N', b'E', b'S', 0x1A, 1, chr_banks, (trainer as u8) << 2, 0, 0, 0, 0, 0, 0, 0, 0, 0]
+    }
+
+    #[test]
+    fn trainer_comes_before_prg_rom() {
+        for trainer in [false, true] {
+            let mut rom = header(0, trainer);
+            if trainer {
+                rom.extend([0x77; TRAINER_SIZE]);
+            }
+            rom.extend([0xA9, 0x42]);
+            rom.resize(rom.len() - 2 + 0x4000, 0);
+            let mut cart = Cart::from_rom(&rom).unwrap();
+            assert_eq!(cart.read(0x8000), Some(0xA9));
+            assert_eq!(cart.read(0x8001), Some(0x42));
+            // The trainer lands in $7000-$71FF
+            assert_eq!(cart.read(0x7000), Some(if trainer { 0x77 } else { 0x00 }));
+        }
+    }
+
+    #[test]
+    fn no_chr_rom_means_chr_ram() {
+        let mut rom = header(0, false);
+        rom.resize(rom.len() + 0x4000, 0);
+        let mut cart = Cart::from_rom(&rom).unwrap();
+        for address in [0x0000, 0x1000, 0x1FFF] {
+            cart.ppu_write(address, address as u8 ^ 0x5A);
+        }
+        for address in [0x0000, 0x1000, 0x1FFF] {
+            assert_eq!(cart.ppu_read(address), address as u8 ^ 0x5A);
+        }
+    }
+
+    #[test]
+    fn rom_shorter_than_its_header_is_rejected() {
+        let mut rom = header(1, false);
+        rom[4] = 2;
+        rom.resize(100, 0);
+        assert_eq!(Cart::from_rom(&rom).err(), Some(RustNesError::RomTooSmall { expected: 40976, actual: 100 }));
+    }
+
+    #[test]
+    fn battery_ram_survives_a_save_file() {
+        let mut rom = header(1, false);
+        rom[6] |= 0x02;
+        rom.resize(rom.len() + 0x4000 + 0x2000, 0);
+        let mut cart = Cart::from_rom(&rom).unwrap();
+        cart.write(0x6000, 0x12);
+        cart.write(0x7FFF, 0x34);
+        let save = cart.save_ram().unwrap().to_vec();
+
+        let mut cart = Cart::from_rom(&rom).unwrap();
+        assert_eq!(cart.read(0x6000), Some(0x00));
+        cart.load_save_ram(&save).unwrap();
+        assert_eq!((cart.read(0x6000), cart.read(0x7FFF)), (Some(0x12), Some(0x34)));
+
+        assert_eq!(cart.load_save_ram(&[0; PRG_RAM_SIZE + 1]), Err(RustNesError::OutOfBounds { addr: 0x8000 }));
+        let no_battery = Cart::from_rom(&[&header(1, false)[..], &[0; 0x6000]].concat()).unwrap();
+        assert_eq!(no_battery.save_ram(), None);
+    }
+}
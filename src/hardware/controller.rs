@@ -0,0 +1,196 @@
+use bitflags::bitflags;
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+
+bitflags! {
+    /// Buttons of a standard controller, in the order they get shifted out.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub struct Buttons: u8 {
+        const A      = 0b0000_0001;
+        const B      = 0b0000_0010;
+        const SELECT = 0b0000_0100;
+        const START  = 0b0000_1000;
+        const UP     = 0b0001_0000;
+        const DOWN   = 0b0010_0000;
+        const LEFT   = 0b0100_0000;
+        const RIGHT  = 0b1000_0000;
+    }
+}
+
+/// What's plugged into the controller ports.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ControllerPort {
+    /// A standard controller in each port
+    #[default]
+    Standard,
+    /// A Four Score (or Satellite) multitap, for four players. $4016 shifts out controller 1,
+    /// then controller 3, then a signature byte; $4017 does the same for controllers 2 and 4.
+    FourScore,
+    /// A standard controller in port 1 and a Zapper in port 2
+    Zapper,
+}
+
+/// Standard NES controller, read serially through $4016 (port 1) or $4017 (port 2).
+///
+/// While the strobe bit written to $4016 is high, the controller continuously reloads its shift
+/// register with the button state. Once it goes low, each read shifts out one button (A, B,
+/// Select, Start, Up, Down, Left, Right), after which reads return 1.
+///
+/// Buttons can be given turbo (auto-fire): while held, they alternate between pressed and
+/// released every so many frames. The bus tells the controller whenever a frame completes, so
+/// turbo is deterministic.
+#[derive(Default)]
+pub struct Controller {
+    buttons: Buttons,
+    shift: u8,
+    strobe: bool,
+    /// Frames each button stays pressed (then released) for under turbo, in shift order. 0 means
+    /// no turbo.
+    turbo: [u32; 8],
+    /// Number of frames the PPU has completed, for timing turbo
+    frame: u64,
+}
+
+impl Controller {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets which buttons are currently held down.
+    pub fn set_buttons(&mut self, buttons: Buttons) {
+        self.buttons = buttons;
+        if self.strobe {
+            self.shift = self.pressed().bits();
+        }
+    }
+
+    /// The buttons currently held down, turbo or not.
+    pub fn buttons(&self) -> Buttons {
+        self.buttons
+    }
+
+    /// Gives the given buttons turbo: while held, they're reported pressed for `frames` frames,
+    /// then released for as many, and so on. 0 turns turbo off again.
+    pub fn set_turbo(&mut self, buttons: Buttons, frames: u32) {
+        for (bit, turbo) in self.turbo.iter_mut().enumerate() {
+            if buttons.bits() & 1 << bit != 0 {
+                *turbo = frames;
+            }
+        }
+    }
+
+    /// Tells the controller how many frames the PPU has completed, which turbo counts in.
+    pub fn set_frame(&mut self, frame: u64) {
+        self.frame = frame;
+        if self.strobe {
+            self.shift = self.pressed().bits();
+        }
+    }
+
+    /// The buttons reported as pressed: the ones held down, minus turbo buttons that are in their
+    /// released phase.
+    pub fn pressed(&self) -> Buttons {
+        let mut released = 0;
+        for (bit, &turbo) in self.turbo.iter().enumerate() {
+            if turbo != 0 && (self.frame / turbo as u64) % 2 == 1 {
+                released |= 1 << bit;
+            }
+        }
+        self.buttons - Buttons::from_bits_retain(released)
+    }
+
+    /// Whether the strobe is high, which keeps the controller reloading.
+    pub fn strobe(&self) -> bool {
+        self.strobe
+    }
+
+    /// Handles a write to $4016. Only bit 0 (the strobe) is relevant.
+    pub fn write(&mut self, value: u8) {
+        self.strobe = value & 1 == 1;
+        if self.strobe {
+            self.shift = self.pressed().bits();
+        }
+    }
+
+    /// The value the next read will return, without shifting.
+    pub fn peek(&self) -> u8 {
+        if self.strobe {
+            return self.pressed().contains(Buttons::A) as u8;
+        }
+        self.shift & 1
+    }
+
+    /// Shifts out the next button's state in bit 0.
+    pub fn read(&mut self) -> u8 {
+        if self.strobe {
+            return self.pressed().contains(Buttons::A) as u8;
+        }
+        let result = self.shift & 1;
+        // Official controllers shift in 1s once all 8 buttons have been read
+        self.shift = self.shift >> 1 | 0x80;
+        result
+    }
+}
+
+impl Stateful for Controller {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.buttons.bits());
+        state.u8(self.shift);
+        state.bool(self.strobe);
+        for &turbo in &self.turbo {
+            state.u32(turbo);
+        }
+        state.u64(self.frame);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.buttons = Buttons::from_bits_retain(state.u8()?);
+        self.shift = state.u8()?;
+        self.strobe = state.bool()?;
+        for turbo in &mut self.turbo {
+            *turbo = state.u32()?;
+        }
+        self.frame = state.u64()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads the 8 buttons out, in shift order
+    fn read_out(controller: &mut Controller) -> u8 {
+        (0..8).fold(0, |buttons, bit| buttons | controller.read() << bit)
+    }
+
+    #[test]
+    fn reads_come_from_the_strobe_snapshot() {
+        let mut controller = Controller::new();
+        controller.set_buttons(Buttons::A | Buttons::UP);
+        controller.write(1);
+        controller.write(0);
+        controller.set_buttons(Buttons::B | Buttons::START);
+        assert_eq!(read_out(&mut controller), (Buttons::A | Buttons::UP).bits());
+        // Past the 8 buttons, official controllers report 1s
+        assert_eq!(controller.read(), 1);
+    }
+
+    #[test]
+    fn turbo_alternates_every_period() {
+        let mut controller = Controller::new();
+        controller.set_buttons(Buttons::A | Buttons::B);
+        controller.set_turbo(Buttons::A, 3);
+        let pressed: Vec<Buttons> = (0..12)
+            .map(|frame| {
+                controller.set_frame(frame);
+                controller.pressed()
+            })
+            .collect();
+        let held = Buttons::A | Buttons::B;
+        assert_eq!(pressed, [[held; 3], [Buttons::B; 3], [held; 3], [Buttons::B; 3]].concat());
+
+        controller.set_turbo(Buttons::A, 0);
+        assert_eq!(controller.pressed(), held);
+    }
+}
@@ -0,0 +1,68 @@
+use std::rc::Rc;
+use crate::hardware::MemoryBus;
+use super::MOS6502;
+use super::status::Status;
+
+/// The register values `MOS6502::reset` starts execution with. The defaults match a NES at
+/// power-on; test programs that expect something else can override them with
+/// `MOS6502::with_config`.
+///
+/// RAM isn't part of the CPU, so its power-on contents are up to the bus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CpuConfig {
+    /// Stack pointer after reset. The reset sequence pushes three times (without writing) from
+    /// $00, leaving $FD.
+    pub stack_ptr: u8,
+    /// Status register after reset. The unused bit is set regardless, as it always reads as 1.
+    pub status: u8,
+    /// Where to start executing, instead of the address in the reset vector. Handy for test ROMs
+    /// like nestest that have an automated entry point.
+    pub start_pc: Option<u16>,
+}
+
+impl Default for CpuConfig {
+    fn default() -> Self {
+        Self {
+            stack_ptr: 0xFD,
+            // IRQs stay masked until the program clears I
+            status: (Status::UNUSED | Status::INTERRUPT).bits(),
+            start_pc: None,
+        }
+    }
+}
+
+impl MOS6502 {
+    /// Constructs a CPU like `new`, but resetting into the given register values instead of the
+    /// NES's.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+    /// let config = CpuConfig { start_pc: Some(0x0400), stack_ptr: 0xFF, ..CpuConfig::default() };
+    /// let mut my_cpu = MOS6502::with_config(my_bus.clone(), config);
+    ///
+    /// // INX at $0400
+    /// my_bus.borrow().write(0x0400, 0xE8);
+    /// my_cpu.reset().unwrap();
+    /// assert_eq!(my_cpu.pc(), 0x0400);
+    /// assert_eq!(my_cpu.stack_pointer(), 0xFF);
+    /// my_cpu.step_instruction().unwrap();
+    /// assert_eq!(my_cpu.x(), 1);
+    /// assert_eq!(my_cpu.pc(), 0x0401);
+    /// ```
+    pub fn with_config(bus: Rc<dyn MemoryBus>, config: CpuConfig) -> Self {
+        let mut cpu = Self::new(bus);
+        cpu.config = config;
+        cpu
+    }
+
+    /// The register values `reset` starts with.
+    pub fn config(&self) -> CpuConfig {
+        self.config
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+use crate::disasm;
+use super::MOS6502;
+
+/// How many upcoming instructions the `Debug` output disassembles
+const UPCOMING_INSTRUCTIONS: usize = 3;
+
+impl MOS6502 {
+    /// The status register as letters, `NVUBDIZC` from bit 7 down to bit 0: uppercase for flags
+    /// that are set, lowercase for ones that aren't.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+    /// let mut my_cpu = MOS6502::new(my_bus.clone());
+    /// my_cpu.reset().unwrap();
+    /// assert_eq!(my_cpu.status_flags(), "nvUbdIzc");
+    /// ```
+    pub fn status_flags(&self) -> String {
+        format!("{:?}", self.status)
+    }
+}
+
+/// Shows the registers on one line, like a trace log: `A:00 X:00 Y:00 P:nvUbdIzc SP:FD PC:C000`.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::cell::RefCell;
+/// use std::rc::Rc;
+/// use rust_nes::hardware::*;
+///
+/// let my_bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+/// // Starting with V, D and C set
+/// let config = CpuConfig { start_pc: Some(0x0400), status: 0x49, ..CpuConfig::default() };
+/// let mut my_cpu = MOS6502::with_config(my_bus.clone(), config);
+///
+/// // LDA #$80, LDX #$7F, LDY #$00
+/// for (offset, byte) in [0xA9, 0x80, 0xA2, 0x7F, 0xA0, 0x00].into_iter().enumerate() {
+///     my_bus.borrow().write(0x0400 + offset as u16, byte);
+/// }
+/// my_cpu.reset().unwrap();
+/// my_cpu.run_instructions(3).unwrap();
+/// assert_eq!(my_cpu.to_string(), "A:80 X:7F Y:00 P:nVUbDiZC SP:FD PC:0406");
+/// ```
+impl fmt::Display for MOS6502 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "A:{:02X} X:{:02X} Y:{:02X} P:{:?} SP:{:02X} PC:{:04X}",
+            self.a, self.x, self.y, self.status, self.stack_ptr, self.program_counter,
+        )
+    }
+}
+
+/// Shows the registers, the decoded status flags and the next few instructions (read without side
+/// effects), for panic and assertion messages. `{:#?}` spreads it over several lines.
+impl fmt::Debug for MOS6502 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let upcoming: Vec<String> = disasm::disassemble_range(|address| self.bus.peek(address), self.program_counter, 0xFFFF)
+            .take(UPCOMING_INSTRUCTIONS)
+            .map(|(address, text, _)| format!("{:04X}: {}", address, text))
+            .collect();
+        f.debug_struct("MOS6502")
+            .field("pc", &format_args!("${:04X}", self.program_counter))
+            .field("a", &format_args!("${:02X}", self.a))
+            .field("x", &format_args!("${:02X}", self.x))
+            .field("y", &format_args!("${:02X}", self.y))
+            .field("sp", &format_args!("${:02X}", self.stack_ptr))
+            .field("p", &format_args!("${:02X} ({:?})", self.status.bits(), self.status))
+            .field("cycles", &self.cycles)
+            .field("upcoming", &upcoming)
+            .finish()
+    }
+}
@@ -1,57 +1,190 @@
-use super::MOS6502;
+use crate::disasm::AddressingMode;
+use super::micro_ops::MicroOp;
 
-pub(crate) const MAX_INSTR_CYCLES: usize = 6;
+/// The most cycles an instruction can spend after its fetch cycle. The longest instructions are
+/// the unofficial read-modify-writes through a zero-page pointer, at 8 cycles in total.
+pub(crate) const MAX_INSTR_CYCLES: usize = 7;
 
 /// Takes in a mutable list of `InstrDef`s as its first parameter, then a sequence of opcode numbers
-/// followed by their micro-operations.
+/// followed by their mnemonic, `AddressingMode` variant and micro-operations.
+///
+/// The micro-operations may be preceded by any number of addressing mode constants (like `ZPG` or
+/// `ABS_RMW`), each joined with `+`, whose micro-operations come first.
+///
+/// Listing an opcode twice, or giving an instruction more than `MAX_INSTR_CYCLES`
+/// micro-operations, fails to compile.
+///
+/// # Examples
+///
+/// `InstrDef`, `MicroOp`, `AddressingMode` and `MAX_INSTR_CYCLES` are looked up where the macro
+/// is used. Outside this crate, stand-ins will do:
+///
+/// ```rust
+/// use rust_nes::disasm::AddressingMode;
+/// use rust_nes::hardware::cpu::MicroOp;
+///
+/// const MAX_INSTR_CYCLES: usize = 7;
+///
+/// #[derive(Clone, Copy)]
+/// struct InstrDef(usize);
+///
+/// impl InstrDef {
+///     fn from(_: &str, _: AddressingMode, ops: &[MicroOp]) -> Self {
+///         Self(ops.len())
+///     }
+/// }
+///
+/// let mut instrs = [InstrDef(0); 256];
+/// rust_nes::opcodes!(instrs, {
+///     0xA5 => LDA ZeroPage: [imm_zal, zal_lda],
+///     0xEA => NOP Implied: [nop],
+/// });
+/// assert_eq!(instrs[0xA5].0, 2);
+/// ```
+///
+/// An instruction with 8 micro-operations doesn't fit:
+///
+/// ```compile_fail
+/// # use rust_nes::disasm::AddressingMode;
+/// # use rust_nes::hardware::cpu::MicroOp;
+/// # const MAX_INSTR_CYCLES: usize = 7;
+/// # #[derive(Clone, Copy)]
+/// # struct InstrDef(usize);
+/// # impl InstrDef {
+/// #     fn from(_: &str, _: AddressingMode, ops: &[MicroOp]) -> Self { Self(ops.len()) }
+/// # }
+/// let mut instrs = [InstrDef(0); 256];
+/// rust_nes::opcodes!(instrs, {
+///     0xEA => NOP Implied: [nop, nop, nop, nop, nop, nop, nop, nop],
+/// });
+/// ```
+///
+/// Neither does an opcode defined twice:
+///
+/// ```compile_fail
+/// # use rust_nes::disasm::AddressingMode;
+/// # use rust_nes::hardware::cpu::MicroOp;
+/// # const MAX_INSTR_CYCLES: usize = 7;
+/// # #[derive(Clone, Copy)]
+/// # struct InstrDef(usize);
+/// # impl InstrDef {
+/// #     fn from(_: &str, _: AddressingMode, ops: &[MicroOp]) -> Self { Self(ops.len()) }
+/// # }
+/// let mut instrs = [InstrDef(0); 256];
+/// rust_nes::opcodes!(instrs, {
+///     0xEA => NOP Implied: [nop],
+///     0xEA => NOP Implied: [nop],
+/// });
+/// ```
 #[macro_export]
 macro_rules! opcodes {
-    // We take the 'instrs' array plus a block of `opcode => [microops...]` lines.
+    // We take the 'instrs' array plus a block of `opcode => MNEMONIC Mode: MODE + [microops...]` lines.
     ($instrs:expr, {
-        $($opcode:literal => [$($microop:ident),*]),+ $(,)?
+        $($opcode:literal => $mnemonic:ident $addressing:ident: $($mode:ident +)* [$($microop:ident),*]),+ $(,)?
     }) => {
+        const _: () = {
+            let opcodes: &[u8] = &[$($opcode),+];
+            let mut i = 0;
+            while i < opcodes.len() {
+                let mut j = i + 1;
+                while j < opcodes.len() {
+                    assert!(opcodes[i] != opcodes[j], "an opcode is defined more than once");
+                    j += 1;
+                }
+                i += 1;
+            }
+        };
         $(
+            const _: () = assert!(
+                0 $(+ $mode.len())* + <[&str]>::len(&[$(stringify!($microop)),*]) <= MAX_INSTR_CYCLES,
+                concat!("opcode ", stringify!($opcode), " (", stringify!($mnemonic), ") has more micro-operations than MAX_INSTR_CYCLES"),
+            );
             $instrs[$opcode as usize] = {
-                let ops: &[fn(&mut MOS6502)] = &[
-                    $(Self::$microop),*
+                let parts: &[&[MicroOp]] = &[
+                    $($mode,)*
+                    &[$(MicroOp::$microop),*],
                 ];
-                InstrDef::from(ops)
+                InstrDef::from(stringify!($mnemonic), AddressingMode::$addressing, &parts.concat())
             };
         )+
     }
 }
 
+// ADDRESSING MODES //
+// The cycles that put an instruction's effective address into the zero-page or absolute address
+// latch. Indexed absolute modes always spend the cycle that fixes up the high byte, as stores and
+// read-modify-writes do; loads only spend it on page crossings, so they list their own.
+
+/// Zero-page
+pub(crate) const ZPG: &[MicroOp] = &[MicroOp::imm_zal];
+/// Zero-page, indexed by X
+pub(crate) const ZPG_X: &[MicroOp] = &[MicroOp::imm_zal, MicroOp::add_x_zal];
+/// Zero-page, indexed by Y
+pub(crate) const ZPG_Y: &[MicroOp] = &[MicroOp::imm_zal, MicroOp::add_y_zal];
+/// Absolute
+pub(crate) const ABS: &[MicroOp] = &[MicroOp::imm_lo_aal, MicroOp::imm_hi_aal];
+/// Absolute, indexed by X
+pub(crate) const ABS_X: &[MicroOp] = &[MicroOp::imm_lo_aal, MicroOp::imm_hi_aal, MicroOp::add_x_aal];
+/// Absolute, indexed by Y
+pub(crate) const ABS_Y: &[MicroOp] = &[MicroOp::imm_lo_aal, MicroOp::imm_hi_aal, MicroOp::add_y_aal];
+/// X-indexed zero-page pointer
+pub(crate) const IND_X: &[MicroOp] = &[MicroOp::imm_zal, MicroOp::add_x_zal, MicroOp::ind_lo_aal, MicroOp::ind_hi_aal];
+/// Zero-page pointer, indexed by Y
+pub(crate) const IND_Y: &[MicroOp] = &[MicroOp::imm_zal, MicroOp::ind_lo_aal, MicroOp::ind_hi_aal, MicroOp::add_y_aal];
+
+// READ-MODIFY-WRITE //
+// Follow the addressing mode of a read-modify-write instruction: read the operand into the data
+// latch, then write it back unmodified while it's being modified. The final cycle writes the
+// result (see `rmw_ops!`).
+
+/// Read-modify-write at the zero-page address latch
+pub(crate) const ZPG_RMW: &[MicroOp] = &[MicroOp::zal_dl, MicroOp::dl_zal];
+/// Read-modify-write at the absolute address latch
+pub(crate) const ABS_RMW: &[MicroOp] = &[MicroOp::aal_dl, MicroOp::dl_aal];
+
 /// Const-sized struct for storing an instruction definition.
 #[derive(Clone, Copy)]
 pub struct InstrDef {
+    /// Mnemonic, e.g. `"LDA"`. Unofficial opcodes go by their most common names.
+    pub mnemonic: &'static str,
+    /// How the operand is interpreted, which also gives the instruction's length
+    pub mode: AddressingMode,
     pub cycles: usize,
-    pub u_ops: [Option<fn(&mut MOS6502) -> ()>; MAX_INSTR_CYCLES]
+    pub u_ops: [Option<MicroOp>; MAX_INSTR_CYCLES]
 }
 
 impl InstrDef {
+    /// The definition of the opcodes that lock the CPU up, which have no micro-operations (see
+    /// `MOS6502::instruction_table`).
+    pub(crate) const JAM: Self = Self {
+        mnemonic: "JAM",
+        mode: AddressingMode::Implied,
+        cycles: 0,
+        u_ops: [None; MAX_INSTR_CYCLES],
+    };
+
     /// Helper function for generating definitions easily.
     ///
     /// NOTE that the actual processing of an instruction is 1 less cycle than how long it takes on
     /// paper; the first cycle is actually fetching the instruction.
-    pub(crate) fn from(ops: &[fn(&mut MOS6502)]) -> Self {
-        debug_assert!(ops.len() <= MAX_INSTR_CYCLES, "The amount of operations must be less than or equal to {}\nEither condense the instruction or modify MAX_INSTR_CYCLES", MAX_INSTR_CYCLES);
-        //if ops.len() > MAX_INSTR_CYCLES {
-        //    compile_error!()
-        //}
+    pub(crate) fn from(mnemonic: &'static str, mode: AddressingMode, ops: &[MicroOp]) -> Self {
+        assert!(ops.len() <= MAX_INSTR_CYCLES, "The amount of operations must be less than or equal to {}\nEither condense the instruction or modify MAX_INSTR_CYCLES", MAX_INSTR_CYCLES);
         let mut u_ops = [None; MAX_INSTR_CYCLES];
         for (i, &op) in ops.iter().enumerate() {
             u_ops[i] = Some(op);
         }
         Self {
+            mnemonic,
+            mode,
             cycles: ops.len(),
             u_ops,
         }
     }
 
-    /// Returns the InstrDef's micro-operations as a vector
+    /// Returns the InstrDef's micro-operations, in execution order
     /// (Remember that `InstrDef` is const sized)
-    pub(crate) fn as_vec(&self) -> Vec<fn(&mut MOS6502)> {
-        self.u_ops[0..self.cycles].iter().map(|&it| it.unwrap()).collect()
+    pub(crate) fn u_ops(&self) -> impl Iterator<Item = MicroOp> + '_ {
+        self.u_ops[0..self.cycles].iter().flatten().copied()
     }
 }
 
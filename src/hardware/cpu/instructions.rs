@@ -1,60 +1,400 @@
+use crate::disasm::AddressingMode;
 use crate::opcodes;
 use super::MOS6502;
 use super::instr_def::*;
+use super::micro_ops::MicroOp;
 
 impl MOS6502 {
     /// Here we define each CPU opcode by what it does during each cycle of its execution. Each
-    /// opcode is represented simply by a list of function references. As seen in the definition of
-    /// `InstrDef`, the function signatures must be `fn(&mut MOS6502) -> ()`.
+    /// opcode is represented simply by a list of micro-operations, named after the `MOS6502`
+    /// methods that implement them (see `MicroOp`), along with its mnemonic and addressing mode,
+    /// which the disassembler goes by.
+    ///
+    /// Unofficial opcodes are included too, as some games and many test ROMs use them. Most are
+    /// stable combinations of official operations, but those marked unstable (ANE, LXA, SHA, SHX,
+    /// SHY and TAS) depend on analog effects that vary between chips; they're emulated the way
+    /// most 2A03s behave. The JAM opcodes, which lock the CPU up, are left undefined so that
+    /// `step` reports them as `InvalidOpcode`.
     ///
     /// See [6502 Instruction Set](https://www.masswerk.at/6502/6502_instruction_set.html) for info.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_nes::disasm::AddressingMode;
+    /// use rust_nes::hardware::MOS6502;
+    ///
+    /// let instructions = MOS6502::instruction_table();
+    /// assert_eq!((instructions[0xBD].mnemonic, instructions[0xBD].mode), ("LDA", AddressingMode::AbsoluteX));
+    /// assert_eq!((instructions[0x6C].mnemonic, instructions[0x6C].mode), ("JMP", AddressingMode::Indirect));
+    /// assert_eq!((instructions[0x0A].mnemonic, instructions[0x0A].mode), ("ASL", AddressingMode::Accumulator));
+    /// assert_eq!((instructions[0xB7].mnemonic, instructions[0xB7].mode), ("LAX", AddressingMode::ZeroPageY));
+    /// assert_eq!(instructions[0x02].mnemonic, "JAM");
+    /// ```
     pub fn instruction_table() -> [InstrDef; 256] {
-        let mut instrs: [InstrDef; 256] = [InstrDef{cycles: 0, u_ops: [None; MAX_INSTR_CYCLES]}; 256];
+        let mut instrs: [InstrDef; 256] = [InstrDef::JAM; 256];
 
         opcodes!(instrs, {
-            0x81 => [imm_zal, add_x_zal, ind_lo_aal, ind_hi_aal, aal_sta],      // STA X, ind
-            0x84 => [imm_zal, zal_sty],                                         // STY zpg
-            0x85 => [imm_zal, zal_sta],                                         // STA zpg
-            0x86 => [imm_zal, zal_stx],                                         // STX zpg
-            0x8A => [txa],                                                      // TXA impl
-            0x8C => [imm_lo_aal, imm_hi_aal, aal_sty],                          // STY abs
-            0x8D => [imm_lo_aal, imm_hi_aal, aal_sta],                          // STA abs
-            0x8E => [imm_lo_aal, imm_hi_aal, aal_stx],                          // STX abs
-
-            0x91 => [imm_zal, ind_lo_aal, ind_hi_aal, add_y_aal, aal_sta],      // STA ind, Y
-            0x94 => [imm_zal, add_x_zal, zal_sty],                              // STY zpg, X
-            0x95 => [imm_zal, add_x_zal, zal_sta],                              // STA zpg, X
-            0x96 => [imm_zal, add_y_zal, zal_stx],                              // STX zpg, Y
-            0x98 => [tya],                                                      // TYA impl
-            0x99 => [imm_lo_aal, imm_hi_aal, add_y_aal, aal_sta],               // STA abs, Y
-            0x9D => [imm_lo_aal, imm_hi_aal, add_x_aal, aal_sta],               // STA abs, X
-
-            0xA0 => [imm_y],                                                    // LDY #
-            0xA1 => [imm_zal, add_x_zal, ind_lo_aal, ind_hi_aal, aal_lda],      // LDA X,ind
-            0xA2 => [imm_x],                                                    // LDX #
-            0xA4 => [imm_zal, zal_ldy],                                         // LDY zpg
-            0xA5 => [imm_zal, zal_lda],                                         // LDA zpg
-            0xA6 => [imm_zal, zal_ldx],                                         // LDX zpg
-            0xA8 => [tay],                                                      // TAY impl
-            0xA9 => [imm_a],                                                    // LDA #
-            0xAA => [tax],                                                      // TAX impl
-            0xAC => [imm_lo_aal, imm_hi_aal, aal_ldy],                          // LDY abs
-            0xAD => [imm_lo_aal, imm_hi_aal, aal_lda],                          // LDA abs
-            0xAE => [imm_lo_aal, imm_hi_aal, aal_ldx],                          // LDX abs
-
-            0xB1 => [imm_zal, ind_lo_aal, ind_hi_aal, y_aal_lda],               // LDA ind, Y
-            0xB4 => [imm_zal, add_x_zal, zal_ldy],                              // LDY zpg, X
-            0xB5 => [imm_zal, add_x_zal, zal_lda],                              // LDA zpg, X
-            0xB6 => [imm_zal, add_y_zal, zal_ldx],                              // LDX zpg, Y
-            0xB9 => [imm_lo_aal, imm_hi_aal, y_aal_lda],                        // LDA abs, Y
-            0xBC => [imm_lo_aal, imm_hi_aal, x_aal_ldy],                        // LDY abs, X
-            0xBD => [imm_lo_aal, imm_hi_aal, x_aal_lda],                        // LDA abs, X
-            0xBE => [imm_lo_aal, imm_hi_aal, y_aal_ldx],                        // LDX abs, Y
-
-            0xEA => [nop],                                                      // NOP
+            0x00 => BRK Implied:     [imm_nop, push_pch, push_pcl, push_p_brk, irq_lo_aal, irq_hi_pc],   // BRK impl
+            0x01 => ORA IndirectX:   [imm_zal, add_x_zal, ind_lo_aal, ind_hi_aal, aal_ora],              // ORA X,ind
+            0x03 => SLO IndirectX:   IND_X + ABS_RMW + [aal_slo],                                        // SLO X,ind (unofficial)
+            0x04 => NOP ZeroPage:    [imm_zal, zal_nop],                                                 // NOP zpg (unofficial)
+            0x05 => ORA ZeroPage:    [imm_zal, zal_ora],                                                 // ORA zpg
+            0x06 => ASL ZeroPage:    ZPG + ZPG_RMW + [zal_asl],                                          // ASL zpg
+            0x07 => SLO ZeroPage:    ZPG + ZPG_RMW + [zal_slo],                                          // SLO zpg (unofficial)
+            0x08 => PHP Implied:     [read_pc, push_p_brk],                                              // PHP impl
+            0x09 => ORA Immediate:   [imm_ora],                                                          // ORA #
+            0x0A => ASL Accumulator: [asl_a],                                                            // ASL A
+            0x0B => ANC Immediate:   [imm_anc],                                                          // ANC # (unofficial)
+            0x0C => NOP Absolute:    [imm_lo_aal, imm_hi_aal, aal_nop],                                  // NOP abs (unofficial)
+            0x0D => ORA Absolute:    [imm_lo_aal, imm_hi_aal, aal_ora],                                  // ORA abs
+            0x0E => ASL Absolute:    ABS + ABS_RMW + [aal_asl],                                          // ASL abs
+            0x0F => SLO Absolute:    ABS + ABS_RMW + [aal_slo],                                          // SLO abs (unofficial)
+
+            0x10 => BPL Relative:    [bpl],                                                              // BPL rel
+            0x11 => ORA IndirectY:   [imm_zal, ind_lo_aal, ind_hi_aal, y_aal_ora],                       // ORA ind,Y
+            0x13 => SLO IndirectY:   IND_Y + ABS_RMW + [aal_slo],                                        // SLO ind,Y (unofficial)
+            0x14 => NOP ZeroPageX:   [imm_zal, add_x_zal, zal_nop],                                      // NOP zpg,X (unofficial)
+            0x15 => ORA ZeroPageX:   [imm_zal, add_x_zal, zal_ora],                                      // ORA zpg,X
+            0x16 => ASL ZeroPageX:   ZPG_X + ZPG_RMW + [zal_asl],                                        // ASL zpg,X
+            0x17 => SLO ZeroPageX:   ZPG_X + ZPG_RMW + [zal_slo],                                        // SLO zpg,X (unofficial)
+            0x18 => CLC Implied:     [clc],                                                              // CLC impl
+            0x19 => ORA AbsoluteY:   [imm_lo_aal, imm_hi_aal, y_aal_ora],                                // ORA abs,Y
+            0x1A => NOP Implied:     [nop],                                                              // NOP (unofficial)
+            0x1B => SLO AbsoluteY:   ABS_Y + ABS_RMW + [aal_slo],                                        // SLO abs,Y (unofficial)
+            0x1C => NOP AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_nop],                                // NOP abs,X (unofficial)
+            0x1D => ORA AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_ora],                                // ORA abs,X
+            0x1E => ASL AbsoluteX:   ABS_X + ABS_RMW + [aal_asl],                                        // ASL abs,X
+            0x1F => SLO AbsoluteX:   ABS_X + ABS_RMW + [aal_slo],                                        // SLO abs,X (unofficial)
+
+            0x20 => JSR Absolute:    [imm_lo_aal, stack_dummy, push_pch, push_pcl, imm_hi_pc],           // JSR abs
+            0x21 => AND IndirectX:   [imm_zal, add_x_zal, ind_lo_aal, ind_hi_aal, aal_and],              // AND X,ind
+            0x23 => RLA IndirectX:   IND_X + ABS_RMW + [aal_rla],                                        // RLA X,ind (unofficial)
+            0x24 => BIT ZeroPage:    [imm_zal, zal_bit],                                                 // BIT zpg
+            0x25 => AND ZeroPage:    [imm_zal, zal_and],                                                 // AND zpg
+            0x26 => ROL ZeroPage:    ZPG + ZPG_RMW + [zal_rol],                                          // ROL zpg
+            0x27 => RLA ZeroPage:    ZPG + ZPG_RMW + [zal_rla],                                          // RLA zpg (unofficial)
+            0x28 => PLP Implied:     [read_pc, stack_dummy, pull_p],                                     // PLP impl
+            0x29 => AND Immediate:   [imm_and],                                                          // AND #
+            0x2A => ROL Accumulator: [rol_a],                                                            // ROL A
+            0x2B => ANC Immediate:   [imm_anc],                                                          // ANC # (unofficial)
+            0x2C => BIT Absolute:    [imm_lo_aal, imm_hi_aal, aal_bit],                                  // BIT abs
+            0x2D => AND Absolute:    [imm_lo_aal, imm_hi_aal, aal_and],                                  // AND abs
+            0x2E => ROL Absolute:    ABS + ABS_RMW + [aal_rol],                                          // ROL abs
+            0x2F => RLA Absolute:    ABS + ABS_RMW + [aal_rla],                                          // RLA abs (unofficial)
+
+            0x30 => BMI Relative:    [bmi],                                                              // BMI rel
+            0x31 => AND IndirectY:   [imm_zal, ind_lo_aal, ind_hi_aal, y_aal_and],                       // AND ind,Y
+            0x33 => RLA IndirectY:   IND_Y + ABS_RMW + [aal_rla],                                        // RLA ind,Y (unofficial)
+            0x34 => NOP ZeroPageX:   [imm_zal, add_x_zal, zal_nop],                                      // NOP zpg,X (unofficial)
+            0x35 => AND ZeroPageX:   [imm_zal, add_x_zal, zal_and],                                      // AND zpg,X
+            0x36 => ROL ZeroPageX:   ZPG_X + ZPG_RMW + [zal_rol],                                        // ROL zpg,X
+            0x37 => RLA ZeroPageX:   ZPG_X + ZPG_RMW + [zal_rla],                                        // RLA zpg,X (unofficial)
+            0x38 => SEC Implied:     [sec],                                                              // SEC impl
+            0x39 => AND AbsoluteY:   [imm_lo_aal, imm_hi_aal, y_aal_and],                                // AND abs,Y
+            0x3A => NOP Implied:     [nop],                                                              // NOP (unofficial)
+            0x3B => RLA AbsoluteY:   ABS_Y + ABS_RMW + [aal_rla],                                        // RLA abs,Y (unofficial)
+            0x3C => NOP AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_nop],                                // NOP abs,X (unofficial)
+            0x3D => AND AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_and],                                // AND abs,X
+            0x3E => ROL AbsoluteX:   ABS_X + ABS_RMW + [aal_rol],                                        // ROL abs,X
+            0x3F => RLA AbsoluteX:   ABS_X + ABS_RMW + [aal_rla],                                        // RLA abs,X (unofficial)
+
+            0x40 => RTI Implied:     [read_pc, stack_dummy, pull_p, pull_pcl, pull_pch],                 // RTI impl
+            0x41 => EOR IndirectX:   [imm_zal, add_x_zal, ind_lo_aal, ind_hi_aal, aal_eor],              // EOR X,ind
+            0x43 => SRE IndirectX:   IND_X + ABS_RMW + [aal_sre],                                        // SRE X,ind (unofficial)
+            0x44 => NOP ZeroPage:    [imm_zal, zal_nop],                                                 // NOP zpg (unofficial)
+            0x45 => EOR ZeroPage:    [imm_zal, zal_eor],                                                 // EOR zpg
+            0x46 => LSR ZeroPage:    ZPG + ZPG_RMW + [zal_lsr],                                          // LSR zpg
+            0x47 => SRE ZeroPage:    ZPG + ZPG_RMW + [zal_sre],                                          // SRE zpg (unofficial)
+            0x48 => PHA Implied:     [read_pc, push_a],                                                  // PHA impl
+            0x49 => EOR Immediate:   [imm_eor],                                                          // EOR #
+            0x4A => LSR Accumulator: [lsr_a],                                                            // LSR A
+            0x4B => ALR Immediate:   [imm_alr],                                                          // ALR # (unofficial)
+            0x4C => JMP Absolute:    [imm_lo_aal, imm_hi_pc],                                            // JMP abs
+            0x4D => EOR Absolute:    [imm_lo_aal, imm_hi_aal, aal_eor],                                  // EOR abs
+            0x4E => LSR Absolute:    ABS + ABS_RMW + [aal_lsr],                                          // LSR abs
+            0x4F => SRE Absolute:    ABS + ABS_RMW + [aal_sre],                                          // SRE abs (unofficial)
+
+            0x50 => BVC Relative:    [bvc],                                                              // BVC rel
+            0x51 => EOR IndirectY:   [imm_zal, ind_lo_aal, ind_hi_aal, y_aal_eor],                       // EOR ind,Y
+            0x53 => SRE IndirectY:   IND_Y + ABS_RMW + [aal_sre],                                        // SRE ind,Y (unofficial)
+            0x54 => NOP ZeroPageX:   [imm_zal, add_x_zal, zal_nop],                                      // NOP zpg,X (unofficial)
+            0x55 => EOR ZeroPageX:   [imm_zal, add_x_zal, zal_eor],                                      // EOR zpg,X
+            0x56 => LSR ZeroPageX:   ZPG_X + ZPG_RMW + [zal_lsr],                                        // LSR zpg,X
+            0x57 => SRE ZeroPageX:   ZPG_X + ZPG_RMW + [zal_sre],                                        // SRE zpg,X (unofficial)
+            0x58 => CLI Implied:     [cli],                                                              // CLI impl
+            0x59 => EOR AbsoluteY:   [imm_lo_aal, imm_hi_aal, y_aal_eor],                                // EOR abs,Y
+            0x5A => NOP Implied:     [nop],                                                              // NOP (unofficial)
+            0x5B => SRE AbsoluteY:   ABS_Y + ABS_RMW + [aal_sre],                                        // SRE abs,Y (unofficial)
+            0x5C => NOP AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_nop],                                // NOP abs,X (unofficial)
+            0x5D => EOR AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_eor],                                // EOR abs,X
+            0x5E => LSR AbsoluteX:   ABS_X + ABS_RMW + [aal_lsr],                                        // LSR abs,X
+            0x5F => SRE AbsoluteX:   ABS_X + ABS_RMW + [aal_sre],                                        // SRE abs,X (unofficial)
+
+            0x60 => RTS Implied:     [read_pc, stack_dummy, pull_pcl, pull_pch, inc_pc],                 // RTS impl
+            0x61 => ADC IndirectX:   [imm_zal, add_x_zal, ind_lo_aal, ind_hi_aal, aal_adc],              // ADC X,ind
+            0x63 => RRA IndirectX:   IND_X + ABS_RMW + [aal_rra],                                        // RRA X,ind (unofficial)
+            0x64 => NOP ZeroPage:    [imm_zal, zal_nop],                                                 // NOP zpg (unofficial)
+            0x65 => ADC ZeroPage:    [imm_zal, zal_adc],                                                 // ADC zpg
+            0x66 => ROR ZeroPage:    ZPG + ZPG_RMW + [zal_ror],                                          // ROR zpg
+            0x67 => RRA ZeroPage:    ZPG + ZPG_RMW + [zal_rra],                                          // RRA zpg (unofficial)
+            0x68 => PLA Implied:     [read_pc, stack_dummy, pull_a],                                     // PLA impl
+            0x69 => ADC Immediate:   [imm_adc],                                                          // ADC #
+            0x6A => ROR Accumulator: [ror_a],                                                            // ROR A
+            0x6B => ARR Immediate:   [imm_arr],                                                          // ARR # (unofficial)
+            0x6C => JMP Indirect:    [imm_lo_aal, imm_hi_aal, aal_dl, jmp_ind_hi],                       // JMP ind
+            0x6D => ADC Absolute:    [imm_lo_aal, imm_hi_aal, aal_adc],                                  // ADC abs
+            0x6E => ROR Absolute:    ABS + ABS_RMW + [aal_ror],                                          // ROR abs
+            0x6F => RRA Absolute:    ABS + ABS_RMW + [aal_rra],                                          // RRA abs (unofficial)
+
+            0x70 => BVS Relative:    [bvs],                                                              // BVS rel
+            0x71 => ADC IndirectY:   [imm_zal, ind_lo_aal, ind_hi_aal, y_aal_adc],                       // ADC ind,Y
+            0x73 => RRA IndirectY:   IND_Y + ABS_RMW + [aal_rra],                                        // RRA ind,Y (unofficial)
+            0x74 => NOP ZeroPageX:   [imm_zal, add_x_zal, zal_nop],                                      // NOP zpg,X (unofficial)
+            0x75 => ADC ZeroPageX:   [imm_zal, add_x_zal, zal_adc],                                      // ADC zpg,X
+            0x76 => ROR ZeroPageX:   ZPG_X + ZPG_RMW + [zal_ror],                                        // ROR zpg,X
+            0x77 => RRA ZeroPageX:   ZPG_X + ZPG_RMW + [zal_rra],                                        // RRA zpg,X (unofficial)
+            0x78 => SEI Implied:     [sei],                                                              // SEI impl
+            0x79 => ADC AbsoluteY:   [imm_lo_aal, imm_hi_aal, y_aal_adc],                                // ADC abs,Y
+            0x7A => NOP Implied:     [nop],                                                              // NOP (unofficial)
+            0x7B => RRA AbsoluteY:   ABS_Y + ABS_RMW + [aal_rra],                                        // RRA abs,Y (unofficial)
+            0x7C => NOP AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_nop],                                // NOP abs,X (unofficial)
+            0x7D => ADC AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_adc],                                // ADC abs,X
+            0x7E => ROR AbsoluteX:   ABS_X + ABS_RMW + [aal_ror],                                        // ROR abs,X
+            0x7F => RRA AbsoluteX:   ABS_X + ABS_RMW + [aal_rra],                                        // RRA abs,X (unofficial)
+
+            0x80 => NOP Immediate:   [imm_nop],                                                          // NOP # (unofficial)
+            0x81 => STA IndirectX:   IND_X + [aal_sta],                                                  // STA X,ind
+            0x82 => NOP Immediate:   [imm_nop],                                                          // NOP # (unofficial)
+            0x83 => SAX IndirectX:   IND_X + [aal_sax],                                                  // SAX X,ind (unofficial)
+            0x84 => STY ZeroPage:    ZPG + [zal_sty],                                                    // STY zpg
+            0x85 => STA ZeroPage:    ZPG + [zal_sta],                                                    // STA zpg
+            0x86 => STX ZeroPage:    ZPG + [zal_stx],                                                    // STX zpg
+            0x87 => SAX ZeroPage:    ZPG + [zal_sax],                                                    // SAX zpg (unofficial)
+            0x88 => DEY Implied:     [dey],                                                              // DEY impl
+            0x89 => NOP Immediate:   [imm_nop],                                                          // NOP # (unofficial)
+            0x8A => TXA Implied:     [txa],                                                              // TXA impl
+            0x8B => ANE Immediate:   [imm_ane],                                                          // ANE # (unofficial, unstable)
+            0x8C => STY Absolute:    ABS + [aal_sty],                                                    // STY abs
+            0x8D => STA Absolute:    ABS + [aal_sta],                                                    // STA abs
+            0x8E => STX Absolute:    ABS + [aal_stx],                                                    // STX abs
+            0x8F => SAX Absolute:    ABS + [aal_sax],                                                    // SAX abs (unofficial)
+
+            0x90 => BCC Relative:    [bcc],                                                              // BCC rel
+            0x91 => STA IndirectY:   IND_Y + [aal_sta],                                                  // STA ind,Y
+            0x93 => SHA IndirectY:   IND_Y + [aal_sha],                                                  // SHA ind,Y (unofficial, unstable)
+            0x94 => STY ZeroPageX:   ZPG_X + [zal_sty],                                                  // STY zpg,X
+            0x95 => STA ZeroPageX:   ZPG_X + [zal_sta],                                                  // STA zpg,X
+            0x96 => STX ZeroPageY:   ZPG_Y + [zal_stx],                                                  // STX zpg,Y
+            0x97 => SAX ZeroPageY:   ZPG_Y + [zal_sax],                                                  // SAX zpg,Y (unofficial)
+            0x98 => TYA Implied:     [tya],                                                              // TYA impl
+            0x99 => STA AbsoluteY:   ABS_Y + [aal_sta],                                                  // STA abs,Y
+            0x9A => TXS Implied:     [txs],                                                              // TXS impl
+            0x9B => TAS AbsoluteY:   ABS_Y + [aal_tas],                                                  // TAS abs,Y (unofficial, unstable)
+            0x9C => SHY AbsoluteX:   ABS_X + [aal_shy],                                                  // SHY abs,X (unofficial, unstable)
+            0x9D => STA AbsoluteX:   ABS_X + [aal_sta],                                                  // STA abs,X
+            0x9E => SHX AbsoluteY:   ABS_Y + [aal_shx],                                                  // SHX abs,Y (unofficial, unstable)
+            0x9F => SHA AbsoluteY:   ABS_Y + [aal_sha],                                                  // SHA abs,Y (unofficial, unstable)
+
+            0xA0 => LDY Immediate:   [imm_y],                                                            // LDY #
+            0xA1 => LDA IndirectX:   [imm_zal, add_x_zal, ind_lo_aal, ind_hi_aal, aal_lda],              // LDA X,ind
+            0xA2 => LDX Immediate:   [imm_x],                                                            // LDX #
+            0xA3 => LAX IndirectX:   [imm_zal, add_x_zal, ind_lo_aal, ind_hi_aal, aal_lax],              // LAX X,ind (unofficial)
+            0xA4 => LDY ZeroPage:    [imm_zal, zal_ldy],                                                 // LDY zpg
+            0xA5 => LDA ZeroPage:    [imm_zal, zal_lda],                                                 // LDA zpg
+            0xA6 => LDX ZeroPage:    [imm_zal, zal_ldx],                                                 // LDX zpg
+            0xA7 => LAX ZeroPage:    [imm_zal, zal_lax],                                                 // LAX zpg (unofficial)
+            0xA8 => TAY Implied:     [tay],                                                              // TAY impl
+            0xA9 => LDA Immediate:   [imm_a],                                                            // LDA #
+            0xAA => TAX Implied:     [tax],                                                              // TAX impl
+            0xAB => LXA Immediate:   [imm_lxa],                                                          // LXA # (unofficial, unstable)
+            0xAC => LDY Absolute:    [imm_lo_aal, imm_hi_aal, aal_ldy],                                  // LDY abs
+            0xAD => LDA Absolute:    [imm_lo_aal, imm_hi_aal, aal_lda],                                  // LDA abs
+            0xAE => LDX Absolute:    [imm_lo_aal, imm_hi_aal, aal_ldx],                                  // LDX abs
+            0xAF => LAX Absolute:    [imm_lo_aal, imm_hi_aal, aal_lax],                                  // LAX abs (unofficial)
+
+            0xB0 => BCS Relative:    [bcs],                                                              // BCS rel
+            0xB1 => LDA IndirectY:   [imm_zal, ind_lo_aal, ind_hi_aal, y_aal_lda],                       // LDA ind,Y
+            0xB3 => LAX IndirectY:   [imm_zal, ind_lo_aal, ind_hi_aal, y_aal_lax],                       // LAX ind,Y (unofficial)
+            0xB4 => LDY ZeroPageX:   [imm_zal, add_x_zal, zal_ldy],                                      // LDY zpg,X
+            0xB5 => LDA ZeroPageX:   [imm_zal, add_x_zal, zal_lda],                                      // LDA zpg,X
+            0xB6 => LDX ZeroPageY:   [imm_zal, add_y_zal, zal_ldx],                                      // LDX zpg,Y
+            0xB7 => LAX ZeroPageY:   [imm_zal, add_y_zal, zal_lax],                                      // LAX zpg,Y (unofficial)
+            0xB8 => CLV Implied:     [clv],                                                              // CLV impl
+            0xB9 => LDA AbsoluteY:   [imm_lo_aal, imm_hi_aal, y_aal_lda],                                // LDA abs,Y
+            0xBA => TSX Implied:     [tsx],                                                              // TSX impl
+            0xBB => LAS AbsoluteY:   [imm_lo_aal, imm_hi_aal, y_aal_las],                                // LAS abs,Y (unofficial)
+            0xBC => LDY AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_ldy],                                // LDY abs,X
+            0xBD => LDA AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_lda],                                // LDA abs,X
+            0xBE => LDX AbsoluteY:   [imm_lo_aal, imm_hi_aal, y_aal_ldx],                                // LDX abs,Y
+            0xBF => LAX AbsoluteY:   [imm_lo_aal, imm_hi_aal, y_aal_lax],                                // LAX abs,Y (unofficial)
+
+            0xC0 => CPY Immediate:   [imm_cpy],                                                          // CPY #
+            0xC1 => CMP IndirectX:   [imm_zal, add_x_zal, ind_lo_aal, ind_hi_aal, aal_cmp],              // CMP X,ind
+            0xC2 => NOP Immediate:   [imm_nop],                                                          // NOP # (unofficial)
+            0xC3 => DCP IndirectX:   IND_X + ABS_RMW + [aal_dcp],                                        // DCP X,ind (unofficial)
+            0xC4 => CPY ZeroPage:    [imm_zal, zal_cpy],                                                 // CPY zpg
+            0xC5 => CMP ZeroPage:    [imm_zal, zal_cmp],                                                 // CMP zpg
+            0xC6 => DEC ZeroPage:    ZPG + ZPG_RMW + [zal_dec],                                          // DEC zpg
+            0xC7 => DCP ZeroPage:    ZPG + ZPG_RMW + [zal_dcp],                                          // DCP zpg (unofficial)
+            0xC8 => INY Implied:     [iny],                                                              // INY impl
+            0xC9 => CMP Immediate:   [imm_cmp],                                                          // CMP #
+            0xCA => DEX Implied:     [dex],                                                              // DEX impl
+            0xCB => SBX Immediate:   [imm_sbx],                                                          // SBX # (unofficial)
+            0xCC => CPY Absolute:    [imm_lo_aal, imm_hi_aal, aal_cpy],                                  // CPY abs
+            0xCD => CMP Absolute:    [imm_lo_aal, imm_hi_aal, aal_cmp],                                  // CMP abs
+            0xCE => DEC Absolute:    ABS + ABS_RMW + [aal_dec],                                          // DEC abs
+            0xCF => DCP Absolute:    ABS + ABS_RMW + [aal_dcp],                                          // DCP abs (unofficial)
+
+            0xD0 => BNE Relative:    [bne],                                                              // BNE rel
+            0xD1 => CMP IndirectY:   [imm_zal, ind_lo_aal, ind_hi_aal, y_aal_cmp],                       // CMP ind,Y
+            0xD3 => DCP IndirectY:   IND_Y + ABS_RMW + [aal_dcp],                                        // DCP ind,Y (unofficial)
+            0xD4 => NOP ZeroPageX:   [imm_zal, add_x_zal, zal_nop],                                      // NOP zpg,X (unofficial)
+            0xD5 => CMP ZeroPageX:   [imm_zal, add_x_zal, zal_cmp],                                      // CMP zpg,X
+            0xD6 => DEC ZeroPageX:   ZPG_X + ZPG_RMW + [zal_dec],                                        // DEC zpg,X
+            0xD7 => DCP ZeroPageX:   ZPG_X + ZPG_RMW + [zal_dcp],                                        // DCP zpg,X (unofficial)
+            0xD8 => CLD Implied:     [cld],                                                              // CLD impl
+            0xD9 => CMP AbsoluteY:   [imm_lo_aal, imm_hi_aal, y_aal_cmp],                                // CMP abs,Y
+            0xDA => NOP Implied:     [nop],                                                              // NOP (unofficial)
+            0xDB => DCP AbsoluteY:   ABS_Y + ABS_RMW + [aal_dcp],                                        // DCP abs,Y (unofficial)
+            0xDC => NOP AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_nop],                                // NOP abs,X (unofficial)
+            0xDD => CMP AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_cmp],                                // CMP abs,X
+            0xDE => DEC AbsoluteX:   ABS_X + ABS_RMW + [aal_dec],                                        // DEC abs,X
+            0xDF => DCP AbsoluteX:   ABS_X + ABS_RMW + [aal_dcp],                                        // DCP abs,X (unofficial)
+
+            0xE0 => CPX Immediate:   [imm_cpx],                                                          // CPX #
+            0xE1 => SBC IndirectX:   [imm_zal, add_x_zal, ind_lo_aal, ind_hi_aal, aal_sbc],              // SBC X,ind
+            0xE2 => NOP Immediate:   [imm_nop],                                                          // NOP # (unofficial)
+            0xE3 => ISC IndirectX:   IND_X + ABS_RMW + [aal_isc],                                        // ISC X,ind (unofficial)
+            0xE4 => CPX ZeroPage:    [imm_zal, zal_cpx],                                                 // CPX zpg
+            0xE5 => SBC ZeroPage:    [imm_zal, zal_sbc],                                                 // SBC zpg
+            0xE6 => INC ZeroPage:    ZPG + ZPG_RMW + [zal_inc],                                          // INC zpg
+            0xE7 => ISC ZeroPage:    ZPG + ZPG_RMW + [zal_isc],                                          // ISC zpg (unofficial)
+            0xE8 => INX Implied:     [inx],                                                              // INX impl
+            0xE9 => SBC Immediate:   [imm_sbc],                                                          // SBC #
+            0xEA => NOP Implied:     [nop],                                                              // NOP
+            0xEB => SBC Immediate:   [imm_sbc],                                                          // SBC # (unofficial)
+            0xEC => CPX Absolute:    [imm_lo_aal, imm_hi_aal, aal_cpx],                                  // CPX abs
+            0xED => SBC Absolute:    [imm_lo_aal, imm_hi_aal, aal_sbc],                                  // SBC abs
+            0xEE => INC Absolute:    ABS + ABS_RMW + [aal_inc],                                          // INC abs
+            0xEF => ISC Absolute:    ABS + ABS_RMW + [aal_isc],                                          // ISC abs (unofficial)
+
+            0xF0 => BEQ Relative:    [beq],                                                              // BEQ rel
+            0xF1 => SBC IndirectY:   [imm_zal, ind_lo_aal, ind_hi_aal, y_aal_sbc],                       // SBC ind,Y
+            0xF3 => ISC IndirectY:   IND_Y + ABS_RMW + [aal_isc],                                        // ISC ind,Y (unofficial)
+            0xF4 => NOP ZeroPageX:   [imm_zal, add_x_zal, zal_nop],                                      // NOP zpg,X (unofficial)
+            0xF5 => SBC ZeroPageX:   [imm_zal, add_x_zal, zal_sbc],                                      // SBC zpg,X
+            0xF6 => INC ZeroPageX:   ZPG_X + ZPG_RMW + [zal_inc],                                        // INC zpg,X
+            0xF7 => ISC ZeroPageX:   ZPG_X + ZPG_RMW + [zal_isc],                                        // ISC zpg,X (unofficial)
+            0xF8 => SED Implied:     [sed],                                                              // SED impl
+            0xF9 => SBC AbsoluteY:   [imm_lo_aal, imm_hi_aal, y_aal_sbc],                                // SBC abs,Y
+            0xFA => NOP Implied:     [nop],                                                              // NOP (unofficial)
+            0xFB => ISC AbsoluteY:   ABS_Y + ABS_RMW + [aal_isc],                                        // ISC abs,Y (unofficial)
+            0xFC => NOP AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_nop],                                // NOP abs,X (unofficial)
+            0xFD => SBC AbsoluteX:   [imm_lo_aal, imm_hi_aal, x_aal_sbc],                                // SBC abs,X
+            0xFE => INC AbsoluteX:   ABS_X + ABS_RMW + [aal_inc],                                        // INC abs,X
+            0xFF => ISC AbsoluteX:   ABS_X + ABS_RMW + [aal_isc],                                        // ISC abs,X (unofficial)
         });
 
         instrs
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::hardware::MemoryBus;
+    use crate::testing::TestProgram;
+
+    const NEGATIVE: u8 = 0x80;
+    const ZERO: u8 = 0x02;
+
+    /// Runs the first `instructions` instructions of `program`, from $0600, with `memory` loaded
+    /// beforehand
+    fn run(program: &[u8], memory: &[(u16, u8)], instructions: u32) -> TestProgram {
+        let mut test = TestProgram::new(0x0600, program);
+        for &(address, value) in memory {
+            test.bus.write(address, value);
+        }
+        test.cpu.run_instructions(instructions).unwrap();
+        test
+    }
+
+    #[test]
+    fn lax_loads_a_and_x() {
+        let memory = [(0x0010, 0x8F), (0x0020, 0x00), (0x0021, 0x03), (0x0305, 0x00), (0x0312, 0x8F)];
+        for (program, instructions, flags) in [
+            (&[0xA7, 0x10][..], 1, NEGATIVE),                   // LAX $10
+            (&[0xA0, 0x05, 0xB7, 0x0B][..], 2, NEGATIVE),       // LDY #$05, LAX $0B,Y
+            (&[0xAF, 0x12, 0x03][..], 1, NEGATIVE),             // LAX $0312
+            (&[0xA0, 0x05, 0xB3, 0x20][..], 2, ZERO),           // LDY #$05, LAX ($20),Y
+            (&[0xA0, 0x12, 0xBF, 0x00, 0x03][..], 2, NEGATIVE), // LDY #$12, LAX $0300,Y
+        ] {
+            let test = run(program, &memory, instructions);
+            let expected = if flags == ZERO { 0x00 } else { 0x8F };
+            assert_eq!((test.cpu.accumulator(), test.cpu.x()), (expected, expected), "{:02X?}", program);
+            assert_eq!(test.cpu.status_bits() & (NEGATIVE | ZERO), flags, "{:02X?}", program);
+        }
+    }
+
+    #[test]
+    fn sax_stores_a_and_x() {
+        // LDA #$F0, LDX #$3C, LDY #$01, then the SAX
+        let setup = [0xA9, 0xF0, 0xA2, 0x3C, 0xA0, 0x01];
+        let memory = [(0x0040, 0x00), (0x0041, 0x03)];
+        for (sax, address) in [
+            (&[0x87, 0x10][..], 0x0010),        // SAX $10
+            (&[0x97, 0x10][..], 0x0011),        // SAX $10,Y
+            (&[0x8F, 0x34, 0x03][..], 0x0334),  // SAX $0334
+            (&[0x83, 0x04][..], 0x0300),        // SAX ($04,X)
+        ] {
+            let test = run(&[&setup[..], sax].concat(), &memory, 4);
+            assert_eq!(test.bus.read(address), 0x30, "{:02X?}", sax);
+            // Flags are left alone
+            assert_eq!(test.cpu.status_bits() & (NEGATIVE | ZERO), 0, "{:02X?}", sax);
+        }
+    }
+
+    #[test]
+    fn dcp_decrements_then_compares() {
+        const CARRY: u8 = 0x01;
+        // LDA #$42, DCP $10
+        let test = run(&[0xA9, 0x42, 0xC7, 0x10], &[(0x0010, 0x43)], 2);
+        assert_eq!(test.bus.read(0x0010), 0x42);
+        assert_eq!(test.cpu.status_bits() & (NEGATIVE | ZERO | CARRY), ZERO | CARRY);
+        // LDA #$10, DCP $0310
+        let test = run(&[0xA9, 0x10, 0xCF, 0x10, 0x03], &[(0x0310, 0x20)], 2);
+        assert_eq!(test.bus.read(0x0310), 0x1F);
+        assert_eq!(test.cpu.status_bits() & (NEGATIVE | ZERO | CARRY), NEGATIVE);
+    }
+
+    #[test]
+    fn unstable_stores_and_with_the_high_byte_plus_one() {
+        // LDA #$FF, LDX #$F6, LDY #$05, then the store, to $0305 with a base high byte of $03.
+        // A AND X and X alone are both $F6, so each stores $F6 AND $04.
+        let setup = [0xA9, 0xFF, 0xA2, 0xF6, 0xA0, 0x05];
+        let memory = [(0x0020, 0x00), (0x0021, 0x03), (0x0305, 0xAA)];
+        for store in [
+            &[0x9F, 0x00, 0x03][..], // SHA $0300,Y
+            &[0x93, 0x20][..],       // SHA ($20),Y
+            &[0x9E, 0x00, 0x03][..], // SHX $0300,Y
+            &[0x9B, 0x00, 0x03][..], // TAS $0300,Y
+        ] {
+            let test = run(&[&setup[..], store].concat(), &memory, 4);
+            assert_eq!(test.bus.read(0x0305), 0x04, "{:02X?}", store);
+        }
+        // TAS also sets the stack pointer to A AND X
+        let test = run(&[&setup[..], &[0x9B, 0x00, 0x03]].concat(), &memory, 4);
+        assert_eq!(test.cpu.stack_pointer(), 0xF6);
+
+        // LDY #$C3, LDX #$05, SHY $0300,X
+        let test = run(&[0xA0, 0xC3, 0xA2, 0x05, 0x9C, 0x00, 0x03], &memory, 3);
+        assert_eq!(test.bus.read(0x0305), 0xC3 & 0x04);
+    }
+
+    #[test]
+    fn unstable_stores_crossing_a_page_replace_the_high_byte() {
+        // LDX #$01, LDY #$02, SHX $02FF,Y: stores $01 AND $03, at $0101 instead of $0301
+        let test = run(&[0xA2, 0x01, 0xA0, 0x02, 0x9E, 0xFF, 0x02], &[(0x0101, 0xAA), (0x0301, 0xAA)], 3);
+        assert_eq!((test.bus.read(0x0101), test.bus.read(0x0301)), (0x01, 0xAA));
+    }
+}
@@ -1,9 +1,195 @@
 use crate::MOS6502;
+use super::status::Status;
+
+/// Declares the `MicroOp` enum, with one variant per micro-operation method of the same name.
+macro_rules! micro_ops {
+    ($($name:ident),+ $(,)?) => {
+        /// Names a single micro-operation, i.e. the `MOS6502` method of the same name.
+        ///
+        /// Storing these instead of function pointers lets the pending pipeline be inspected and
+        /// serialized. The variants deliberately keep the snake_case of the methods they name.
+        #[allow(non_camel_case_types)]
+        #[repr(u8)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum MicroOp {
+            $($name),+
+        }
+
+        impl MicroOp {
+            /// Every micro-operation, indexed by its `u8` representation.
+            pub const ALL: &'static [MicroOp] = &[$(MicroOp::$name),+];
+
+            /// Performs the micro-operation on the given CPU.
+            pub fn exec(self, cpu: &mut MOS6502) {
+                match self {
+                    $(MicroOp::$name => cpu.$name()),+
+                }
+            }
+
+            /// Inverse of `self as u8`.
+            pub fn from_u8(value: u8) -> Option<Self> {
+                Self::ALL.get(value as usize).copied()
+            }
+        }
+    }
+}
+
+/// Generates the final cycle of a read instruction for each addressing mode. Each micro-op fetches
+/// the operand and hands it to the given CPU common function, in order:
+/// immediate, zero-page, absolute, absolute + X and absolute + Y.
+macro_rules! read_ops {
+    ($($op:ident => $imm:ident, $zal:ident, $aal:ident, $x_aal:ident, $y_aal:ident;)+) => {
+        impl MOS6502 {
+            $(
+                #[doc = concat!("Immediate fetch into `", stringify!($op), "`")]
+                pub fn $imm(&mut self) {
+                    self.imm_dl();
+                    self.$op(self.state.data_latch);
+                }
+                #[doc = concat!("Zero-page fetch into `", stringify!($op), "`")]
+                pub fn $zal(&mut self) {
+                    let value = self.read(self.state.zpg_addr_latch as u16);
+                    self.$op(value);
+                }
+                #[doc = concat!("Absolute fetch into `", stringify!($op), "`")]
+                pub fn $aal(&mut self) {
+                    let value = self.read(self.state.abs_addr_latch);
+                    self.$op(value);
+                }
+                #[doc = concat!("Absolute fetch (plus index stored in X) into `", stringify!($op), "`.")]
+                #[doc = "Page crossings incur additional cycle."]
+                pub fn $x_aal(&mut self) {
+                    if let Some(value) = self.indexed_read(self.x, MicroOp::$aal) {
+                        self.$op(value);
+                    }
+                }
+                #[doc = concat!("Absolute fetch (plus index stored in Y) into `", stringify!($op), "`.")]
+                #[doc = "Page crossings incur additional cycle."]
+                pub fn $y_aal(&mut self) {
+                    if let Some(value) = self.indexed_read(self.y, MicroOp::$aal) {
+                        self.$op(value);
+                    }
+                }
+            )+
+        }
+    }
+}
+
+read_ops! {
+    adc => imm_adc, zal_adc, aal_adc, x_aal_adc, y_aal_adc;
+    sbc => imm_sbc, zal_sbc, aal_sbc, x_aal_sbc, y_aal_sbc;
+    and => imm_and, zal_and, aal_and, x_aal_and, y_aal_and;
+    ora => imm_ora, zal_ora, aal_ora, x_aal_ora, y_aal_ora;
+    eor => imm_eor, zal_eor, aal_eor, x_aal_eor, y_aal_eor;
+    cmp => imm_cmp, zal_cmp, aal_cmp, x_aal_cmp, y_aal_cmp;
+    lax => imm_lax, zal_lax, aal_lax, x_aal_lax, y_aal_lax;
+    discard => imm_nop, zal_nop, aal_nop, x_aal_nop, y_aal_nop;
+    cpx => imm_cpx, zal_cpx, aal_cpx, x_aal_cpx, y_aal_cpx;
+    cpy => imm_cpy, zal_cpy, aal_cpy, x_aal_cpy, y_aal_cpy;
+    bit => imm_bit, zal_bit, aal_bit, x_aal_bit, y_aal_bit;
+}
+
+/// Generates micro-ops that fetch an immediate operand and hand it to the given CPU common
+/// function, for instructions that only have an immediate addressing mode.
+macro_rules! imm_ops {
+    ($($op:ident => $imm:ident;)+) => {
+        impl MOS6502 {
+            $(
+                #[doc = concat!("Immediate fetch into `", stringify!($op), "`")]
+                pub fn $imm(&mut self) {
+                    self.imm_dl();
+                    self.$op(self.state.data_latch);
+                }
+            )+
+        }
+    }
+}
+
+imm_ops! {
+    anc => imm_anc;
+    alr => imm_alr;
+    arr => imm_arr;
+    ane => imm_ane;
+    lxa => imm_lxa;
+    sbx => imm_sbx;
+}
+
+/// Generates the final cycle of a read-modify-write instruction, which writes the modified data
+/// latch back to either the zero-page or the absolute address latch.
+macro_rules! rmw_ops {
+    ($($op:ident => $zal:ident, $aal:ident;)+) => {
+        impl MOS6502 {
+            $(
+                #[doc = concat!("Zero-page write of data latch modified by `", stringify!($op), "`")]
+                pub fn $zal(&mut self) {
+                    let result = self.$op(self.state.data_latch);
+                    self.write(self.state.zpg_addr_latch as u16, result);
+                }
+                #[doc = concat!("Absolute write of data latch modified by `", stringify!($op), "`")]
+                pub fn $aal(&mut self) {
+                    let result = self.$op(self.state.data_latch);
+                    self.write(self.state.abs_addr_latch, result);
+                }
+            )+
+        }
+    }
+}
+
+rmw_ops! {
+    asl => zal_asl, aal_asl;
+    lsr => zal_lsr, aal_lsr;
+    rol => zal_rol, aal_rol;
+    ror => zal_ror, aal_ror;
+    inc => zal_inc, aal_inc;
+    dec => zal_dec, aal_dec;
+    slo => zal_slo, aal_slo;
+    rla => zal_rla, aal_rla;
+    sre => zal_sre, aal_sre;
+    rra => zal_rra, aal_rra;
+    dcp => zal_dcp, aal_dcp;
+    isc => zal_isc, aal_isc;
+}
+
+// Every micro-operation defined in this file must be listed here.
+micro_ops! {
+    imm_a, imm_y, imm_x, imm_zal, imm_lo_aal, imm_hi_aal,
+    zal_lda, zal_ldx, zal_ldy, aal_lda, aal_ldx, aal_ldy, x_aal_lda, y_aal_lda, x_aal_ldy, y_aal_ldx,
+    imm_adc, zal_adc, aal_adc, x_aal_adc, y_aal_adc,
+    imm_sbc, zal_sbc, aal_sbc, x_aal_sbc, y_aal_sbc,
+    imm_and, zal_and, aal_and, x_aal_and, y_aal_and,
+    imm_ora, zal_ora, aal_ora, x_aal_ora, y_aal_ora,
+    imm_eor, zal_eor, aal_eor, x_aal_eor, y_aal_eor,
+    imm_cmp, zal_cmp, aal_cmp, x_aal_cmp, y_aal_cmp,
+    imm_lax, zal_lax, aal_lax, x_aal_lax, y_aal_lax,
+    imm_nop, zal_nop, aal_nop, x_aal_nop, y_aal_nop,
+    imm_cpx, zal_cpx, aal_cpx, x_aal_cpx, y_aal_cpx,
+    imm_cpy, zal_cpy, aal_cpy, x_aal_cpy, y_aal_cpy,
+    imm_bit, zal_bit, aal_bit, x_aal_bit, y_aal_bit,
+    imm_anc, imm_alr, imm_arr, imm_ane, imm_lxa, imm_sbx, y_aal_las, aal_las,
+    zal_dl, aal_dl,
+    ind_lo_aal, ind_hi_aal,
+    aal_sty, aal_sta, aal_stx, zal_sty, zal_sta, zal_stx, zal_sax, aal_sax,
+    aal_sha, aal_shx, aal_shy, aal_tas,
+    dl_zal, dl_aal,
+    zal_asl, aal_asl, zal_lsr, aal_lsr, zal_rol, aal_rol, zal_ror, aal_ror,
+    zal_inc, aal_inc, zal_dec, aal_dec,
+    zal_slo, aal_slo, zal_rla, aal_rla, zal_sre, aal_sre, zal_rra, aal_rra,
+    zal_dcp, aal_dcp, zal_isc, aal_isc,
+    push_pch, push_pcl, push_p, push_p_brk, push_a,
+    stack_dummy, pull_pcl, pull_pch, pull_p, pull_a,
+    nmi_lo_aal, nmi_hi_pc, irq_lo_aal, irq_hi_pc,
+    imm_hi_pc, jmp_ind_hi, inc_pc,
+    bpl, bmi, bvc, bvs, bcc, bcs, bne, beq,
+    clc, sec, cli, sei, clv, cld, sed,
+    tax, tay, txa, tya, tsx, txs, inx, iny, dex, dey, asl_a, lsr_a, rol_a, ror_a,
+    add_x_zal, add_y_zal, add_x_aal, add_y_aal,
+    read_pc, nop,
+}
 
 impl MOS6502 {
     // CPU SUB-INSTRUCTIONS //
     // Below are all of the possible operations that may happen during a clock cycle in the CPU.
-    // The specs for CPU opcodes (found in `instructions.rs`) are lists of references to these.
+    // The specs for CPU opcodes (found in `instructions.rs`) are lists of `MicroOp`s naming these.
 
     // -------- //
     // FETCHERS //
@@ -52,77 +238,86 @@ impl MOS6502 {
 
     /// Zero-page fetch into accumulator
     pub fn zal_lda(&mut self) {
-        self.a = self.bus.borrow_mut().read(self.state.zpg_addr_latch as u16);
+        self.a = self.read(self.state.zpg_addr_latch as u16);
         self.upd_nz(self.a);
     }
     /// Zero-page fetch into X register
     pub fn zal_ldx(&mut self) {
-        self.x = self.bus.borrow_mut().read(self.state.zpg_addr_latch as u16);
+        self.x = self.read(self.state.zpg_addr_latch as u16);
         self.upd_nz(self.x);
     }
     /// Zero-page fetch into Y register
     pub fn zal_ldy(&mut self) {
-        self.y = self.bus.borrow_mut().read(self.state.zpg_addr_latch as u16);
+        self.y = self.read(self.state.zpg_addr_latch as u16);
         self.upd_nz(self.y);
     }
     /// Absolute fetch into accumulator
     pub fn aal_lda(&mut self) {
-        self.a = self.bus.borrow_mut().read(self.state.abs_addr_latch);
+        self.a = self.read(self.state.abs_addr_latch);
         self.upd_nz(self.a);
     }
     /// Absolute fetch into X register
     pub fn aal_ldx(&mut self) {
-        self.x = self.bus.borrow_mut().read(self.state.abs_addr_latch);
+        self.x = self.read(self.state.abs_addr_latch);
         self.upd_nz(self.x);
     }
     /// Absolute fetch into Y register
     pub fn aal_ldy(&mut self) {
-        self.y = self.bus.borrow_mut().read(self.state.abs_addr_latch);
+        self.y = self.read(self.state.abs_addr_latch);
         self.upd_nz(self.y);
     }
     /// Absolute fetch (plus index stored in X) into accumulator.
     /// Page crossings incur additional cycle.
     pub fn x_aal_lda(&mut self) {
-        if self.state.abs_addr_latch & 0xFF + self.x as u16 > 0xFF {
-            // Wait an extra cycle.
-            // IRL harware takes an extra cycle to resolve the new page.
-            self.state.u_op_queue.push_front(Self::nop);
+        if let Some(value) = self.indexed_read(self.x, MicroOp::aal_lda) {
+            self.a = value;
+            self.upd_nz(self.a);
         }
-        self.a = self.bus.borrow_mut().read(self.state.abs_addr_latch + self.x as u16);
-        self.upd_nz(self.a);
     }
     /// Absolute fetch (plus index stored in Y) into accumulator.
     /// Page crossings incur additional cycle.
     pub fn y_aal_lda(&mut self) {
-        if self.state.abs_addr_latch & 0xFF + self.y as u16 > 0xFF {
-            // Wait an extra cycle.
-            // IRL harware takes an extra cycle to resolve the new page.
-            self.state.u_op_queue.push_front(Self::nop);
+        if let Some(value) = self.indexed_read(self.y, MicroOp::aal_lda) {
+            self.a = value;
+            self.upd_nz(self.a);
         }
-        self.a = self.bus.borrow_mut().read(self.state.abs_addr_latch + self.y as u16);
-        self.upd_nz(self.a);
     }
     /// Absolute fetch (plus index stored in X) into Y register.
     /// Page crossings incur additional cycle.
     pub fn x_aal_ldy(&mut self) {
-        if self.state.abs_addr_latch & 0xFF + self.x as u16 > 0xFF {
-            // Wait an extra cycle.
-            // IRL harware takes an extra cycle to resolve the new page.
-            self.state.u_op_queue.push_front(Self::nop);
+        if let Some(value) = self.indexed_read(self.x, MicroOp::aal_ldy) {
+            self.y = value;
+            self.upd_nz(self.y);
         }
-        self.y = self.bus.borrow_mut().read(self.state.abs_addr_latch + self.x as u16);
-        self.upd_nz(self.y);
     }
     /// Absolute fetch (plus index stored in Y) into X register.
     /// Page crossings incur additional cycle.
     pub fn y_aal_ldx(&mut self) {
-        if self.state.abs_addr_latch & 0xFF + self.y as u16 > 0xFF {
-            // Wait an extra cycle.
-            // IRL harware takes an extra cycle to resolve the new page.
-            self.state.u_op_queue.push_front(Self::nop);
+        if let Some(value) = self.indexed_read(self.y, MicroOp::aal_ldx) {
+            self.x = value;
+            self.upd_nz(self.x);
+        }
+    }
+    /// Absolute fetch (plus index stored in Y) into `las` (unofficial).
+    /// Page crossings incur additional cycle.
+    pub fn y_aal_las(&mut self) {
+        if let Some(value) = self.indexed_read(self.y, MicroOp::aal_las) {
+            self.las(value);
         }
-        self.x = self.bus.borrow_mut().read(self.state.abs_addr_latch + self.y as u16);
-        self.upd_nz(self.x);
+    }
+    /// Absolute fetch into `las` (unofficial), once `y_aal_las` has fixed up a page crossing
+    pub fn aal_las(&mut self) {
+        let value = self.read(self.state.abs_addr_latch);
+        self.las(value);
+    }
+
+    /// Zero-page fetch into data latch (first cycle of read-modify-write)
+    pub fn zal_dl(&mut self) {
+        self.state.data_latch = self.read(self.state.zpg_addr_latch as u16);
+    }
+    /// Absolute fetch into data latch (first cycle of read-modify-write)
+    pub fn aal_dl(&mut self) {
+        self.state.data_latch = self.read(self.state.abs_addr_latch);
     }
 
     // INDIRECT //
@@ -130,13 +325,13 @@ impl MOS6502 {
     /// Indirect (pointer found with zero-page latch) fetch into low byte of absolute address latch
     /// Zeroes out the high byte as a side effect.
     pub fn ind_lo_aal(&mut self) {
-        self.state.abs_addr_latch = self.bus.borrow_mut().read(self.state.zpg_addr_latch as u16) as u16;
+        self.state.abs_addr_latch = self.read(self.state.zpg_addr_latch as u16) as u16;
     }
     /// Indirect (pointer found with zero-page latch) fetch into high byte of absolute address latch
     /// Preserves the low byte.
     pub fn ind_hi_aal(&mut self) {
         self.state.abs_addr_latch &= 0xFF; // Make sure the high byte is cleared
-        self.state.abs_addr_latch |= (self.bus.borrow_mut().read((self.state.zpg_addr_latch + 1) as u16) as u16) << 8;
+        self.state.abs_addr_latch |= (self.read(self.state.zpg_addr_latch.wrapping_add(1) as u16) as u16) << 8;
     }
 
     // ------- //
@@ -145,27 +340,224 @@ impl MOS6502 {
 
     /// Absolute write from Y reg
     pub fn aal_sty(&mut self) {
-        self.bus.borrow_mut().write(self.state.abs_addr_latch, self.y);
+        self.write(self.state.abs_addr_latch, self.y);
     }
     /// Absolute write from accumulator
     pub fn aal_sta(&mut self) {
-        self.bus.borrow_mut().write(self.state.abs_addr_latch, self.a);
+        self.write(self.state.abs_addr_latch, self.a);
     }
     /// Absolute write from X reg
     pub fn aal_stx(&mut self) {
-        self.bus.borrow_mut().write(self.state.abs_addr_latch, self.x);
+        self.write(self.state.abs_addr_latch, self.x);
     }
     /// Zero-page write from Y reg
     pub fn zal_sty(&mut self) {
-        self.bus.borrow_mut().write(self.state.zpg_addr_latch as u16, self.y);
+        self.write(self.state.zpg_addr_latch as u16, self.y);
     }
     /// Zero-page write from accumulator
     pub fn zal_sta(&mut self) {
-        self.bus.borrow_mut().write(self.state.zpg_addr_latch as u16, self.a);
+        self.write(self.state.zpg_addr_latch as u16, self.a);
     }
     /// Zero-page write from X reg
     pub fn zal_stx(&mut self) {
-        self.bus.borrow_mut().write(self.state.zpg_addr_latch as u16, self.x);
+        self.write(self.state.zpg_addr_latch as u16, self.x);
+    }
+    /// Zero-page write of accumulator AND X reg (unofficial)
+    pub fn zal_sax(&mut self) {
+        self.write(self.state.zpg_addr_latch as u16, self.a & self.x);
+    }
+    /// Absolute write of accumulator AND X reg (unofficial)
+    pub fn aal_sax(&mut self) {
+        self.write(self.state.abs_addr_latch, self.a & self.x);
+    }
+    /// Absolute write of accumulator AND X reg AND address high byte + 1 (unofficial, unstable)
+    pub fn aal_sha(&mut self) {
+        self.unstable_store(self.a & self.x, self.y);
+    }
+    /// Absolute write of X reg AND address high byte + 1 (unofficial, unstable)
+    pub fn aal_shx(&mut self) {
+        self.unstable_store(self.x, self.y);
+    }
+    /// Absolute write of Y reg AND address high byte + 1 (unofficial, unstable)
+    pub fn aal_shy(&mut self) {
+        self.unstable_store(self.y, self.x);
+    }
+    /// Transfer accumulator AND X reg into stack pointer, then absolute write of it AND address
+    /// high byte + 1 (unofficial, unstable)
+    pub fn aal_tas(&mut self) {
+        self.stack_ptr = self.a & self.x;
+        self.unstable_store(self.stack_ptr, self.y);
+    }
+    /// Zero-page write from data latch.
+    /// Read-modify-write instructions do this with the unmodified value while modifying it.
+    pub fn dl_zal(&mut self) {
+        self.write(self.state.zpg_addr_latch as u16, self.state.data_latch);
+    }
+    /// Absolute write from data latch.
+    /// Read-modify-write instructions do this with the unmodified value while modifying it.
+    pub fn dl_aal(&mut self) {
+        self.write(self.state.abs_addr_latch, self.state.data_latch);
+    }
+
+    // ----- //
+    // STACK //
+    // ----- //
+
+    /// Push high byte of program counter onto the stack
+    pub fn push_pch(&mut self) {
+        self.push((self.program_counter >> 8) as u8);
+    }
+    /// Push low byte of program counter onto the stack
+    pub fn push_pcl(&mut self) {
+        self.push(self.program_counter as u8);
+    }
+    /// Push processor status onto the stack, with the break flag cleared (hardware interrupts)
+    pub fn push_p(&mut self) {
+        self.push((self.status.bits() | Status::UNUSED.bits()) & !Status::BREAK.bits());
+    }
+    /// Push processor status onto the stack, with the break flag set (BRK, PHP)
+    pub fn push_p_brk(&mut self) {
+        self.push(self.status.bits() | Status::UNUSED.bits() | Status::BREAK.bits());
+    }
+    /// Push accumulator onto the stack
+    pub fn push_a(&mut self) {
+        self.push(self.a);
+    }
+    /// Dummy read from the top of the stack, while the stack pointer is being adjusted
+    pub fn stack_dummy(&mut self) {
+        _ = self.read(self.stack_address());
+    }
+    /// Pull low byte of program counter from the stack
+    pub fn pull_pcl(&mut self) {
+        self.program_counter = self.program_counter & 0xFF00 | self.pull() as u16;
+    }
+    /// Pull high byte of program counter from the stack
+    pub fn pull_pch(&mut self) {
+        self.program_counter = self.program_counter & 0x00FF | (self.pull() as u16) << 8;
+    }
+    /// Pull processor status from the stack
+    pub fn pull_p(&mut self) {
+        let value = self.pull();
+        self.set_status_from_stack(value);
+    }
+    /// Pull accumulator from the stack
+    pub fn pull_a(&mut self) {
+        self.a = self.pull();
+        self.upd_nz(self.a);
+    }
+
+    // ---------- //
+    // INTERRUPTS //
+    // ---------- //
+
+    /// Fetch low byte of the NMI vector into the absolute address latch
+    pub fn nmi_lo_aal(&mut self) {
+        self.state.abs_addr_latch = self.read(0xFFFA) as u16;
+    }
+    /// Fetch high byte of the NMI vector, jump to the resulting address and disable interrupts
+    pub fn nmi_hi_pc(&mut self) {
+        self.program_counter = (self.read(0xFFFB) as u16) << 8 | self.state.abs_addr_latch & 0xFF;
+        self.status.insert(Status::INTERRUPT);
+    }
+
+    /// Fetch low byte of the IRQ/BRK vector into the absolute address latch
+    pub fn irq_lo_aal(&mut self) {
+        self.state.abs_addr_latch = self.read(0xFFFE) as u16;
+    }
+    /// Fetch high byte of the IRQ/BRK vector, jump to the resulting address and disable interrupts
+    pub fn irq_hi_pc(&mut self) {
+        self.program_counter = (self.read(0xFFFF) as u16) << 8 | self.state.abs_addr_latch & 0xFF;
+        self.status.insert(Status::INTERRUPT);
+    }
+
+    // ----- //
+    // JUMPS //
+    // ----- //
+
+    /// Immediate fetch of the high byte of the target address, then jump to the absolute address
+    /// latch (JMP abs, JSR)
+    pub fn imm_hi_pc(&mut self) {
+        self.imm_dl();
+        self.program_counter = (self.state.data_latch as u16) << 8 | self.state.abs_addr_latch & 0xFF;
+    }
+    /// Fetch the high byte of an indirect jump target and jump. The data latch holds the low byte.
+    ///
+    /// The pointer's high byte is fetched without carrying into the page, so `JMP ($10FF)` reads
+    /// its high byte from $1000.
+    pub fn jmp_ind_hi(&mut self) {
+        let address = self.state.abs_addr_latch & 0xFF00 | self.state.abs_addr_latch.wrapping_add(1) & 0x00FF;
+        self.program_counter = (self.read(address) as u16) << 8 | self.state.data_latch as u16;
+    }
+    /// Dummy read from the program counter, then increment it (the last cycle of RTS)
+    pub fn inc_pc(&mut self) {
+        _ = self.read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
+    }
+
+    // Branches //
+
+    /// Branch if result plus
+    pub fn bpl(&mut self) {
+        self.branch(!self.status.contains(Status::NEGATIVE));
+    }
+    /// Branch if result minus
+    pub fn bmi(&mut self) {
+        self.branch(self.status.contains(Status::NEGATIVE));
+    }
+    /// Branch if overflow clear
+    pub fn bvc(&mut self) {
+        self.branch(!self.status.contains(Status::OVERFLOW));
+    }
+    /// Branch if overflow set
+    pub fn bvs(&mut self) {
+        self.branch(self.status.contains(Status::OVERFLOW));
+    }
+    /// Branch if carry clear
+    pub fn bcc(&mut self) {
+        self.branch(!self.status.contains(Status::CARRY));
+    }
+    /// Branch if carry set
+    pub fn bcs(&mut self) {
+        self.branch(self.status.contains(Status::CARRY));
+    }
+    /// Branch if result not zero
+    pub fn bne(&mut self) {
+        self.branch(!self.status.contains(Status::ZERO));
+    }
+    /// Branch if result zero
+    pub fn beq(&mut self) {
+        self.branch(self.status.contains(Status::ZERO));
+    }
+
+    // Flags //
+
+    /// Clear carry
+    pub fn clc(&mut self) {
+        self.status.remove(Status::CARRY);
+    }
+    /// Set carry
+    pub fn sec(&mut self) {
+        self.status.insert(Status::CARRY);
+    }
+    /// Clear interrupt disable
+    pub fn cli(&mut self) {
+        self.status.remove(Status::INTERRUPT);
+    }
+    /// Set interrupt disable
+    pub fn sei(&mut self) {
+        self.status.insert(Status::INTERRUPT);
+    }
+    /// Clear overflow
+    pub fn clv(&mut self) {
+        self.status.remove(Status::OVERFLOW);
+    }
+    /// Clear decimal
+    pub fn cld(&mut self) {
+        self.status.remove(Status::DECIMAL);
+    }
+    /// Set decimal (no effect on arithmetic unless decimal mode is enabled, which the 2A03 lacks)
+    pub fn sed(&mut self) {
+        self.status.insert(Status::DECIMAL);
     }
 
     // Single-operation Instructions //
@@ -190,28 +582,134 @@ impl MOS6502 {
         self.a = self.y;
         self.upd_nz(self.a);
     }
+    /// Transfer stack pointer into X reg
+    pub fn tsx(&mut self) {
+        self.x = self.stack_ptr;
+        self.upd_nz(self.x);
+    }
+    /// Transfer X reg into stack pointer (flags unaffected)
+    pub fn txs(&mut self) {
+        self.stack_ptr = self.x;
+    }
+    /// Increment X reg
+    pub fn inx(&mut self) {
+        self.x = self.x.wrapping_add(1);
+        self.upd_nz(self.x);
+    }
+    /// Increment Y reg
+    pub fn iny(&mut self) {
+        self.y = self.y.wrapping_add(1);
+        self.upd_nz(self.y);
+    }
+    /// Decrement X reg
+    pub fn dex(&mut self) {
+        self.x = self.x.wrapping_sub(1);
+        self.upd_nz(self.x);
+    }
+    /// Decrement Y reg
+    pub fn dey(&mut self) {
+        self.y = self.y.wrapping_sub(1);
+        self.upd_nz(self.y);
+    }
+    /// Arithmetic shift left on Accumulator
+    pub fn asl_a(&mut self) {
+        self.a = self.asl(self.a);
+    }
+    /// Logical shift right on Accumulator
+    pub fn lsr_a(&mut self) {
+        self.a = self.lsr(self.a);
+    }
+    /// Rotate left on Accumulator
+    pub fn rol_a(&mut self) {
+        self.a = self.rol(self.a);
+    }
+    /// Rotate right on Accumulator
+    pub fn ror_a(&mut self) {
+        self.a = self.ror(self.a);
+    }
 
-    /// Add value stored in reg. X to Zero-page Address Latch
+    /// Add value stored in reg. X to Zero-page Address Latch.
+    /// Also perform dummy read from the unindexed address, while the addition happens.
     pub fn add_x_zal(&mut self) {
-        self.state.zpg_addr_latch += self.x;
+        _ = self.read(self.state.zpg_addr_latch as u16);
+        self.state.zpg_addr_latch = self.state.zpg_addr_latch.wrapping_add(self.x);
     }
-    /// Add value stored in reg. Y to Zero-page Address Latch
+    /// Add value stored in reg. Y to Zero-page Address Latch.
+    /// Also perform dummy read from the unindexed address, while the addition happens.
     pub fn add_y_zal(&mut self) {
-        self.state.zpg_addr_latch += self.y;
+        _ = self.read(self.state.zpg_addr_latch as u16);
+        self.state.zpg_addr_latch = self.state.zpg_addr_latch.wrapping_add(self.y);
     }
     /// Add value stored in reg. X to Absolute Address Latch.
-    /// Also perform dummy read from the resultant address
+    /// Also perform dummy read from the address before its high byte is fixed up.
+    ///
+    /// Stores and read-modify-writes always spend this extra cycle, page crossing or not. Loads
+    /// only pay for it on page crossings (see `indexed_read`).
     pub fn add_x_aal(&mut self) {
-        self.state.abs_addr_latch += self.x as u16;
-        _ = self.bus.borrow_mut().read(self.state.abs_addr_latch);
+        self.index_aal(self.x);
     }
     /// Add value stored in reg. Y to Absolute Address Latch.
-    /// Also perform dummy read from the resultant address
+    /// Also perform dummy read from the address before its high byte is fixed up.
+    ///
+    /// Stores and read-modify-writes always spend this extra cycle, page crossing or not. Loads
+    /// only pay for it on page crossings (see `indexed_read`).
     pub fn add_y_aal(&mut self) {
-        self.state.abs_addr_latch += self.y as u16;
-        _ = self.bus.borrow_mut().read(self.state.abs_addr_latch);
+        self.index_aal(self.y);
+    }
+    /// Dummy read from the program counter, without incrementing it
+    pub fn read_pc(&mut self) {
+        _ = self.read(self.program_counter);
     }
     /// No-op.
     pub fn nop(&mut self) {}
 }
 
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::{BusAccess, MemoryBus};
+    use crate::hardware::MOS6502;
+    use crate::testing::TestBus;
+
+    /// A `TestBus` that records every access made through it
+    #[derive(Default)]
+    struct LoggingBus {
+        memory: TestBus,
+        log: RefCell<Vec<(BusAccess, u16)>>,
+    }
+
+    impl MemoryBus for LoggingBus {
+        fn read(&self, address: u16) -> u8 {
+            self.log.borrow_mut().push((BusAccess::Read, address));
+            self.memory.read(address)
+        }
+
+        fn write(&self, address: u16, value: u8) {
+            self.log.borrow_mut().push((BusAccess::Write, address));
+            self.memory.write(address, value);
+        }
+    }
+
+    #[test]
+    fn indexed_store_makes_one_dummy_read() {
+        // LDX #$05, STA $03FF,X
+        let bus = Rc::new(LoggingBus::default());
+        bus.memory.load(0x0600, &[0xA2, 0x05, 0x9D, 0xFF, 0x03]);
+        bus.memory.load(0xFFFC, &[0x00, 0x06]);
+        let mut cpu = MOS6502::new(bus.clone());
+        cpu.reset().unwrap();
+        cpu.step_instruction().unwrap();
+        bus.log.borrow_mut().clear();
+
+        assert_eq!(cpu.step_instruction(), Ok(5));
+        assert_eq!(*bus.log.borrow(), [
+            (BusAccess::Read, 0x0602),
+            (BusAccess::Read, 0x0603),
+            (BusAccess::Read, 0x0604),
+            // Before the high byte is fixed up
+            (BusAccess::Read, 0x0304),
+            (BusAccess::Write, 0x0404),
+        ]);
+    }
+}
@@ -3,15 +3,50 @@ pub(crate) mod state;
 pub(crate) mod status;
 pub(crate) mod micro_ops;
 pub(crate) mod instructions;
+pub(crate) mod snapshot;
+pub(crate) mod profiler;
+pub(crate) mod config;
+pub(crate) mod debug;
+#[cfg(test)]
+mod timing;
+
+pub use instr_def::InstrDef;
+pub use micro_ops::MicroOp;
+pub use snapshot::CpuSnapshot;
+pub use profiler::OpStat;
+pub use config::CpuConfig;
 
 use std::rc::Rc;
-use std::cell::RefCell;
-use crate::RustNesError;
-use crate::hardware::Bus;
-use crate::hardware::cpu::instr_def::*;
+use std::collections::BTreeSet;
+use crate::{disasm, RustNesError};
+use crate::hardware::MemoryBus;
+use crate::hardware::cpu::profiler::Profiler;
 use crate::hardware::cpu::state::MOSState;
 use crate::hardware::cpu::status::Status;
 
+/// Cycles the reset sequence takes, before the first instruction is fetched.
+const RESET_CYCLES: u64 = 7;
+
+/// Stands in for the chip-dependent bits ORed into the accumulator by ANE and LXA. $EE is what
+/// most 2A03s seem to use.
+const UNSTABLE_MAGIC: u8 = 0xEE;
+
+/// The stack lives in page 1. The stack pointer only supplies the low byte, so the stack wraps
+/// around within $0100-$01FF rather than spilling into neighbouring pages.
+const STACK_PAGE: u16 = 0x0100;
+
+/// What the CPU does when it fetches an opcode it has no definition for (see
+/// `MOS6502::set_invalid_opcode_policy`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum InvalidOpcodePolicy {
+    /// `step` returns `RustNesError::InvalidOpcode`, so bad jumps and unsupported opcodes get
+    /// noticed
+    #[default]
+    Error,
+    /// The opcode runs as a NOP, skipping over as many operand bytes as its addressing mode would
+    /// have. Helps with ROMs that mostly work. Debug builds log each one skipped.
+    Nop,
+}
 
 /// Virtual MOS 6502 processor. The roles of `MOS6502` are as follows:
 ///
@@ -30,26 +65,36 @@ use crate::hardware::cpu::status::Status;
 /// - Frame counter control
 /// - Clock speed
 pub struct MOS6502 {
-    pub(crate) bus: Rc<RefCell<Bus>>,
+    pub(crate) bus: Rc<dyn MemoryBus>,
     pub(crate) program_counter: u16,
     pub(crate) a: u8,
     pub(crate) x: u8,
     pub(crate) y: u8,
-    status: Status,
-    stack_ptr: u8,
+    pub(crate) status: Status,
+    pub(crate) stack_ptr: u8,
     pub(crate) state: MOSState,
     instructions: [InstrDef; 256],
+    breakpoints: BTreeSet<u16>,
+    profiler: Profiler,
+    /// Whether the D flag selects decimal arithmetic. The 2A03 lacks the circuitry for it.
+    decimal_enabled: bool,
+    /// Cycles elapsed since power-on, counting the reset sequence
+    pub(crate) cycles: u64,
+    invalid_opcode_policy: InvalidOpcodePolicy,
+    /// Register values to reset into
+    config: CpuConfig,
 }
 
 impl MOS6502 {
-    /// Constructs a new 6502 CPU (`MOS6502`). Requires access to a memory bus.
+    /// Constructs a new 6502 CPU (`MOS6502`). Requires access to a memory bus: the NES's `Bus`,
+    /// or any other `MemoryBus`.
     ///
     /// # Examples
     ///
     /// ```rust
     /// use std::cell::RefCell;
     /// use std::rc::Rc;
-    /// use crate::hardware::*;
+    /// use rust_nes::hardware::*;
     ///
     /// let my_ppu = RefCell::new(PPU::new());
     /// let my_apu = RefCell::new(APU::new());
@@ -57,7 +102,7 @@ impl MOS6502 {
     ///
     /// let my_cpu = MOS6502::new(my_bus.clone());
     /// ```
-    pub fn new(bus: Rc<RefCell<Bus>>) -> Self {
+    pub fn new(bus: Rc<dyn MemoryBus>) -> Self {
         Self {
             bus,
             program_counter: 0,
@@ -68,6 +113,12 @@ impl MOS6502 {
             stack_ptr: 0,
             state: MOSState::new(),
             instructions: Self::instruction_table(),
+            breakpoints: BTreeSet::new(),
+            profiler: Profiler::new(),
+            decimal_enabled: false,
+            cycles: 0,
+            invalid_opcode_policy: InvalidOpcodePolicy::default(),
+            config: CpuConfig::default(),
         }
     }
 
@@ -79,43 +130,443 @@ impl MOS6502 {
     /// In addition, the address space from $8000-$FFFF must be mapped to PRG ROM.
     ///
     /// The stack pointer is initialized with a default of 0xFD, and the unused flag is always set.
+    /// These, and the start address, can be overridden with a `CpuConfig` (see `with_config`).
     ///
     /// TODO: Rewrite this to actually set the State machine to the correct micro-operations that
     /// perform this, instead of just doing it here. It's supposed to take like 8 cycles I think?
     pub fn reset(&mut self) -> Result<(), RustNesError> {
-        let bus = self.bus.borrow();
-        // Get reset vector
-        self.program_counter =
-            (bus.read(0xFFFD) as u16) << 8 |
-            (bus.read(0xFFFC) as u16);
+        self.program_counter = self.reset_target();
 
-        self.status = Status::empty();
+        self.status = Status::from_bits_retain(self.config.status);
         self.status.insert(Status::UNUSED); // This bit is always 1
-        self.stack_ptr = 0xFD;
+        self.stack_ptr = self.config.stack_ptr;
+        self.cycles = RESET_CYCLES;
+        Ok(())
+    }
+
+    /// Does what the reset button does to a running CPU, as opposed to `reset`'s power-on state:
+    /// the current instruction is abandoned, the stack pointer drops by 3 (the reset sequence
+    /// pretends to push the program counter and status without writing), interrupts are disabled
+    /// and execution restarts at the reset vector (or `CpuConfig::start_pc`). The other registers
+    /// and flags keep their values.
+    pub fn soft_reset(&mut self) -> Result<(), RustNesError> {
+        self.state.u_op_queue.clear();
+        self.state.nmi_pending = false;
+        self.profiler.current = None;
+        self.stack_ptr = self.stack_ptr.wrapping_sub(3);
+        self.status.insert(Status::INTERRUPT);
+        self.program_counter = self.reset_target();
+        self.cycles += RESET_CYCLES;
         Ok(())
     }
 
+    /// Where reset starts execution: the address in the reset vector, unless the config overrides
+    /// it.
+    fn reset_target(&self) -> u16 {
+        match self.config.start_pc {
+            Some(address) => address,
+            // Get reset vector
+            None => (self.read(0xFFFD) as u16) << 8 | (self.read(0xFFFC) as u16),
+        }
+    }
+
     /// Steps the CPU by one clock cycle.
     ///
     /// NOTE that the "fetch" stage always accounts for the first cycle of any instruction.
     /// For any instruction, this first cycle is implied.
+    ///
+    /// Doesn't allocate: the micro-op queue is reused from one instruction to the next.
     pub fn step(&mut self) -> Result<(), RustNesError> {
+        self.cycles += 1;
+        if self.bus.poll_nmi() {
+            self.nmi();
+        }
         match self.state.u_op_queue.pop_front() {
+            None if self.state.nmi_pending => {
+                // Interrupts hijack the fetch cycle. The fetched byte is discarded.
+                self.state.nmi_pending = false;
+                self.profiler.current = None;
+                _ = self.read(self.program_counter);
+                self.state.u_op_queue.extend([
+                    MicroOp::read_pc, MicroOp::push_pch, MicroOp::push_pcl, MicroOp::push_p, MicroOp::nmi_lo_aal, MicroOp::nmi_hi_pc,
+                ]);
+            },
+            None if !self.status.contains(Status::INTERRUPT) && self.bus.irq() => {
+                self.profiler.current = None;
+                _ = self.read(self.program_counter);
+                self.state.u_op_queue.extend([
+                    MicroOp::read_pc, MicroOp::push_pch, MicroOp::push_pcl, MicroOp::push_p, MicroOp::irq_lo_aal, MicroOp::irq_hi_pc,
+                ]);
+            },
             None => {
+                let pc = self.program_counter;
                 let next_byte = self.get_prg(); // Fetch
+                if self.profiler.enabled { self.profiler.fetch(next_byte) }
                 let next_instr = self.instructions[next_byte as usize];
-                if next_instr.cycles == 0 { return Err(RustNesError::InvalidOpcode(next_byte)) }
-                self.state.u_op_queue = next_instr.as_vec().into(); // Decode
+                if next_instr.cycles == 0 {
+                    match self.invalid_opcode_policy {
+                        InvalidOpcodePolicy::Error => return Err(RustNesError::InvalidOpcode { opcode: next_byte, pc }),
+                        InvalidOpcodePolicy::Nop => self.skip_invalid_opcode(next_byte),
+                    }
+                    return Ok(());
+                }
+                self.state.u_op_queue.extend(next_instr.u_ops()); // Decode
+            },
+            Some(next) => { // Execute
+                if self.profiler.enabled { self.profiler.cycle() }
+                next.exec(self)
             },
-            Some(next) => { next(self) }, // Execute
         }
         Ok(())
     }
 
+    /// Runs the CPU until the end of an instruction, and returns the number of cycles consumed.
+    ///
+    /// Normally this fetches and executes one whole instruction. If called partway through an
+    /// instruction, only the rest of that instruction is run. A pending interrupt sequence counts
+    /// as an instruction of its own.
+    ///
+    /// Only the CPU is stepped, so this suits debuggers and tests more than running the console.
+    /// `step` remains the cycle-accurate way to advance it.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+    /// let mut my_cpu = MOS6502::new(my_bus.clone());
+    ///
+    /// // LDA $0010, twice
+    /// for (address, byte) in [0xAD, 0x10, 0x00, 0xAD, 0x10, 0x00].into_iter().enumerate() {
+    ///     my_bus.borrow().write(address as u16, byte);
+    /// }
+    /// assert_eq!(my_cpu.step_instruction().unwrap(), 4);
+    /// let after_one = my_cpu.cycle_count();
+    /// for _ in 0..4 {
+    ///     my_cpu.step().unwrap();
+    /// }
+    /// assert_eq!(my_cpu.cycle_count(), after_one + 4);
+    /// assert_eq!(my_cpu.pc(), 0x0006);
+    /// ```
+    pub fn step_instruction(&mut self) -> Result<u8, RustNesError> {
+        let mut cycles = 0;
+        loop {
+            self.step()?;
+            cycles += 1;
+            if self.state.u_op_queue.is_empty() {
+                return Ok(cycles);
+            }
+        }
+    }
+
+    /// Runs the CPU for exactly the given number of cycles, which may leave it partway through an
+    /// instruction. Like `step_instruction`, only the CPU is stepped, which makes this a cheap way
+    /// to run test programs and benchmarks headless.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+    /// let mut my_cpu = MOS6502::new(my_bus.clone());
+    ///
+    /// // INX (2 cycles), then JMP back to it (3 cycles)
+    /// for (address, byte) in [0xE8, 0x4C, 0x00, 0x00].into_iter().enumerate() {
+    ///     my_bus.borrow().write(address as u16, byte);
+    /// }
+    /// my_cpu.run_cycles(50).unwrap();
+    /// assert_eq!(my_cpu.x(), 10);
+    /// assert_eq!(my_cpu.pc(), 0x0000);
+    /// ```
+    pub fn run_cycles(&mut self, cycles: u64) -> Result<(), RustNesError> {
+        for _ in 0..cycles {
+            self.step()?;
+        }
+        Ok(())
+    }
+
+    /// Runs exactly `count` instructions (see `step_instruction`) and returns the number of cycles
+    /// they took. The counterpart to `run_cycles` for lock-step tests, as it always stops between
+    /// instructions.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+    /// let mut my_cpu = MOS6502::new(my_bus.clone());
+    ///
+    /// // LDA #$01 (2 cycles), STA $10 (3 cycles), INX (2 cycles)
+    /// for (address, byte) in [0xA9, 0x01, 0x85, 0x10, 0xE8].into_iter().enumerate() {
+    ///     my_bus.borrow().write(address as u16, byte);
+    /// }
+    /// assert_eq!(my_cpu.run_instructions(3).unwrap(), 7);
+    /// assert_eq!(my_cpu.pc(), 0x0005);
+    /// assert_eq!(my_bus.borrow().read(0x0010), 0x01);
+    /// ```
+    pub fn run_instructions(&mut self, count: u32) -> Result<u64, RustNesError> {
+        let mut cycles = 0;
+        for _ in 0..count {
+            cycles += self.step_instruction()? as u64;
+        }
+        Ok(cycles)
+    }
+
+    /// Runs whole instructions until `condition` holds between two of them, and returns the number
+    /// of cycles that took. The condition is checked before the first instruction too, so nothing
+    /// is run if it already holds.
+    ///
+    /// Never returns if the condition never holds, unless an error occurs.
+    pub fn run_until(&mut self, mut condition: impl FnMut(&MOS6502) -> bool) -> Result<u64, RustNesError> {
+        let start = self.cycles;
+        while !condition(self) {
+            self.step_instruction()?;
+        }
+        Ok(self.cycles - start)
+    }
+
+    /// Runs instructions until the CPU is about to execute a BRK, and returns its address. Test
+    /// programs commonly end with one.
+    ///
+    /// Never returns if no BRK is reached, unless an error occurs.
+    pub fn run_until_halt(&mut self) -> Result<u16, RustNesError> {
+        self.run_until(|cpu| cpu.state.u_op_queue.is_empty() && cpu.bus.peek(cpu.program_counter) == 0x00)?;
+        Ok(self.program_counter)
+    }
+
+    /// Enables decimal mode, as found on a stock 6502: ADC and SBC work on packed BCD while the D
+    /// flag is set. It's disabled by default, matching the NES's 2A03, where D has no effect.
+    pub fn set_decimal_enabled(&mut self, enabled: bool) {
+        self.decimal_enabled = enabled;
+    }
+
+    /// Chooses what happens when the CPU fetches an opcode it doesn't define. Defaults to
+    /// `InvalidOpcodePolicy::Error`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rust_nes::RustNesError;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+    /// let mut my_cpu = MOS6502::new(my_bus.clone());
+    ///
+    /// // $02 (one of the JAMs) at $0000, then INX
+    /// my_bus.borrow().write(0x0000, 0x02);
+    /// my_bus.borrow().write(0x0001, 0xE8);
+    /// assert_eq!(my_cpu.step(), Err(RustNesError::InvalidOpcode { opcode: 0x02, pc: 0x0000 }));
+    ///
+    /// let mut my_cpu = MOS6502::new(my_bus.clone());
+    /// my_cpu.set_invalid_opcode_policy(InvalidOpcodePolicy::Nop);
+    /// my_cpu.step_instruction().unwrap();
+    /// my_cpu.step_instruction().unwrap();
+    /// assert_eq!(my_cpu.x(), 1);
+    /// ```
+    pub fn set_invalid_opcode_policy(&mut self, policy: InvalidOpcodePolicy) {
+        self.invalid_opcode_policy = policy;
+    }
+
+    /// The current invalid opcode policy (see `set_invalid_opcode_policy`).
+    pub fn invalid_opcode_policy(&self) -> InvalidOpcodePolicy {
+        self.invalid_opcode_policy
+    }
+
+    /// Whether decimal mode is enabled (see `set_decimal_enabled`).
+    pub fn decimal_enabled(&self) -> bool {
+        self.decimal_enabled
+    }
+
+    /// Runs instructions until the CPU is about to execute one at a breakpoint, and returns its
+    /// address. At least one instruction is always run, so calling this again while parked on a
+    /// breakpoint moves past it.
+    ///
+    /// Never returns if no breakpoint is reached, unless an error occurs.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+    /// let mut my_cpu = MOS6502::new(my_bus.clone());
+    ///
+    /// // INX, then JMP back to it
+    /// for (address, byte) in [0xE8, 0x4C, 0x00, 0x00].into_iter().enumerate() {
+    ///     my_bus.borrow().write(address as u16, byte);
+    /// }
+    /// my_cpu.add_breakpoint(0x0001);
+    /// assert_eq!(my_cpu.run_until_break().unwrap(), 0x0001);
+    /// assert_eq!(my_cpu.run_until_break().unwrap(), 0x0001);
+    /// assert_eq!(my_cpu.x(), 2);
+    /// ```
+    pub fn run_until_break(&mut self) -> Result<u16, RustNesError> {
+        loop {
+            self.step_instruction()?;
+            if self.at_breakpoint() {
+                return Ok(self.program_counter);
+            }
+        }
+    }
+
+    /// Marks an address to stop at, once the CPU is about to execute the instruction there.
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.breakpoints.insert(address);
+    }
+
+    /// Removes a breakpoint added with `add_breakpoint`.
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.breakpoints.remove(&address);
+    }
+
+    /// Whether the CPU is between instructions, with the program counter on a breakpoint.
+    pub fn at_breakpoint(&self) -> bool {
+        self.state.u_op_queue.is_empty() && self.breakpoints.contains(&self.program_counter)
+    }
+
+    /// Signals a non-maskable interrupt. The interrupt sequence begins once the current instruction
+    /// finishes, and takes 7 cycles to jump to the address found in the NMI vector ($FFFA-$FFFB).
+    pub fn nmi(&mut self) {
+        self.state.nmi_pending = true;
+    }
+
+    /// Halts the CPU for the given number of cycles, as happens when DMA takes over the bus.
+    pub fn stall(&mut self, cycles: u16) {
+        for _ in 0..cycles {
+            self.state.u_op_queue.push_front(MicroOp::nop);
+        }
+    }
+
+    /// The micro-operations still pending for the current instruction, in execution order.
+    pub fn pending_u_ops(&self) -> impl Iterator<Item = MicroOp> + '_ {
+        self.state.u_op_queue.iter().copied()
+    }
+
+    /// Returns how many cycles have elapsed, including the 7 of the reset sequence and any spent
+    /// stalled.
+    pub fn cycle_count(&self) -> u64 {
+        self.cycles
+    }
+
+    /// Returns the current program counter.
+    pub fn pc(&self) -> u16 {
+        self.program_counter
+    }
+
+    /// Sets the program counter, so the next instruction is fetched from `address`. Saves setting
+    /// up a reset vector just to run a few hand-placed instructions. An instruction already under
+    /// way carries on, and may move the program counter itself (see `jump_to`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+    /// let mut my_cpu = MOS6502::new(my_bus.clone());
+    ///
+    /// // LDX #$07 in RAM at $0300
+    /// my_bus.borrow().write(0x0300, 0xA2);
+    /// my_bus.borrow().write(0x0301, 0x07);
+    /// my_cpu.set_pc(0x0300);
+    /// my_cpu.step_instruction().unwrap();
+    /// assert_eq!(my_cpu.x(), 0x07);
+    /// assert_eq!(my_cpu.pc(), 0x0302);
+    /// ```
+    pub fn set_pc(&mut self, address: u16) {
+        self.program_counter = address;
+    }
+
+    /// Abandons the current instruction, if any, and continues from `address`.
+    pub fn jump_to(&mut self, address: u16) {
+        self.state.u_op_queue.clear();
+        self.profiler.current = None;
+        self.program_counter = address;
+    }
+
+    /// Returns the accumulator.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::cell::RefCell;
+    /// use std::rc::Rc;
+    /// use rust_nes::hardware::*;
+    ///
+    /// let my_bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+    /// let mut my_cpu = MOS6502::new(my_bus.clone());
+    ///
+    /// // The program counter starts at $0000, so put an operand there and load it
+    /// my_bus.borrow().write(0x0000, 0x42);
+    /// my_cpu.imm_a();
+    /// assert_eq!(my_cpu.accumulator(), 0x42);
+    /// ```
+    pub fn accumulator(&self) -> u8 {
+        self.a
+    }
+
+    /// Returns the X index register.
+    pub fn x(&self) -> u8 {
+        self.x
+    }
+
+    /// Returns the Y index register.
+    pub fn y(&self) -> u8 {
+        self.y
+    }
+
+    /// Returns the stack pointer (an offset into page $01).
+    pub fn stack_pointer(&self) -> u8 {
+        self.stack_ptr
+    }
+
+    /// Returns the processor status register, as `NV-BDIZC` from bit 7 down to bit 0.
+    pub fn status_bits(&self) -> u8 {
+        self.status.bits()
+    }
+
+    /// Reads a byte from the bus. Reads can have side effects (like acknowledging PPU status), but
+    /// the bus handles those internally, so only a shared borrow is needed.
+    pub(crate) fn read(&self, address: u16) -> u8 {
+        self.bus.read(address)
+    }
+
+    /// Writes a byte to the bus.
+    pub(crate) fn write(&self, address: u16, value: u8) {
+        self.bus.write(address, value)
+    }
+
+    /// Queues a NOP in place of an undefined opcode that was just fetched, reading past as many
+    /// operand bytes as its addressing mode would have.
+    fn skip_invalid_opcode(&mut self, opcode: u8) {
+        if cfg!(debug_assertions) {
+            eprintln!("WARNING: skipped invalid opcode {:#04x} at {:#06x}", opcode, self.program_counter.wrapping_sub(1));
+        }
+        match disasm::decode(opcode).1.operand_len() {
+            0 => self.state.u_op_queue.push_back(MicroOp::read_pc),
+            len => self.state.u_op_queue.extend((0..len).map(|_| MicroOp::imm_nop)),
+        }
+    }
+
     /// Retrieves the next byte in the program, and increments the program counter.
     fn get_prg(&mut self) -> u8 {
-        let result = self.bus.borrow_mut().read(self.program_counter);
-        self.program_counter += 1;
+        let result = self.read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
         result
     }
 
@@ -126,10 +577,395 @@ impl MOS6502 {
         self.status.set(Status::NEGATIVE, number & 0x80 == 0x80);
         self.status.set(Status::ZERO, number == 0);
     }
+    /// Read the effective address formed by adding an index to the absolute address latch.
+    /// Page crossings incur additional cycle.
+    ///
+    /// The index is added to the low byte first, and the hardware reads from that address right
+    /// away. On a page crossing that's the wrong page, so the read is discarded, the high byte gets
+    /// fixed up, and `fixed` (the non-indexed form of the calling micro-op) is queued to read the
+    /// right address on the next cycle. Returns `None` in that case.
+    pub(crate) fn indexed_read(&mut self, index: u8, fixed: MicroOp) -> Option<u8> {
+        let address = self.state.abs_addr_latch.wrapping_add(index as u16);
+        let unfixed = self.state.abs_addr_latch & 0xFF00 | address & 0x00FF;
+        let value = self.read(unfixed);
+        if address != unfixed {
+            self.state.abs_addr_latch = address;
+            self.state.u_op_queue.push_front(fixed);
+            return None;
+        }
+        Some(value)
+    }
+    /// Add an index to the absolute address latch, with the dummy read from the address before
+    /// its high byte is fixed up (see `indexed_read`).
+    pub(crate) fn index_aal(&mut self, index: u8) {
+        let address = self.state.abs_addr_latch.wrapping_add(index as u16);
+        _ = self.read(self.state.abs_addr_latch & 0xFF00 | address & 0x00FF);
+        self.state.abs_addr_latch = address;
+    }
+
+    // ALU operations //
+
+    /// Add with carry. Binary unless decimal mode is both enabled and selected by the D flag.
+    pub(crate) fn adc(&mut self, value: u8) {
+        if self.decimal_mode() {
+            self.adc_decimal(value);
+        } else {
+            self.adc_binary(value);
+        }
+    }
+    /// Subtract with borrow (inverted carry). In decimal mode, the flags still come from the binary
+    /// subtraction, as on an NMOS 6502.
+    pub(crate) fn sbc(&mut self, value: u8) {
+        let a = self.a;
+        let carry = self.status.contains(Status::CARRY);
+        self.adc_binary(!value);
+        if self.decimal_mode() {
+            let mut low = (a & 0x0F) as i16 - (value & 0x0F) as i16 + carry as i16 - 1;
+            if low < 0 {
+                low = ((low - 0x06) & 0x0F) - 0x10;
+            }
+            let mut result = (a & 0xF0) as i16 - (value & 0xF0) as i16 + low;
+            if result < 0 {
+                result -= 0x60;
+            }
+            self.a = result as u8;
+        }
+    }
+    /// Binary add with carry
+    fn adc_binary(&mut self, value: u8) {
+        let sum = self.a as u16 + value as u16 + self.status.contains(Status::CARRY) as u16;
+        let result = sum as u8;
+        self.status.set(Status::CARRY, sum > 0xFF);
+        self.status.set(Status::OVERFLOW, (self.a ^ result) & (value ^ result) & 0x80 != 0);
+        self.a = result;
+        self.upd_nz(self.a);
+    }
+    /// Packed BCD add with carry. Like an NMOS 6502, Z reflects the binary sum, while N and V are
+    /// taken after adjusting the low digit but before adjusting the high one.
+    fn adc_decimal(&mut self, value: u8) {
+        let carry = self.status.contains(Status::CARRY);
+        let binary = self.a.wrapping_add(value).wrapping_add(carry as u8);
+        let mut low = (self.a & 0x0F) as u16 + (value & 0x0F) as u16 + carry as u16;
+        if low >= 0x0A {
+            low = ((low + 0x06) & 0x0F) + 0x10;
+        }
+        let mut sum = (self.a & 0xF0) as u16 + (value & 0xF0) as u16 + low;
+        let signed_sum = (self.a & 0xF0) as i8 as i16 + (value & 0xF0) as i8 as i16 + low as i16;
+        self.status.set(Status::NEGATIVE, sum & 0x80 != 0);
+        self.status.set(Status::OVERFLOW, !(-128..=127).contains(&signed_sum));
+        self.status.set(Status::ZERO, binary == 0);
+        if sum >= 0xA0 {
+            sum += 0x60;
+        }
+        self.status.set(Status::CARRY, sum > 0xFF);
+        self.a = sum as u8;
+    }
+    /// Whether ADC and SBC currently operate on packed BCD
+    fn decimal_mode(&self) -> bool {
+        self.decimal_enabled && self.status.contains(Status::DECIMAL)
+    }
+    /// Bitwise AND into accumulator
+    pub(crate) fn and(&mut self, value: u8) {
+        self.a &= value;
+        self.upd_nz(self.a);
+    }
+    /// Bitwise OR into accumulator
+    pub(crate) fn ora(&mut self, value: u8) {
+        self.a |= value;
+        self.upd_nz(self.a);
+    }
+    /// Bitwise exclusive OR into accumulator
+    pub(crate) fn eor(&mut self, value: u8) {
+        self.a ^= value;
+        self.upd_nz(self.a);
+    }
+    /// Compare accumulator
+    pub(crate) fn cmp(&mut self, value: u8) {
+        self.compare(self.a, value);
+    }
+    /// Compare X reg
+    pub(crate) fn cpx(&mut self, value: u8) {
+        self.compare(self.x, value);
+    }
+    /// Compare Y reg
+    pub(crate) fn cpy(&mut self, value: u8) {
+        self.compare(self.y, value);
+    }
+    /// Bit test: Z from accumulator AND value, N and V straight from bits 7 and 6 of value
+    pub(crate) fn bit(&mut self, value: u8) {
+        self.status.set(Status::ZERO, self.a & value == 0);
+        self.status.set(Status::NEGATIVE, value & 0x80 != 0);
+        self.status.set(Status::OVERFLOW, value & 0x40 != 0);
+    }
+    /// Set flags as though `value` was subtracted from `register`
+    pub(crate) fn compare(&mut self, register: u8, value: u8) {
+        self.status.set(Status::CARRY, register >= value);
+        self.upd_nz(register.wrapping_sub(value));
+    }
+    /// Load into both accumulator and X reg (unofficial)
+    pub(crate) fn lax(&mut self, value: u8) {
+        self.a = value;
+        self.x = value;
+        self.upd_nz(value);
+    }
+    /// AND, then copy N into C (unofficial)
+    pub(crate) fn anc(&mut self, value: u8) {
+        self.and(value);
+        self.status.set(Status::CARRY, self.a & 0x80 != 0);
+    }
+    /// AND, then shift the accumulator right (unofficial)
+    pub(crate) fn alr(&mut self, value: u8) {
+        self.a = self.lsr(self.a & value);
+    }
+    /// AND, then rotate the accumulator right. C comes from bit 6 of the result, and V from bit 6
+    /// XOR bit 5 (unofficial)
+    pub(crate) fn arr(&mut self, value: u8) {
+        self.a = self.ror(self.a & value);
+        self.status.set(Status::CARRY, self.a & 0x40 != 0);
+        self.status.set(Status::OVERFLOW, (self.a ^ self.a << 1) & 0x40 != 0);
+    }
+    /// Accumulator (ORed with a chip-dependent constant) AND X AND value into accumulator
+    /// (unofficial, unstable)
+    pub(crate) fn ane(&mut self, value: u8) {
+        self.a = (self.a | UNSTABLE_MAGIC) & self.x & value;
+        self.upd_nz(self.a);
+    }
+    /// Accumulator (ORed with a chip-dependent constant) AND value into both accumulator and X reg
+    /// (unofficial, unstable)
+    pub(crate) fn lxa(&mut self, value: u8) {
+        self.lax((self.a | UNSTABLE_MAGIC) & value);
+    }
+    /// Accumulator AND X reg, minus value (without borrow), into X reg. Flags are set as by CMP
+    /// (unofficial)
+    pub(crate) fn sbx(&mut self, value: u8) {
+        let register = self.a & self.x;
+        self.compare(register, value);
+        self.x = register.wrapping_sub(value);
+    }
+    /// Value AND stack pointer into accumulator, X reg and stack pointer (unofficial)
+    pub(crate) fn las(&mut self, value: u8) {
+        self.stack_ptr &= value;
+        self.lax(self.stack_ptr);
+    }
+    /// Store `value` AND the high byte of the base address plus 1, at the absolute address latch
+    /// (which has already had `index` added). Used by SHA, SHX, SHY and TAS (unofficial, unstable).
+    ///
+    /// When indexing crossed a page, the high byte of the target address gets replaced by the
+    /// value stored, as on most NMOS chips.
+    pub(crate) fn unstable_store(&mut self, value: u8, index: u8) {
+        let base = self.state.abs_addr_latch.wrapping_sub(index as u16);
+        let result = value & ((base >> 8) as u8).wrapping_add(1);
+        let mut address = self.state.abs_addr_latch;
+        if base & 0xFF00 != address & 0xFF00 {
+            address = (result as u16) << 8 | address & 0x00FF;
+        }
+        self.write(address, result);
+    }
+    /// Throw away the operand (used by NOPs that still read one)
+    pub(crate) fn discard(&mut self, _value: u8) {}
+
+    // Shifts and increments (return the modified value) //
+
+    /// Arithmetic shift left
+    pub(crate) fn asl(&mut self, value: u8) -> u8 {
+        self.status.set(Status::CARRY, value & 0x80 != 0);
+        let result = value << 1;
+        self.upd_nz(result);
+        result
+    }
+    /// Logical shift right
+    pub(crate) fn lsr(&mut self, value: u8) -> u8 {
+        self.status.set(Status::CARRY, value & 0x01 != 0);
+        let result = value >> 1;
+        self.upd_nz(result);
+        result
+    }
+    /// Rotate left through carry
+    pub(crate) fn rol(&mut self, value: u8) -> u8 {
+        let result = value << 1 | self.status.contains(Status::CARRY) as u8;
+        self.status.set(Status::CARRY, value & 0x80 != 0);
+        self.upd_nz(result);
+        result
+    }
+    /// Rotate right through carry
+    pub(crate) fn ror(&mut self, value: u8) -> u8 {
+        let result = value >> 1 | (self.status.contains(Status::CARRY) as u8) << 7;
+        self.status.set(Status::CARRY, value & 0x01 != 0);
+        self.upd_nz(result);
+        result
+    }
+    /// Increment
+    pub(crate) fn inc(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.upd_nz(result);
+        result
+    }
+    /// Decrement
+    pub(crate) fn dec(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.upd_nz(result);
+        result
+    }
+    /// ASL then ORA (unofficial)
+    pub(crate) fn slo(&mut self, value: u8) -> u8 {
+        let result = self.asl(value);
+        self.ora(result);
+        result
+    }
+    /// ROL then AND (unofficial)
+    pub(crate) fn rla(&mut self, value: u8) -> u8 {
+        let result = self.rol(value);
+        self.and(result);
+        result
+    }
+    /// LSR then EOR (unofficial)
+    pub(crate) fn sre(&mut self, value: u8) -> u8 {
+        let result = self.lsr(value);
+        self.eor(result);
+        result
+    }
+    /// ROR then ADC (unofficial)
+    pub(crate) fn rra(&mut self, value: u8) -> u8 {
+        let result = self.ror(value);
+        self.adc(result);
+        result
+    }
+    /// DEC then CMP (unofficial)
+    pub(crate) fn dcp(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_sub(1);
+        self.cmp(result);
+        result
+    }
+    /// INC then SBC (unofficial)
+    pub(crate) fn isc(&mut self, value: u8) -> u8 {
+        let result = value.wrapping_add(1);
+        self.sbc(result);
+        result
+    }
+
+    /// Relative branch using the immediate operand as a signed offset. Taking the branch costs
+    /// an extra cycle, and landing on another page costs one more.
+    pub(crate) fn branch(&mut self, condition: bool) {
+        self.imm_dl();
+        if condition {
+            let target = self.program_counter.wrapping_add(self.state.data_latch as i8 as u16);
+            if target & 0xFF00 != self.program_counter & 0xFF00 {
+                self.state.u_op_queue.push_front(MicroOp::nop);
+            }
+            self.state.u_op_queue.push_front(MicroOp::nop);
+            self.program_counter = target;
+        }
+    }
+
+    /// Address the stack pointer currently points to.
+    pub(crate) fn stack_address(&self) -> u16 {
+        STACK_PAGE | self.stack_ptr as u16
+    }
+    /// Push a byte onto the stack. Pushing with the stack pointer at $00 writes to $0100 and
+    /// wraps it around to $FF.
+    pub(crate) fn push(&mut self, value: u8) {
+        self.write(self.stack_address(), value);
+        self.stack_ptr = self.stack_ptr.wrapping_sub(1);
+    }
+    /// Pull a byte from the stack. Pulling with the stack pointer at $FF wraps it around to $00
+    /// and reads from $0100.
+    pub(crate) fn pull(&mut self) -> u8 {
+        self.stack_ptr = self.stack_ptr.wrapping_add(1);
+        self.read(self.stack_address())
+    }
+    /// Replace the status register with a value pulled from the stack. The break and unused bits
+    /// don't physically exist, so they're ignored.
+    pub(crate) fn set_status_from_stack(&mut self, value: u8) {
+        self.status = Status::from_bits_retain(value & !Status::BREAK.bits() | Status::UNUSED.bits());
+    }
     /// Immediate load into data latch, increment PC
     pub(crate) fn imm_dl(&mut self) {
-        self.state.data_latch = self.bus.borrow_mut().read(self.program_counter);
-        self.program_counter += 1;
+        self.state.data_latch = self.read(self.program_counter);
+        self.program_counter = self.program_counter.wrapping_add(1);
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use crate::{BusAccess, MemoryBus, Nes, StopReason};
+    use crate::testing::{nrom, TestProgram};
+
+    #[test]
+    fn indexed_store_dummy_reads_the_unfixed_address() {
+        // LDX #$03, STA $20FF,X, then loop forever
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&[0xA2, 0x03, 0x9D, 0xFF, 0x20, 0x4C, 0x05, 0x80])).unwrap();
+        nes.reset().unwrap();
+        nes.add_watchpoint(0x2002, BusAccess::Read);
+        nes.add_watchpoint(0x2102, BusAccess::Write);
+        // The high byte isn't fixed up yet on the dummy read: $20FF + 3 reads $2002
+        assert_eq!(nes.run_frame(), Ok(StopReason::Watchpoint(0x2002, BusAccess::Read)));
+        assert_eq!(nes.run_frame(), Ok(StopReason::Watchpoint(0x2102, BusAccess::Write)));
+    }
+
+    #[test]
+    fn stack_wraps_within_page_one() {
+        // LDA #$AA, then 300 PHAs
+        let mut program = vec![0xA9, 0xAA];
+        program.extend([0x48; 300]);
+        let mut test = TestProgram::new(0x0600, &program);
+        let before: Vec<u8> = (0..=0xFFFF).map(|address| test.bus.read(address)).collect();
+        test.cpu.run_instructions(301).unwrap();
+
+        // 300 pushes is a full lap around the stack, and 44 more
+        assert_eq!(test.cpu.stack_pointer(), 0xFDu8.wrapping_sub(44));
+        for address in 0..=0xFFFF {
+            let changed = test.bus.read(address) != before[address as usize];
+            assert_eq!(changed, (0x0100..=0x01FF).contains(&address), "${:04X}", address);
+        }
+    }
+
+    /// Runs `program` from $0600 to its end, with decimal mode enabled or not, and returns A
+    fn run_arithmetic(program: &[u8], decimal_enabled: bool) -> u8 {
+        let mut test = TestProgram::new(0x0600, program);
+        test.cpu.set_decimal_enabled(decimal_enabled);
+        while test.cpu.pc() < 0x0600 + program.len() as u16 {
+            test.cpu.step_instruction().unwrap();
+        }
+        test.cpu.accumulator()
+    }
+
+    #[test]
+    fn decimal_mode_uses_bcd_when_d_is_set() {
+        // SED, CLC, LDA #$15, ADC #$27
+        assert_eq!(run_arithmetic(&[0xF8, 0x18, 0xA9, 0x15, 0x69, 0x27], true), 0x42);
+        // SED, SEC, LDA #$42, SBC #$15
+        assert_eq!(run_arithmetic(&[0xF8, 0x38, 0xA9, 0x42, 0xE9, 0x15], true), 0x27);
+        // SED, CLC, LDA #$58, ADC #$46: carries out as 104
+        assert_eq!(run_arithmetic(&[0xF8, 0x18, 0xA9, 0x58, 0x69, 0x46], true), 0x04);
+        // CLD, CLC, LDA #$15, ADC #$27 stays binary
+        assert_eq!(run_arithmetic(&[0xD8, 0x18, 0xA9, 0x15, 0x69, 0x27], true), 0x3C);
+        // CLD, SEC, LDA #$42, SBC #$15
+        assert_eq!(run_arithmetic(&[0xD8, 0x38, 0xA9, 0x42, 0xE9, 0x15], true), 0x2D);
+    }
+
+    #[test]
+    fn decimal_flag_is_ignored_when_decimal_mode_is_disabled() {
+        let cpu = TestProgram::new(0x0600, &[]).cpu;
+        assert!(!cpu.decimal_enabled());
+        // SED, CLC, LDA #$15, ADC #$27: binary, as on the 2A03
+        assert_eq!(run_arithmetic(&[0xF8, 0x18, 0xA9, 0x15, 0x69, 0x27], false), 0x3C);
+        // SED, SEC, LDA #$42, SBC #$15
+        assert_eq!(run_arithmetic(&[0xF8, 0x38, 0xA9, 0x42, 0xE9, 0x15], false), 0x2D);
+    }
+
+    #[test]
+    fn cycle_count_accumulates_from_reset() {
+        let mut test = TestProgram::new(0x0600, &[
+            0xA9, 0x01,       // LDA #$01: 2 cycles
+            0x8D, 0x00, 0x03, // STA $0300: 4
+            0xA2, 0xFF,       // LDX #$FF: 2
+            0xBD, 0x01, 0x03, // LDA $0301,X: 4, plus 1 for crossing a page
+            0x48,             // PHA: 3
+        ]);
+        assert_eq!(test.cpu.cycle_count(), 7);
+        let cycles = test.cpu.run_instructions(5).unwrap();
+        assert_eq!(cycles, 16);
+        assert_eq!(test.cpu.cycle_count(), 7 + 16);
+    }
+}
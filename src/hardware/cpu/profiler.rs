@@ -0,0 +1,82 @@
+use super::MOS6502;
+
+/// How often an opcode was executed, and how many cycles it took in total.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OpStat {
+    pub count: u64,
+    pub cycles: u64,
+}
+
+/// Tallies executed opcodes while enabled. Interrupt sequences aren't attributed to any opcode,
+/// but cycles the CPU spends stalled (by DMA) count toward the instruction they interrupted.
+pub(crate) struct Profiler {
+    pub enabled: bool,
+    pub stats: [OpStat; 256],
+    /// Opcode of the instruction being executed, if it's being tallied
+    pub current: Option<u8>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            stats: [OpStat::default(); 256],
+            current: None,
+        }
+    }
+
+    /// Counts a fetch of `opcode`, along with the fetch cycle.
+    pub fn fetch(&mut self, opcode: u8) {
+        let stat = &mut self.stats[opcode as usize];
+        stat.count += 1;
+        stat.cycles += 1;
+        self.current = Some(opcode);
+    }
+
+    /// Counts a cycle of the current instruction.
+    pub fn cycle(&mut self) {
+        if let Some(opcode) = self.current {
+            self.stats[opcode as usize].cycles += 1;
+        }
+    }
+}
+
+impl MOS6502 {
+    /// Starts or stops tallying executed opcodes (see `opcode_stats`). Off by default, as it costs
+    /// a little on every cycle.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiler.enabled = enabled;
+        self.profiler.current = None;
+    }
+
+    /// How many times each opcode was executed while profiling, and the cycles spent on it.
+    pub fn opcode_stats(&self) -> [OpStat; 256] {
+        self.profiler.stats
+    }
+
+    /// Clears the tallies gathered so far.
+    pub fn reset_opcode_stats(&mut self) {
+        self.profiler.stats = [OpStat::default(); 256];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TestProgram;
+
+    #[test]
+    fn counts_a_loop() {
+        // LDX #$05, loop: LDA #$01, DEX, BNE loop
+        let mut test = TestProgram::new(0x0600, &[0xA2, 0x05, 0xA9, 0x01, 0xCA, 0xD0, 0xFB]);
+        test.cpu.set_profiling(true);
+        test.cpu.run_instructions(1 + 5 * 3).unwrap();
+        let stats = test.cpu.opcode_stats();
+        assert_eq!(stats[0xA2], OpStat { count: 1, cycles: 2 });
+        assert_eq!(stats[0xA9], OpStat { count: 5, cycles: 10 });
+        assert_eq!(stats[0xCA], OpStat { count: 5, cycles: 10 });
+        // Taken 4 times (3 cycles each), then falls through (2 cycles)
+        assert_eq!(stats[0xD0], OpStat { count: 5, cycles: 14 });
+        assert_eq!(stats.iter().map(|stat| stat.count).sum::<u64>(), 16);
+    }
+}
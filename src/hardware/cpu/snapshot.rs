@@ -0,0 +1,140 @@
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+use super::MOS6502;
+use super::micro_ops::MicroOp;
+use super::status::Status;
+
+/// A copy of everything that makes up the CPU's state, including its internal latches and the
+/// micro-operations still pending. Restoring one resumes execution exactly where it was taken,
+/// even partway through an instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CpuSnapshot {
+    pub program_counter: u16,
+    pub a: u8,
+    pub x: u8,
+    pub y: u8,
+    pub status: u8,
+    pub stack_ptr: u8,
+    pub data_latch: u8,
+    pub abs_addr_latch: u16,
+    pub zpg_addr_latch: u8,
+    /// Remaining micro-operations of the current instruction, in execution order
+    pub u_ops: Vec<MicroOp>,
+    pub nmi_pending: bool,
+    pub cycles: u64,
+}
+
+impl MOS6502 {
+    /// Captures the CPU's current state.
+    pub fn snapshot(&self) -> CpuSnapshot {
+        CpuSnapshot {
+            program_counter: self.program_counter,
+            a: self.a,
+            x: self.x,
+            y: self.y,
+            status: self.status.bits(),
+            stack_ptr: self.stack_ptr,
+            data_latch: self.state.data_latch,
+            abs_addr_latch: self.state.abs_addr_latch,
+            zpg_addr_latch: self.state.zpg_addr_latch,
+            u_ops: self.state.u_op_queue.iter().copied().collect(),
+            nmi_pending: self.state.nmi_pending,
+            cycles: self.cycles,
+        }
+    }
+
+    /// Puts the CPU back into a previously captured state. The rest of the system (memory, PPU,
+    /// etc.) is left as is.
+    pub fn restore(&mut self, snapshot: &CpuSnapshot) {
+        self.program_counter = snapshot.program_counter;
+        self.a = snapshot.a;
+        self.x = snapshot.x;
+        self.y = snapshot.y;
+        self.status = Status::from_bits_retain(snapshot.status);
+        self.stack_ptr = snapshot.stack_ptr;
+        self.state.data_latch = snapshot.data_latch;
+        self.state.abs_addr_latch = snapshot.abs_addr_latch;
+        self.state.zpg_addr_latch = snapshot.zpg_addr_latch;
+        self.state.u_op_queue = snapshot.u_ops.iter().copied().collect();
+        self.state.nmi_pending = snapshot.nmi_pending;
+        self.cycles = snapshot.cycles;
+    }
+}
+
+impl Stateful for MOS6502 {
+    fn save_state(&self, state: &mut StateWriter) {
+        let snapshot = self.snapshot();
+        state.u16(snapshot.program_counter);
+        state.u8(snapshot.a);
+        state.u8(snapshot.x);
+        state.u8(snapshot.y);
+        state.u8(snapshot.status);
+        state.u8(snapshot.stack_ptr);
+        state.u8(snapshot.data_latch);
+        state.u16(snapshot.abs_addr_latch);
+        state.u8(snapshot.zpg_addr_latch);
+        // Micro-ops are stored by their `u8` representation
+        state.u16(snapshot.u_ops.len() as u16);
+        for u_op in snapshot.u_ops {
+            state.u8(u_op as u8);
+        }
+        state.bool(snapshot.nmi_pending);
+        state.u64(snapshot.cycles);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        let mut snapshot = CpuSnapshot {
+            program_counter: state.u16()?,
+            a: state.u8()?,
+            x: state.u8()?,
+            y: state.u8()?,
+            status: state.u8()?,
+            stack_ptr: state.u8()?,
+            data_latch: state.u8()?,
+            abs_addr_latch: state.u16()?,
+            zpg_addr_latch: state.u8()?,
+            u_ops: Vec::new(),
+            nmi_pending: false,
+            cycles: 0,
+        };
+        for _ in 0..state.u16()? {
+            snapshot.u_ops.push(MicroOp::from_u8(state.u8()?).ok_or(RustNesError::InvalidSaveState)?);
+        }
+        snapshot.nmi_pending = state.bool()?;
+        snapshot.cycles = state.u64()?;
+        self.restore(&snapshot);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::TestProgram;
+
+    #[test]
+    fn restoring_mid_instruction_resumes_identically() {
+        // LDA #$10, ADC $0300, TAX, INX, JMP $0600
+        let mut test = TestProgram::new(0x0600, &[0xA9, 0x10, 0x6D, 0x00, 0x03, 0xAA, 0xE8, 0x4C, 0x00, 0x06]);
+        test.bus.load(0x0300, &[0x22]);
+        // The two cycles of LDA, then the fetch and the first operand byte of ADC
+        for _ in 0..4 {
+            test.cpu.step().unwrap();
+        }
+        let snapshot = test.cpu.snapshot();
+        assert!(!snapshot.u_ops.is_empty());
+
+        let run = |test: &mut TestProgram| {
+            for _ in 0..7 {
+                test.cpu.step().unwrap();
+            }
+            test.cpu.snapshot()
+        };
+        let after = run(&mut test);
+        assert_ne!(after, snapshot);
+        assert_eq!(after.x, 0x33);
+
+        test.cpu.restore(&snapshot);
+        assert_eq!(test.cpu.snapshot(), snapshot);
+        assert_eq!(run(&mut test), after);
+    }
+}
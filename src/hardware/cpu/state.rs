@@ -1,17 +1,26 @@
 use std::collections::VecDeque;
-use super::MOS6502;
+use super::instr_def::MAX_INSTR_CYCLES;
+use super::micro_ops::MicroOp;
+
+/// Cycles of DMA stall the micro-op queue has room for up front: a whole OAM DMA (514 at most),
+/// with some DMC sample fetches landing during it.
+const MAX_STALL_CYCLES: usize = 514 + 64;
 
 /// Internal state machine responsible for tracking mid-execution information.
 ///
 /// Contains hidden registers:
 /// - Instruction register: current instruction being operated on
 /// - Address latch: accumulates (16-bit) address to be sent to memory bus
-/// - Micro-op queue: representation of the NES's state machine for its current and future jobs
+/// - Micro-op queue: representation of the NES's state machine for its current and future jobs.
+///   It's sized up front for the longest instruction plus the longest DMA stall, so stepping never
+///   has to grow it.
+/// - NMI latch: whether an NMI is waiting to be serviced at the next instruction boundary
 pub struct MOSState {
     pub data_latch: u8,
     pub abs_addr_latch: u16,
     pub zpg_addr_latch: u8,
-    pub u_op_queue: VecDeque<fn(&mut MOS6502)>
+    pub u_op_queue: VecDeque<MicroOp>,
+    pub nmi_pending: bool,
 }
 
 impl MOSState {
@@ -20,7 +29,8 @@ impl MOSState {
             data_latch: 0,
             abs_addr_latch: 0,
             zpg_addr_latch: 0,
-            u_op_queue: VecDeque::new(),
+            u_op_queue: VecDeque::with_capacity(MAX_INSTR_CYCLES + MAX_STALL_CYCLES),
+            nmi_pending: false,
         }
     }
 }
@@ -1,3 +1,4 @@
+use std::fmt;
 use bitflags::bitflags;
 
 bitflags! {
@@ -13,3 +14,16 @@ bitflags! {
     }
 }
 
+/// Letters for the flags, from bit 7 down to bit 0
+const FLAG_LETTERS: [char; 8] = ['N', 'V', 'U', 'B', 'D', 'I', 'Z', 'C'];
+
+/// The flags as letters, `NVUBDIZC` from bit 7 down to bit 0: uppercase for flags that are set,
+/// lowercase for ones that aren't.
+impl fmt::Debug for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let flags: String = FLAG_LETTERS.iter().enumerate()
+            .map(|(i, letter)| if self.bits() & 0x80 >> i != 0 { *letter } else { letter.to_ascii_lowercase() })
+            .collect();
+        f.write_str(&flags)
+    }
+}
@@ -0,0 +1,122 @@
+//! The documented cycle counts of every opcode, checked against what the CPU actually takes.
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::hardware::*;
+
+/// Documented cycle counts of every opcode on an NMOS 6502, before page-crossing and branch
+/// penalties. 0 marks the JAM opcodes, which lock the CPU up instead.
+const OPCODE_CYCLES: [u8; 256] = [
+    // $x0-$xF
+    7, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 4, 4, 6, 6, // $0x
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // $1x
+    6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 4, 4, 6, 6, // $2x
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // $3x
+    6, 6, 0, 8, 3, 3, 5, 5, 3, 2, 2, 2, 3, 4, 6, 6, // $4x
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // $5x
+    6, 6, 0, 8, 3, 3, 5, 5, 4, 2, 2, 2, 5, 4, 6, 6, // $6x
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // $7x
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // $8x
+    2, 6, 0, 6, 4, 4, 4, 4, 2, 5, 2, 5, 5, 5, 5, 5, // $9x
+    2, 6, 2, 6, 3, 3, 3, 3, 2, 2, 2, 2, 4, 4, 4, 4, // $Ax
+    2, 5, 0, 5, 4, 4, 4, 4, 2, 4, 2, 4, 4, 4, 4, 4, // $Bx
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // $Cx
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // $Dx
+    2, 6, 2, 8, 3, 3, 5, 5, 2, 2, 2, 2, 4, 4, 6, 6, // $Ex
+    2, 5, 0, 8, 4, 4, 6, 6, 2, 4, 2, 7, 4, 4, 7, 7, // $Fx
+];
+
+/// Opcodes that take an extra cycle when indexing crosses a page: the reads in the absolute
+/// indexed and indirect indexed modes. Stores and read-modify-writes always take that cycle, so
+/// it's already part of their base count.
+const PAGE_CROSS_OPCODES: [u8; 32] = [
+    0x11, 0x19, 0x1C, 0x1D, 0x31, 0x39, 0x3C, 0x3D, 0x51, 0x59, 0x5C, 0x5D, 0x71, 0x79, 0x7C, 0x7D,
+    0xB1, 0xB3, 0xB9, 0xBB, 0xBC, 0xBD, 0xBE, 0xBF, 0xD1, 0xD9, 0xDC, 0xDD, 0xF1, 0xF9, 0xFC, 0xFD,
+];
+
+/// Where `check_instruction_timing` places each instruction
+const TIMING_ORIGIN: u16 = 0x0300;
+/// Operand given to every instruction: zero-page $80, absolute $0480, or a branch offset of -128
+/// (which lands on the previous page)
+const TIMING_OPERAND: [u8; 2] = [0x80, 0x04];
+
+/// An instruction that took a different number of cycles than documented.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TimingMismatch {
+    opcode: u8,
+    /// Which variant was run: "base", "page cross", "branch taken" or "branch not taken"
+    case: &'static str,
+    expected: u8,
+    /// 0 if the CPU doesn't implement the opcode
+    actual: u8,
+}
+
+/// Runs every opcode on a bare CPU and compares the cycles taken against `OPCODE_CYCLES`, then
+/// reruns them with indexes that cross a page (expecting an extra cycle only from the ones in
+/// `PAGE_CROSS_OPCODES`), and every branch both taken and not. Returns the instructions whose
+/// timing is off, if any.
+///
+/// Each instruction runs from $0300, with $0480 as its absolute operand and the zero-page pointer
+/// at $80 pointing there too. Indexes are 0 for the base case and $80 for the page-crossing one.
+fn check_instruction_timing() -> Vec<TimingMismatch> {
+    let mut mismatches = Vec::new();
+    let mut check = |opcode: u8, case, expected: u8, actual: u8| {
+        if actual != expected {
+            mismatches.push(TimingMismatch { opcode, case, expected, actual });
+        }
+    };
+    for opcode in 0..=255u8 {
+        let expected = OPCODE_CYCLES[opcode as usize];
+        if opcode & 0x1F == 0x10 {
+            // Branches: the operand lands on another page, which costs 2 extra cycles if taken
+            let (with_flags_clear, with_flags_set) = (time_instruction(opcode, 0, 0x00), time_instruction(opcode, 0, 0xFF));
+            let taken = if opcode & 0x20 == 0 { with_flags_clear } else { with_flags_set };
+            let not_taken = if opcode & 0x20 == 0 { with_flags_set } else { with_flags_clear };
+            check(opcode, "branch taken", expected + 2, taken);
+            check(opcode, "branch not taken", expected, not_taken);
+            continue;
+        }
+        check(opcode, "base", expected, time_instruction(opcode, 0x00, 0x00));
+        // Everything else, indexed stores included, must not be slowed down by the page crossing
+        let penalty = PAGE_CROSS_OPCODES.contains(&opcode) as u8;
+        check(opcode, "page cross", expected + penalty, time_instruction(opcode, 0x80, 0x00));
+    }
+    mismatches
+}
+
+/// Runs a single instruction on a fresh CPU, with both index registers set to `index`, and
+/// returns the cycles it took (0 if the opcode isn't implemented).
+fn time_instruction(opcode: u8, index: u8, status: u8) -> u8 {
+    let bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+    {
+        let bus = bus.borrow();
+        bus.write(TIMING_ORIGIN, opcode);
+        bus.write(TIMING_ORIGIN + 1, TIMING_OPERAND[0]);
+        bus.write(TIMING_ORIGIN + 2, TIMING_OPERAND[1]);
+        bus.write(TIMING_OPERAND[0] as u16, TIMING_OPERAND[0]);
+        bus.write(TIMING_OPERAND[0] as u16 + 1, TIMING_OPERAND[1]);
+    }
+    let mut cpu = MOS6502::new(bus);
+    let mut snapshot = cpu.snapshot();
+    snapshot.program_counter = TIMING_ORIGIN;
+    snapshot.x = index;
+    snapshot.y = index;
+    snapshot.status = status;
+    cpu.restore(&snapshot);
+    cpu.step_instruction().unwrap_or(0)
+}
+
+#[test]
+fn instructions_take_their_documented_cycles() {
+    let mismatches = check_instruction_timing();
+    assert!(mismatches.is_empty(), "{:#04X?}", mismatches);
+}
+
+#[test]
+fn instruction_table_matches_the_documented_cycles() {
+    // Each definition lists the cycles after the opcode fetch; JAMs list none
+    for (opcode, instr) in MOS6502::instruction_table().iter().enumerate() {
+        let expected = OPCODE_CYCLES[opcode].saturating_sub(1) as usize;
+        assert_eq!(instr.cycles, expected, "{:02X} ({})", opcode, instr.mnemonic);
+    }
+}
@@ -0,0 +1,40 @@
+/// A device plugged into the expansion port, under the console. It sees every write to $4016 and
+/// can drive bits 1-4 of reads from $4016 and $4017, which the controllers leave alone. See
+/// `Bus::connect_expansion`.
+///
+/// Devices aren't part of save states; their state is up to them.
+///
+/// # Examples
+///
+/// ```rust
+/// use std::cell::RefCell;
+/// use rust_nes::hardware::*;
+///
+/// /// Reports whatever was last written to $4016, shifted into bits 1-3 of $4017
+/// struct Echo(u8);
+///
+/// impl ExpansionDevice for Echo {
+///     fn peek(&self, port: usize) -> u8 {
+///         if port == 1 { (self.0 & 0x07) << 1 } else { 0 }
+///     }
+///     fn write(&mut self, value: u8) {
+///         self.0 = value;
+///     }
+/// }
+///
+/// let mut my_bus = Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()));
+/// my_bus.connect_expansion(Box::new(Echo(0)));
+/// my_bus.write(0x4016, 0x06);
+/// assert_eq!(my_bus.read(0x4017) & 0x1E, 0x0C);
+/// ```
+pub trait ExpansionDevice {
+    /// The value read from $4016 (port 0) or $4017 (port 1). Only bits 1-4 are used. Defaults to
+    /// `peek`, for devices whose reads have no side effects.
+    fn read(&mut self, port: usize) -> u8 {
+        self.peek(port)
+    }
+    /// Returns what `read` would, without any side effects.
+    fn peek(&self, port: usize) -> u8;
+    /// Handles a write to $4016. Bits 0-2 go out to the expansion port.
+    fn write(&mut self, _value: u8) {}
+}
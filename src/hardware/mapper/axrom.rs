@@ -0,0 +1,102 @@
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter};
+use super::{Mapper, Mirroring, CHR_BANK_SIZE};
+
+/// Size of the switchable PRG-ROM bank.
+const PRG_WINDOW_SIZE: usize = 0x8000;
+
+/// Mapper 7 (AxROM)
+///
+/// PRG-ROM is switched in 32KB banks covering all of $8000-$FFFF, and the cartridge has 8KB of
+/// CHR-RAM. A write anywhere in $8000-$FFFF selects the bank (bits 0-2) and which 1KB of
+/// nametable RAM all four nametables show (bit 4).
+pub struct Axrom {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+    /// Last value written to $8000-$FFFF
+    bank_select: u8,
+}
+
+impl Axrom {
+    pub fn new(prg_rom: Vec<u8>, chr: Vec<u8>) -> Self {
+        let chr_ram = chr.is_empty();
+        Self {
+            prg_rom,
+            chr: if chr_ram { vec![0; CHR_BANK_SIZE] } else { chr },
+            chr_ram,
+            bank_select: 0,
+        }
+    }
+}
+
+impl Mapper for Axrom {
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        self.cpu_peek(address)
+    }
+    fn cpu_peek(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000..=0xFFFF if !self.prg_rom.is_empty() => {
+                let bank = (self.bank_select & 0x07) as usize * PRG_WINDOW_SIZE;
+                Some(self.prg_rom[(bank + address as usize - 0x8000) % self.prg_rom.len()])
+            }
+            _ => None,
+        }
+    }
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        if address >= 0x8000 {
+            self.bank_select = value;
+        }
+    }
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.ppu_peek(address)
+    }
+    fn ppu_peek(&self, address: u16) -> u8 {
+        self.chr[address as usize % self.chr.len()]
+    }
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        if self.chr_ram {
+            let len = self.chr.len();
+            self.chr[address as usize % len] = value;
+        }
+    }
+    fn mirroring(&self) -> Mirroring {
+        if self.bank_select & 0x10 == 0 { Mirroring::SingleScreenLower } else { Mirroring::SingleScreenUpper }
+    }
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.bank_select);
+        if self.chr_ram {
+            state.bytes(&self.chr);
+        }
+    }
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.bank_select = state.u8()?;
+        if self.chr_ram {
+            state.bytes(&mut self.chr)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bank_writes_switch_prg_and_the_nametable() {
+        // 4 banks, each filled with its bank number
+        let prg = (0..4).flat_map(|bank| [bank; PRG_WINDOW_SIZE]).collect();
+        let mut mapper = Axrom::new(prg, vec![]);
+        let window = |mapper: &Axrom| [0x8000, 0xC000, 0xFFFF].map(|address| mapper.cpu_peek(address).unwrap());
+        assert_eq!(window(&mapper), [0; 3]);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenLower);
+
+        mapper.cpu_write(0xC123, 0x12);
+        assert_eq!(window(&mapper), [2; 3]);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenUpper);
+
+        mapper.cpu_write(0x8000, 0x03);
+        assert_eq!(window(&mapper), [3; 3]);
+        assert_eq!(mapper.mirroring(), Mirroring::SingleScreenLower);
+    }
+}
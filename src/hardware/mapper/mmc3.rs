@@ -0,0 +1,260 @@
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter};
+use super::{Mapper, Mirroring, CHR_BANK_SIZE};
+
+/// Size of a switchable PRG-ROM bank.
+const PRG_WINDOW_SIZE: usize = 0x2000;
+/// Size of the smallest switchable CHR bank.
+const CHR_WINDOW_SIZE: usize = 0x0400;
+
+/// Mapper 4 (MMC3)
+///
+/// PRG-ROM is switched in 8KB banks: $8000-$9FFF (or $C000-$DFFF) and $A000-$BFFF are selectable,
+/// the other of $8000/$C000 is fixed to the second-to-last bank and $E000-$FFFF to the last. CHR
+/// is switched as two 2KB and four 1KB banks, and either half of the pattern tables can hold the
+/// 2KB ones. Mirroring is selectable, unless the cartridge is wired for four screens.
+///
+/// Registers (even/odd addresses within each range):
+/// - $8000/$8001: bank select (which register the next bank data write goes to, plus the PRG and
+///   CHR layouts) / bank data
+/// - $A000/$A001: mirroring / PRG-RAM protect (ignored, PRG-RAM is always enabled)
+/// - $C000/$C001: IRQ latch / IRQ reload
+/// - $E000/$E001: IRQ disable (also acknowledges it) / IRQ enable
+///
+/// The IRQ counter is clocked by A12 rising on the PPU's address bus, once a scanline while
+/// rendering. When clocked at 0 (or after a reload request) it's reloaded from the latch,
+/// otherwise it counts down; if that leaves it at 0 with IRQs enabled, the IRQ line is pulled.
+pub struct Mmc3 {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+    /// Last value written to $8000
+    bank_select: u8,
+    /// Bank registers R0-R7: R0/R1 are 2KB CHR banks, R2-R5 1KB CHR banks, R6/R7 PRG banks
+    banks: [u8; 8],
+    mirroring: Mirroring,
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+}
+
+impl Mmc3 {
+    pub fn new(prg_rom: Vec<u8>, chr: Vec<u8>, mirroring: Mirroring) -> Self {
+        let chr_ram = chr.is_empty();
+        Self {
+            prg_rom,
+            chr: if chr_ram { vec![0; CHR_BANK_SIZE] } else { chr },
+            chr_ram,
+            bank_select: 0,
+            banks: [0; 8],
+            mirroring,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+        }
+    }
+
+    /// Whether PRG-ROM splits into whole 8KB banks and CHR-ROM (unless empty, for CHR-RAM) into
+    /// whole 1KB ones.
+    pub fn fits_banks(prg_rom: &[u8], chr: &[u8]) -> bool {
+        prg_rom.len().is_multiple_of(PRG_WINDOW_SIZE) && chr.len().is_multiple_of(CHR_WINDOW_SIZE)
+    }
+
+    /// Offset into PRG-ROM for a CPU address in $8000-$FFFF.
+    fn prg_offset(&self, address: u16) -> usize {
+        let bank_count = self.prg_rom.len() / PRG_WINDOW_SIZE;
+        let second_last = bank_count.saturating_sub(2);
+        let swapped = self.bank_select & 0x40 != 0;
+        let bank = match (address - 0x8000) as usize / PRG_WINDOW_SIZE {
+            0 if swapped => second_last,
+            0 => self.banks[6] as usize,
+            1 => self.banks[7] as usize,
+            2 if swapped => self.banks[6] as usize,
+            2 => second_last,
+            _ => bank_count.saturating_sub(1),
+        };
+        (bank % bank_count.max(1)) * PRG_WINDOW_SIZE + address as usize % PRG_WINDOW_SIZE
+    }
+
+    /// Offset into CHR for a PPU address in $0000-$1FFF.
+    fn chr_offset(&self, address: u16) -> usize {
+        // With CHR inversion, the 2KB banks go in the upper pattern table instead
+        let address = if self.bank_select & 0x80 != 0 { address ^ 0x1000 } else { address } as usize;
+        let bank = match address / CHR_WINDOW_SIZE {
+            0 => self.banks[0] & 0xFE,
+            1 => self.banks[0] | 0x01,
+            2 => self.banks[1] & 0xFE,
+            3 => self.banks[1] | 0x01,
+            window => self.banks[window - 2],
+        } as usize;
+        let bank_count = self.chr.len() / CHR_WINDOW_SIZE;
+        (bank % bank_count) * CHR_WINDOW_SIZE + address % CHR_WINDOW_SIZE
+    }
+}
+
+impl Mapper for Mmc3 {
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        self.cpu_peek(address)
+    }
+    fn cpu_peek(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000..=0xFFFF if !self.prg_rom.is_empty() => Some(self.prg_rom[self.prg_offset(address)]),
+            _ => None,
+        }
+    }
+    fn cpu_write(&mut self, address: u16, value: u8) {
+        match address & 0xE001 {
+            0x8000 => self.bank_select = value,
+            0x8001 => self.banks[self.bank_select as usize & 0x07] = value,
+            0xA000 if self.mirroring != Mirroring::FourScreen => {
+                self.mirroring = if value & 1 == 0 { Mirroring::Vertical } else { Mirroring::Horizontal };
+            }
+            0xC000 => self.irq_latch = value,
+            0xC001 => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            0xE000 => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            0xE001 => self.irq_enabled = true,
+            // PRG-RAM protect at $A001, or below $8000
+            _ => {}
+        }
+    }
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.ppu_peek(address)
+    }
+    fn ppu_peek(&self, address: u16) -> u8 {
+        self.chr[self.chr_offset(address)]
+    }
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        if self.chr_ram {
+            let offset = self.chr_offset(address);
+            self.chr[offset] = value;
+        }
+    }
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+    fn ppu_a12_rise(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.bank_select);
+        state.bytes(&self.banks);
+        // Four-screen cartridges can't change theirs, so that's all there is to mirroring
+        state.bool(self.mirroring == Mirroring::Horizontal);
+        state.u8(self.irq_latch);
+        state.u8(self.irq_counter);
+        state.bool(self.irq_reload);
+        state.bool(self.irq_enabled);
+        state.bool(self.irq_pending);
+        if self.chr_ram {
+            state.bytes(&self.chr);
+        }
+    }
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.bank_select = state.u8()?;
+        state.bytes(&mut self.banks)?;
+        let horizontal = state.bool()?;
+        if self.mirroring != Mirroring::FourScreen {
+            self.mirroring = if horizontal { Mirroring::Horizontal } else { Mirroring::Vertical };
+        }
+        self.irq_latch = state.u8()?;
+        self.irq_counter = state.u8()?;
+        self.irq_reload = state.bool()?;
+        self.irq_enabled = state.bool()?;
+        self.irq_pending = state.bool()?;
+        if self.chr_ram {
+            state.bytes(&mut self.chr)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::NESHeader;
+    use crate::hardware::Cart;
+
+    /// An MMC3 with 8 PRG-ROM banks and 32 CHR-ROM banks, each filled with its bank number
+    fn mmc3() -> Mmc3 {
+        let prg = (0..8).flat_map(|bank| [bank; PRG_WINDOW_SIZE]).collect();
+        let chr = (0..32).flat_map(|bank| [bank; CHR_WINDOW_SIZE]).collect();
+        Mmc3::new(prg, chr, Mirroring::Vertical)
+    }
+
+    fn set_bank(mapper: &mut Mmc3, bank_select: u8, bank: u8) {
+        mapper.cpu_write(0x8000, bank_select);
+        mapper.cpu_write(0x8001, bank);
+    }
+
+    #[test]
+    fn bank_registers() {
+        let mut mapper = mmc3();
+        set_bank(&mut mapper, 6, 3);
+        set_bank(&mut mapper, 7, 5);
+        let prg_banks = |mapper: &Mmc3| [0x8000, 0xA000, 0xC000, 0xE000].map(|address| mapper.cpu_peek(address).unwrap());
+        assert_eq!(prg_banks(&mapper), [3, 5, 6, 7]);
+        // PRG mode 1 swaps $8000 and $C000
+        mapper.cpu_write(0x8000, 0x40);
+        assert_eq!(prg_banks(&mapper), [6, 5, 3, 7]);
+
+        set_bank(&mut mapper, 0, 4);
+        set_bank(&mut mapper, 2, 9);
+        let chr_banks = |mapper: &Mmc3| [0x0000, 0x0400, 0x1000].map(|address| mapper.ppu_peek(address));
+        assert_eq!(chr_banks(&mapper), [4, 5, 9]);
+        // CHR inversion puts the 2KB banks in the upper pattern table
+        mapper.cpu_write(0x8000, 0x80);
+        assert_eq!([mapper.ppu_peek(0x1000), mapper.ppu_peek(0x1400), mapper.ppu_peek(0x0000)], [4, 5, 9]);
+    }
+
+    #[test]
+    fn irq_fires_after_the_latched_count() {
+        let mut mapper = mmc3();
+        mapper.cpu_write(0xC000, 3);
+        mapper.cpu_write(0xC001, 0);
+        mapper.cpu_write(0xE001, 0);
+        // The first rise reloads the counter, then 3 more count it down to 0
+        for _ in 0..3 {
+            mapper.ppu_a12_rise();
+            assert!(!mapper.irq());
+        }
+        mapper.ppu_a12_rise();
+        assert!(mapper.irq());
+        mapper.cpu_write(0xE000, 0);
+        assert!(!mapper.irq());
+    }
+
+    #[test]
+    fn partial_banks_are_rejected() {
+        // NES 2.0, mapper 4, with one size in exponent form: 2^1 * 7 = 14 bytes
+        let rom = |prg_lsb: u8, chr_lsb: u8, size_msb: u8| {
+            let mut rom = vec![b'N', b'E', b'S', 0x1A, prg_lsb, chr_lsb, 0x40, 0x08, 0, size_msb, 0, 0, 0, 0, 0, 0];
+            let header = NESHeader::from_bytes(&rom).unwrap();
+            rom.resize(header.file_size(), 0);
+            rom
+        };
+        assert_eq!(Cart::from_rom(&rom(0x07, 1, 0x0F)).err(), Some(RustNesError::InvalidHeader));
+        assert_eq!(Cart::from_rom(&rom(1, 0x07, 0xF0)).err(), Some(RustNesError::InvalidHeader));
+        assert!(Cart::from_rom(&rom(1, 1, 0x00)).is_ok());
+    }
+}
@@ -0,0 +1,97 @@
+pub mod axrom;
+pub mod mmc3;
+pub mod nrom;
+
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter};
+pub use axrom::Axrom;
+pub use mmc3::Mmc3;
+pub use nrom::Nrom;
+
+/// Size of a PRG-ROM bank, as counted by the header.
+pub const PRG_BANK_SIZE: usize = 0x4000;
+/// Size of a CHR-ROM bank, as counted by the header.
+pub const CHR_BANK_SIZE: usize = 0x2000;
+
+/// How the four nametables at PPU $2000-$2FFF map onto nametable RAM. The console has 2KB of it,
+/// enough for two nametables, and the cartridge decides which of them each address reaches.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mirroring {
+    /// $2000 = $2400 and $2800 = $2C00 (vertical arrangement)
+    Horizontal,
+    /// $2000 = $2800 and $2400 = $2C00 (horizontal arrangement)
+    Vertical,
+    /// All four nametables are the first 1KB of nametable RAM
+    SingleScreenLower,
+    /// All four nametables are the second 1KB of nametable RAM
+    SingleScreenUpper,
+    /// The cartridge adds another 2KB of RAM, so all four nametables are distinct
+    FourScreen,
+}
+
+impl Mirroring {
+    /// Maps a PPU nametable address ($2000-$3EFF) to an offset into nametable RAM, where the
+    /// first 2KB are the console's own and the next 2KB are a four-screen cartridge's.
+    pub fn nametable_offset(self, address: u16) -> usize {
+        let address = address as usize & 0x0FFF;
+        let offset = address & 0x03FF;
+        match self {
+            Mirroring::Horizontal => (address & 0x0800) >> 1 | offset,
+            Mirroring::Vertical => address & 0x0400 | offset,
+            Mirroring::SingleScreenLower => offset,
+            Mirroring::SingleScreenUpper => 0x0400 | offset,
+            Mirroring::FourScreen => address,
+        }
+    }
+}
+
+/// Cartridge circuitry that decides how the CPU and PPU address spaces map onto the cartridge's
+/// memory chips. Mappers own the PRG and CHR memory they control.
+pub trait Mapper {
+    /// Read byte from given CPU address ($4020-$FFFF). Returns `None` if nothing is mapped there.
+    fn cpu_read(&mut self, address: u16) -> Option<u8>;
+    /// Read byte from given CPU address ($4020-$FFFF), without any side effects the read would
+    /// normally have. Returns `None` if nothing is mapped there.
+    fn cpu_peek(&self, address: u16) -> Option<u8>;
+    /// Write byte to given CPU address ($4020-$FFFF).
+    fn cpu_write(&mut self, address: u16, value: u8);
+    /// Read byte from given PPU address ($0000-$1FFF).
+    fn ppu_read(&mut self, address: u16) -> u8;
+    /// Read byte from given PPU address ($0000-$1FFF), without any side effects the read would
+    /// normally have.
+    fn ppu_peek(&self, address: u16) -> u8;
+    /// Write byte to given PPU address ($0000-$1FFF). Ignored unless the cartridge has CHR-RAM.
+    fn ppu_write(&mut self, address: u16, value: u8);
+    /// The current nametable mirroring. Mappers with a mirroring control register can change
+    /// this at any time, so the PPU asks on every nametable access.
+    fn mirroring(&self) -> Mirroring;
+    /// Called when A12 on the PPU's address bus rises after having been low for a while, which
+    /// happens about once a scanline while rendering. MMC3 counts scanlines this way.
+    fn ppu_a12_rise(&mut self) {}
+    /// Whether the mapper is holding the CPU's IRQ line low.
+    fn irq(&self) -> bool {
+        false
+    }
+    /// Saves the mapper's registers, and any RAM it owns (like CHR-RAM), into a save state.
+    /// Mappers without either don't need to implement this.
+    fn save_state(&self, _state: &mut StateWriter) {}
+    /// Restores what `save_state` saved.
+    fn load_state(&mut self, _state: &mut StateReader) -> Result<(), RustNesError> {
+        Ok(())
+    }
+}
+
+/// Constructs the mapper with the given iNES mapper number.
+///
+/// An empty `chr` means the cartridge uses 8KB of CHR-RAM instead of CHR-ROM. `mirroring` is the
+/// one the header asks for, which mappers without mirroring control keep for good.
+pub fn new_mapper(mapper_number: usize, prg: Vec<u8>, chr: Vec<u8>, mirroring: Mirroring) -> Result<Box<dyn Mapper>, RustNesError> {
+    match mapper_number {
+        0 => Ok(Box::new(Nrom::new(prg, chr, mirroring))),
+        // Odd sizes are possible with NES 2.0 headers, but the bank arithmetic needs whole banks
+        4 if !Mmc3::fits_banks(&prg, &chr) => Err(RustNesError::InvalidHeader),
+        4 => Ok(Box::new(Mmc3::new(prg, chr, mirroring))),
+        7 => Ok(Box::new(Axrom::new(prg, chr))),
+        n => Err(RustNesError::UnsupportedMapper(n)),
+    }
+}
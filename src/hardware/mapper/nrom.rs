@@ -0,0 +1,95 @@
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter};
+use super::{Mapper, Mirroring, CHR_BANK_SIZE};
+
+/// Mapper 0 (NROM)
+///
+/// 16KB or 32KB of PRG-ROM at $8000-$FFFF (16KB carts are mirrored into $C000-$FFFF), and 8KB of
+/// CHR-ROM or CHR-RAM. No bank switching, and the mirroring is hardwired.
+pub struct Nrom {
+    prg_rom: Vec<u8>,
+    chr: Vec<u8>,
+    chr_ram: bool,
+    mirroring: Mirroring,
+}
+
+impl Nrom {
+    pub fn new(prg_rom: Vec<u8>, chr: Vec<u8>, mirroring: Mirroring) -> Self {
+        let chr_ram = chr.is_empty();
+        Self {
+            prg_rom,
+            chr: if chr_ram { vec![0; CHR_BANK_SIZE] } else { chr },
+            chr_ram,
+            mirroring,
+        }
+    }
+}
+
+impl Mapper for Nrom {
+    fn cpu_read(&mut self, address: u16) -> Option<u8> {
+        self.cpu_peek(address)
+    }
+    fn cpu_peek(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000..=0xFFFF if !self.prg_rom.is_empty() => {
+                Some(self.prg_rom[(address as usize - 0x8000) % self.prg_rom.len()])
+            }
+            _ => None,
+        }
+    }
+    fn cpu_write(&mut self, address: u16, _value: u8) {
+        // NROM has no registers, so writes to ROM go nowhere
+        if cfg!(debug_assertions) {
+            eprintln!("WARNING: attempted to write to read-only address: {}", address);
+        }
+    }
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.ppu_peek(address)
+    }
+    fn ppu_peek(&self, address: u16) -> u8 {
+        self.chr[address as usize % self.chr.len()]
+    }
+    fn ppu_write(&mut self, address: u16, value: u8) {
+        if self.chr_ram {
+            let len = self.chr.len();
+            self.chr[address as usize % len] = value;
+        }
+    }
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+    fn save_state(&self, state: &mut StateWriter) {
+        if self.chr_ram {
+            state.bytes(&self.chr);
+        }
+    }
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        if self.chr_ram {
+            state.bytes(&mut self.chr)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::Cart;
+    use crate::testing::nrom;
+
+    #[test]
+    fn writes_to_rom_are_ignored() {
+        let mut mapper = Nrom::new(vec![0xEA; 0x4000], vec![], Mirroring::Vertical);
+        mapper.cpu_write(0x8000, 0x00);
+        mapper.cpu_write(0xFFFF, 0x00);
+        assert_eq!((mapper.cpu_read(0x8000), mapper.cpu_read(0xFFFF)), (Some(0xEA), Some(0xEA)));
+    }
+
+    #[test]
+    fn prg_ram_writes_read_back() {
+        let mut cart = Cart::from_rom(&nrom(&[])).unwrap();
+        cart.write(0x6000, 0x42);
+        cart.write(0x8000, 0x42);
+        assert_eq!((cart.read(0x6000), cart.read(0x8000)), (Some(0x42), Some(0x00)));
+    }
+}
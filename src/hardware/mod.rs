@@ -2,14 +2,26 @@
 pub mod apu;
 pub mod bus;
 pub mod cart;
+pub mod controller;
 pub mod cpu;
+pub mod expansion;
+pub mod mapper;
 pub mod ppu;
 pub mod ram;
+pub mod region;
+pub mod ricoh;
+pub mod zapper;
 
 pub use apu::APU;
-pub use bus::Bus;
-pub use cart::Cart;
-pub use cpu::MOS6502;
+pub use bus::{Bus, BusAccess, MemoryBus};
+pub use cart::{load_cartridge, Cart};
+pub use controller::{Buttons, Controller, ControllerPort};
+pub use cpu::{CpuConfig, CpuSnapshot, InvalidOpcodePolicy, MOS6502, OpStat};
+pub use expansion::ExpansionDevice;
+pub use mapper::{Mapper, Mirroring};
 pub use ppu::PPU;
-pub use ram::WorkMemory;
+pub use ram::{RamInit, WorkMemory};
+pub use region::Region;
+pub use ricoh::Ricoh2A03;
+pub use zapper::Zapper;
 
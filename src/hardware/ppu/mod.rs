@@ -0,0 +1,766 @@
+mod render;
+
+use bitflags::bitflags;
+use crate::RustNesError;
+use crate::hardware::{Cart, Mirroring, Region};
+use crate::palette::nes_color_to_rgb;
+use std::cell::RefCell;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+use render::{BackgroundShifters, BackgroundTile, LineSprite, SPRITES_PER_LINE};
+
+bitflags! {
+    /// PPUCTRL ($2000)
+    #[derive(Clone, Copy)]
+    pub(crate) struct PpuCtrl: u8 {
+        const NAMETABLE_X       = 0b0000_0001;
+        const NAMETABLE_Y       = 0b0000_0010;
+        const VRAM_INCREMENT    = 0b0000_0100;
+        const SPRITE_TABLE      = 0b0000_1000;
+        const BG_TABLE          = 0b0001_0000;
+        const SPRITE_SIZE       = 0b0010_0000;
+        const MASTER_SLAVE      = 0b0100_0000;
+        const NMI_ENABLE        = 0b1000_0000;
+    }
+}
+
+bitflags! {
+    /// PPUMASK ($2001)
+    #[derive(Clone, Copy)]
+    pub(crate) struct PpuMask: u8 {
+        const GRAYSCALE         = 0b0000_0001;
+        const SHOW_BG_LEFT      = 0b0000_0010;
+        const SHOW_SPRITES_LEFT = 0b0000_0100;
+        const SHOW_BG           = 0b0000_1000;
+        const SHOW_SPRITES      = 0b0001_0000;
+        const EMPHASIZE_RED     = 0b0010_0000;
+        const EMPHASIZE_GREEN   = 0b0100_0000;
+        const EMPHASIZE_BLUE    = 0b1000_0000;
+    }
+}
+
+bitflags! {
+    /// PPUSTATUS ($2002)
+    #[derive(Clone, Copy)]
+    pub(crate) struct PpuStatus: u8 {
+        const SPRITE_OVERFLOW   = 0b0010_0000;
+        const SPRITE_0_HIT      = 0b0100_0000;
+        const VBLANK            = 0b1000_0000;
+    }
+}
+
+/// Dots (PPU clock cycles) per scanline.
+pub const DOTS_PER_SCANLINE: u16 = 341;
+/// Width of the picture, in pixels.
+pub const SCREEN_WIDTH: usize = 256;
+/// Height of the picture, in pixels.
+pub const SCREEN_HEIGHT: usize = 240;
+/// Size of nametable RAM: the console's 2KB, plus the 2KB a four-screen cartridge adds.
+pub const NAMETABLE_RAM_SIZE: usize = 0x1000;
+/// Size of palette RAM, at $3F00-$3F1F in PPU address space.
+pub const PALETTE_RAM_SIZE: usize = 0x20;
+/// Size of OAM (sprite memory): 64 sprites of 4 bytes.
+pub const OAM_SIZE: usize = 0x100;
+/// Dots A12 has to stay low before it rising again counts, like MMC3's filter (about 3 CPU
+/// cycles). Keeps the short dips between pattern fetches from clocking the counter.
+const A12_FILTER_DOTS: u16 = 9;
+/// Scanlines a bright pixel keeps a Zapper's photodiode sensing light for, after it's drawn.
+const LIGHT_SCANLINES: i16 = 20;
+/// How bright a pixel has to be (the average of its red, green and blue) for a Zapper to sense it.
+const LIGHT_THRESHOLD: u16 = 0xC0;
+
+/// Picture Processing Unit (Ricoh 2C02).
+///
+/// The PPU runs at 3 times the speed of the CPU (3.2 on PAL), and its clock is tracked in
+/// scanlines and dots. Scanline -1 is the pre-render line, 0-239 are visible, 240 is idle and the
+/// rest are vblank (241-260 on NTSC, see `Region`).
+///
+/// Visible scanlines draw one pixel per dot (during dots 1-256), from the background and the up
+/// to 8 sprites picked for the line, as PPUMASK allows. With rendering disabled, the picture is
+/// the backdrop color.
+///
+/// Scrolling works through the "loopy" registers: the VRAM address `v`, which rendering walks
+/// across the nametables tile by tile and line by line, the temporary address `t` that PPUCTRL,
+/// PPUSCROLL and PPUADDR writes assemble and that gets copied into `v` at the start of each line
+/// (horizontal bits) and frame (vertical bits), the fine X scroll `x`, and the write toggle `w`
+/// shared by the two-write registers. `v` and `t` are laid out as `yyy NN YYYYY XXXXX`: fine Y,
+/// nametable, coarse Y and coarse X.
+pub struct PPU {
+    region: Region,
+    ctrl: PpuCtrl,
+    mask: PpuMask,
+    status: PpuStatus,
+    scanline: i16,
+    dot: u16,
+    /// The PPU's own data bus, which write-only registers and unused bits read back from
+    io_latch: u8,
+    /// Set when the PPU pulls the CPU's NMI line low, cleared once the CPU acknowledges it.
+    nmi_pending: bool,
+    /// Number of frames completed (incremented upon entering vblank)
+    frame: u64,
+    /// The picture, as one NES color number per pixel
+    framebuffer: Vec<u8>,
+    /// Nametable RAM, laid out as `Mirroring::nametable_offset` addresses it
+    nametables: [u8; NAMETABLE_RAM_SIZE],
+    /// Palette RAM: NES color numbers for the background and sprite palettes
+    palette: [u8; PALETTE_RAM_SIZE],
+    /// Current VRAM address (`v`), which PPUDATA accesses and rendering fetches from
+    vram_addr: u16,
+    /// Temporary VRAM address (`t`), assembled by PPUCTRL, PPUSCROLL and PPUADDR writes
+    temp_addr: u16,
+    /// Fine X scroll (`x`): which pixel of the first tile a line starts at
+    fine_x: u8,
+    /// Whether the next PPUSCROLL or PPUADDR write is the second of the pair (`w`, cleared by
+    /// reading PPUSTATUS)
+    write_toggle: bool,
+    /// What PPUDATA reads return next, for addresses below the palettes
+    read_buffer: u8,
+    /// Object attribute memory: position, tile and attributes of each sprite
+    oam: [u8; OAM_SIZE],
+    /// Where OAMDATA accesses go, set through OAMADDR
+    oam_addr: u8,
+    /// The background tile fetched last, for the shifters to load next
+    background_tile: BackgroundTile,
+    background_shifters: BackgroundShifters,
+    /// The sprites picked for the current scanline, in priority order
+    line_sprites: [LineSprite; SPRITES_PER_LINE],
+    line_sprite_count: usize,
+    /// Whether sprite 0 is among them (in the first slot), for sprite 0 hit
+    sprite_zero_in_line: bool,
+    /// Dots since A12 was last high on the PPU's address bus, for the cartridge's A12 watch
+    a12_low_dots: u16,
+}
+
+impl Default for PPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PPU {
+    pub fn new() -> Self {
+        Self {
+            region: Region::default(),
+            ctrl: PpuCtrl::empty(),
+            mask: PpuMask::empty(),
+            status: PpuStatus::empty(),
+            scanline: -1,
+            dot: 0,
+            io_latch: 0,
+            nmi_pending: false,
+            frame: 0,
+            framebuffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            nametables: [0; NAMETABLE_RAM_SIZE],
+            palette: [0; PALETTE_RAM_SIZE],
+            vram_addr: 0,
+            temp_addr: 0,
+            fine_x: 0,
+            write_toggle: false,
+            read_buffer: 0,
+            oam: [0; OAM_SIZE],
+            oam_addr: 0,
+            background_tile: BackgroundTile::default(),
+            background_shifters: BackgroundShifters::default(),
+            line_sprites: [LineSprite::default(); SPRITES_PER_LINE],
+            line_sprite_count: 0,
+            sprite_zero_in_line: false,
+            a12_low_dots: 0,
+        }
+    }
+
+    /// Advances the PPU by one dot. `cart` is told when A12 rises (see `Mapper::ppu_a12_rise`).
+    pub fn tick(&mut self, cart: Option<&RefCell<Cart>>) {
+        self.dot += 1;
+        if self.dot == DOTS_PER_SCANLINE {
+            self.dot = 0;
+            self.scanline += 1;
+            if self.scanline > self.region.last_scanline() {
+                self.scanline = -1;
+            }
+        }
+
+        if self.dot == 1 {
+            match self.scanline {
+                scanline if scanline == self.region.vblank_scanline() => {
+                    self.frame += 1;
+                    self.status.insert(PpuStatus::VBLANK);
+                    if self.ctrl.contains(PpuCtrl::NMI_ENABLE) {
+                        self.nmi_pending = true;
+                    }
+                }
+                -1 => {
+                    self.status.remove(PpuStatus::VBLANK | PpuStatus::SPRITE_0_HIT | PpuStatus::SPRITE_OVERFLOW);
+                }
+                _ => {}
+            }
+        }
+
+        if self.scanline < SCREEN_HEIGHT as i16 && self.rendering_enabled() {
+            self.render_background(cart);
+        }
+        if (0..SCREEN_HEIGHT as i16).contains(&self.scanline) && (1..=SCREEN_WIDTH as u16).contains(&self.dot) {
+            self.render_pixel();
+        }
+        // Sprites for the next line are picked once this line's are done with
+        if self.dot == 257 && self.scanline < SCREEN_HEIGHT as i16 - 1 {
+            if self.rendering_enabled() {
+                self.evaluate_sprites(self.scanline + 1, cart);
+            } else {
+                self.line_sprite_count = 0;
+                self.sprite_zero_in_line = false;
+            }
+        }
+
+        if self.address_a12() {
+            if self.a12_low_dots >= A12_FILTER_DOTS {
+                if let Some(cart) = cart {
+                    cart.borrow_mut().ppu_a12_rise();
+                }
+            }
+            self.a12_low_dots = 0;
+        } else {
+            self.a12_low_dots = self.a12_low_dots.saturating_add(1);
+        }
+    }
+
+    /// The level of A12 on the PPU's address bus this dot. While rendering, that follows the
+    /// background and sprite fetches: each 8-dot group reads two nametable/attribute bytes (A12
+    /// low) and then two pattern bytes from the relevant pattern table. 8x16 sprites are assumed
+    /// to come from $1000, as unused sprite slots do. Otherwise the bus holds the VRAM address.
+    fn address_a12(&self) -> bool {
+        let rendering = self.scanline < SCREEN_HEIGHT as i16
+            && self.mask.intersects(PpuMask::SHOW_BG | PpuMask::SHOW_SPRITES);
+        if !rendering {
+            return self.vram_addr & 0x1000 != 0;
+        }
+        match self.dot {
+            0 | 337.. => false,
+            dot if (dot - 1) % 8 < 4 => false,
+            257..=320 => self.ctrl.intersects(PpuCtrl::SPRITE_TABLE | PpuCtrl::SPRITE_SIZE),
+            _ => self.ctrl.contains(PpuCtrl::BG_TABLE),
+        }
+    }
+
+    /// Clears PPUCTRL and PPUMASK, as the reset button does. Everything else, nametable RAM
+    /// included, is left as is.
+    pub fn reset(&mut self) {
+        self.ctrl = PpuCtrl::empty();
+        self.mask = PpuMask::empty();
+    }
+
+    /// Switches to the frame timing of the given region.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+    }
+
+    /// Number of frames the PPU has completed.
+    pub fn frame_count(&self) -> u64 {
+        self.frame
+    }
+
+    /// The scanline being drawn: -1 for the pre-render line, 0-239 for the picture, then
+    /// post-render and vblank up to 260 (310 on PAL and Dendy).
+    pub fn scanline(&self) -> i16 {
+        self.scanline
+    }
+
+    /// The dot (PPU clock cycle) within the current scanline, 0-340.
+    pub fn dot(&self) -> u16 {
+        self.dot
+    }
+
+    /// The picture, as 256x240 NES color numbers (see `palette` for converting them to RGB).
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.framebuffer
+    }
+
+    /// Returns whether the PPU is requesting an NMI, acknowledging the request in the process.
+    pub fn take_nmi(&mut self) -> bool {
+        std::mem::take(&mut self.nmi_pending)
+    }
+
+    /// The current PPUMASK value, whose grayscale and emphasis bits affect how the picture's
+    /// colors come out (see `palette::Palette::rgb_masked`).
+    pub fn mask(&self) -> u8 {
+        self.mask.bits()
+    }
+
+    /// Object attribute memory, for inspecting sprites.
+    pub fn oam(&self) -> &[u8; OAM_SIZE] {
+        &self.oam
+    }
+
+    /// Whether a Zapper aimed at pixel (`x`, `y`) would sense light right now: the pixel is bright,
+    /// and was drawn during the last `LIGHT_SCANLINES` scanlines (the photodiode only reacts to the
+    /// beam passing by). Pixels outside the picture never are.
+    pub fn light_sensed(&self, x: u16, y: u16) -> bool {
+        if x as usize >= SCREEN_WIDTH || y as usize >= SCREEN_HEIGHT {
+            return false;
+        }
+        // Pixel x is drawn at dot x + 1
+        let lines_since = self.scanline - y as i16;
+        let recent = (0..=LIGHT_SCANLINES).contains(&lines_since) && (lines_since > 0 || self.dot > x);
+        let (r, g, b) = nes_color_to_rgb(self.framebuffer[y as usize * SCREEN_WIDTH + x as usize]);
+        recent && (r as u16 + g as u16 + b as u16) / 3 >= LIGHT_THRESHOLD
+    }
+
+    /// Whether the vblank flag in PPUSTATUS is currently set.
+    pub fn in_vblank(&self) -> bool {
+        self.status.contains(PpuStatus::VBLANK)
+    }
+
+    /// Reads a byte of nametable RAM, given a PPU address in $2000-$3EFF and the cartridge's
+    /// current mirroring.
+    pub fn read_nametable(&self, address: u16, mirroring: Mirroring) -> u8 {
+        self.nametables[mirroring.nametable_offset(address)]
+    }
+
+    /// Writes a byte of nametable RAM, given a PPU address in $2000-$3EFF and the cartridge's
+    /// current mirroring.
+    pub fn write_nametable(&mut self, address: u16, value: u8, mirroring: Mirroring) {
+        self.nametables[mirroring.nametable_offset(address)] = value;
+    }
+
+    /// Reads from the PPU's own address space ($0000-$3FFF): pattern tables come from the
+    /// cartridge, nametables from nametable RAM (mirrored however the cartridge currently has it
+    /// wired) and palettes from palette RAM. Without a cartridge, pattern tables and nametables
+    /// read as 0, as nothing enables them.
+    pub fn read_memory(&mut self, address: u16, cart: Option<&RefCell<Cart>>) -> u8 {
+        match address & 0x3FFF {
+            address @ 0x0000..=0x1FFF => cart.map_or(0, |cart| cart.borrow_mut().ppu_read(address)),
+            address @ 0x2000..=0x3EFF => cart.map_or(0, |cart| self.read_nametable(address, cart.borrow().mirroring())),
+            address => self.palette[Self::palette_index(address)],
+        }
+    }
+
+    /// Returns what `read_memory` would, without any side effects on the cartridge.
+    pub fn peek_memory(&self, address: u16, cart: Option<&RefCell<Cart>>) -> u8 {
+        match address & 0x3FFF {
+            address @ 0x0000..=0x1FFF => cart.map_or(0, |cart| cart.borrow().ppu_peek(address)),
+            address @ 0x2000..=0x3EFF => cart.map_or(0, |cart| self.read_nametable(address, cart.borrow().mirroring())),
+            address => self.palette[Self::palette_index(address)],
+        }
+    }
+
+    /// Writes to the PPU's own address space ($0000-$3FFF). See `read_memory`.
+    pub fn write_memory(&mut self, address: u16, value: u8, cart: Option<&RefCell<Cart>>) {
+        match address & 0x3FFF {
+            address @ 0x0000..=0x1FFF => {
+                if let Some(cart) = cart {
+                    cart.borrow_mut().ppu_write(address, value)
+                }
+            }
+            address @ 0x2000..=0x3EFF => {
+                if let Some(cart) = cart {
+                    let mirroring = cart.borrow().mirroring();
+                    self.write_nametable(address, value, mirroring)
+                }
+            }
+            // Palette RAM is only 6 bits wide
+            address => self.palette[Self::palette_index(address)] = value & 0x3F,
+        }
+    }
+
+    /// Draws one of the four nametables ($2000, $2400, $2800 or $2C00, by `index`) as a 256x240
+    /// picture of NES color numbers, like `framebuffer`: each tile from the background pattern
+    /// table PPUCTRL selects, colored by its attribute bits and the palettes in palette RAM. For
+    /// debugging; nothing is changed by it.
+    pub fn render_nametable(&self, index: u8, cart: Option<&RefCell<Cart>>) -> Vec<u8> {
+        let base = 0x2000 + (index as u16 & 0x03) * 0x0400;
+        let pattern_table = if self.ctrl.contains(PpuCtrl::BG_TABLE) { 0x1000 } else { 0x0000 };
+        let mut picture = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
+        for tile_y in 0..SCREEN_HEIGHT / 8 {
+            for tile_x in 0..SCREEN_WIDTH / 8 {
+                let tile = self.peek_memory(base + (tile_y * 32 + tile_x) as u16, cart);
+                // Each attribute byte covers 4x4 tiles, 2 bits for each 2x2 quarter
+                let attribute = self.peek_memory(base + 0x03C0 + (tile_y / 4 * 8 + tile_x / 4) as u16, cart);
+                let palette = attribute >> ((tile_y & 2) << 1 | tile_x & 2) & 0x03;
+                let origin = tile_y * 8 * SCREEN_WIDTH + tile_x * 8;
+                self.draw_tile(&mut picture, SCREEN_WIDTH, origin, pattern_table + tile as u16 * 16, palette, cart);
+            }
+        }
+        picture
+    }
+
+    /// Draws pattern table `table` (0 for $0000, 1 for $1000) as a 128x128 sheet of its 256 tiles,
+    /// in NES color numbers from palette `palette` (0-3 for the background palettes, 4-7 for the
+    /// sprite ones). For debugging; nothing is changed by it.
+    pub fn render_pattern_table(&self, table: u8, palette: u8, cart: Option<&RefCell<Cart>>) -> Vec<u8> {
+        let pattern_table = (table as u16 & 0x01) * 0x1000;
+        let mut picture = vec![0; 128 * 128];
+        for tile in 0..256 {
+            let origin = tile / 16 * 8 * 128 + tile % 16 * 8;
+            self.draw_tile(&mut picture, 128, origin, pattern_table + tile as u16 * 16, palette & 0x07, cart);
+        }
+        picture
+    }
+
+    /// Draws the 8x8 tile at `address` in a pattern table into `picture` (`width` pixels wide)
+    /// with its top left corner at index `origin`. Pixels of value 0 get the backdrop color, like
+    /// they do on screen.
+    fn draw_tile(&self, picture: &mut [u8], width: usize, origin: usize, address: u16, palette: u8, cart: Option<&RefCell<Cart>>) {
+        for row in 0..8 {
+            let low = self.peek_memory(address + row as u16, cart);
+            let high = self.peek_memory(address + row as u16 + 8, cart);
+            for column in 0..8 {
+                let value = (low >> (7 - column) & 1) | (high >> (7 - column) & 1) << 1;
+                let entry = if value == 0 { 0 } else { palette << 2 | value };
+                picture[origin + row * width + column] = self.palette[Self::palette_index(0x3F00 + entry as u16)];
+            }
+        }
+    }
+
+    /// Maps a palette address ($3F00-$3FFF) to an index into palette RAM. The whole range mirrors
+    /// the 32 bytes, and the sprite palettes' first entries ($3F10/$3F14/$3F18/$3F1C) are the
+    /// background palettes' ones.
+    fn palette_index(address: u16) -> usize {
+        let index = address as usize % PALETTE_RAM_SIZE;
+        if index & 0x13 == 0x10 { index & 0x0F } else { index }
+    }
+
+    /// Returns what reading a PPU register would, without the side effects (clearing vblank,
+    /// etc.). The address must already be mirrored down to $2000-$2007.
+    pub fn peek(&self, address: u16) -> u8 {
+        match address {
+            0x2002 => self.status.bits() | (self.io_latch & 0x1F),
+            0x2004 => self.oam[self.oam_addr as usize],
+            0x2007 if self.vram_addr & 0x3FFF >= 0x3F00 => {
+                self.io_latch & 0xC0 | self.palette[Self::palette_index(self.vram_addr)]
+            }
+            0x2007 => self.read_buffer,
+            _ => self.io_latch,
+        }
+    }
+
+    /// Reads from a PPU register. The address must already be mirrored down to $2000-$2007.
+    /// PPUDATA reads reach the cartridge through `cart`.
+    ///
+    /// Write-only registers, and the unused low bits of PPUSTATUS, return whatever was last on the
+    /// PPU's data bus.
+    pub fn read(&mut self, address: u16, cart: Option<&RefCell<Cart>>) -> u8 {
+        match address {
+            0x2002 => {
+                self.io_latch = self.status.bits() | (self.io_latch & 0x1F);
+                self.status.remove(PpuStatus::VBLANK);
+                self.write_toggle = false;
+            }
+            0x2004 => self.io_latch = self.oam[self.oam_addr as usize],
+            0x2007 => {
+                // Reads below the palettes are delayed by one: they return what the previous read
+                // fetched into the buffer. Palette reads return right away, but still refill the
+                // buffer, with the nametable byte "underneath" the palette.
+                let address = self.vram_addr & 0x3FFF;
+                if address >= 0x3F00 {
+                    self.io_latch = self.io_latch & 0xC0 | self.read_memory(address, cart);
+                    self.read_buffer = self.read_memory(address - 0x1000, cart);
+                } else {
+                    self.io_latch = self.read_buffer;
+                    self.read_buffer = self.read_memory(address, cart);
+                }
+                self.increment_vram_addr();
+            }
+            // Write-only registers leave the data bus as it was
+            _ => {}
+        }
+        self.io_latch
+    }
+
+    /// Advances the VRAM address after a PPUDATA access, by 1 or 32 depending on PPUCTRL.
+    fn increment_vram_addr(&mut self) {
+        let step = if self.ctrl.contains(PpuCtrl::VRAM_INCREMENT) { 32 } else { 1 };
+        self.vram_addr = self.vram_addr.wrapping_add(step) & 0x7FFF;
+    }
+
+    /// Writes to a PPU register. The address must already be mirrored down to $2000-$2007.
+    /// PPUDATA writes reach the cartridge through `cart`. Writes to PPUSTATUS are ignored, apart
+    /// from the value landing on the data bus.
+    pub fn write(&mut self, address: u16, value: u8, cart: Option<&RefCell<Cart>>) {
+        self.io_latch = value;
+        match address {
+            0x2000 => {
+                let nmi_was_enabled = self.ctrl.contains(PpuCtrl::NMI_ENABLE);
+                self.ctrl = PpuCtrl::from_bits_retain(value);
+                // The nametable bits double as the top bits of the scroll position
+                self.temp_addr = self.temp_addr & !0x0C00 | ((value & 0x03) as u16) << 10;
+                // Enabling NMI during vblank immediately raises an NMI
+                if !nmi_was_enabled && self.ctrl.contains(PpuCtrl::NMI_ENABLE) && self.in_vblank() {
+                    self.nmi_pending = true;
+                }
+            }
+            0x2001 => self.mask = PpuMask::from_bits_retain(value),
+            0x2003 => self.oam_addr = value,
+            0x2004 => {
+                self.oam[self.oam_addr as usize] = value;
+                self.oam_addr = self.oam_addr.wrapping_add(1);
+            }
+            0x2005 => {
+                // X scroll first (coarse and fine), then Y scroll (coarse and fine)
+                if self.write_toggle {
+                    self.temp_addr = self.temp_addr & !0x73E0 | ((value & 0x07) as u16) << 12 | ((value >> 3) as u16) << 5;
+                } else {
+                    self.temp_addr = self.temp_addr & !0x001F | (value >> 3) as u16;
+                    self.fine_x = value & 0x07;
+                }
+                self.write_toggle = !self.write_toggle;
+            }
+            0x2006 => {
+                // High byte first (only 6 bits of it), then the low byte, which takes effect
+                if self.write_toggle {
+                    self.temp_addr = self.temp_addr & 0xFF00 | value as u16;
+                    self.vram_addr = self.temp_addr;
+                } else {
+                    self.temp_addr = self.temp_addr & 0x00FF | ((value & 0x3F) as u16) << 8;
+                }
+                self.write_toggle = !self.write_toggle;
+            }
+            0x2007 => {
+                self.write_memory(self.vram_addr, value, cart);
+                self.increment_vram_addr();
+            }
+            // PPUSTATUS is read-only
+            _ => {}
+        }
+    }
+}
+
+impl Stateful for PPU {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.ctrl.bits());
+        state.u8(self.mask.bits());
+        state.u8(self.status.bits());
+        state.u16(self.scanline as u16);
+        state.u16(self.dot);
+        state.u8(self.io_latch);
+        state.bool(self.nmi_pending);
+        state.u64(self.frame);
+        state.bytes(&self.framebuffer);
+        state.bytes(&self.nametables);
+        state.bytes(&self.palette);
+        state.u16(self.vram_addr);
+        state.u16(self.temp_addr);
+        state.u8(self.fine_x);
+        state.bool(self.write_toggle);
+        state.u8(self.read_buffer);
+        state.bytes(&self.oam);
+        state.u8(self.oam_addr);
+        self.save_render_state(state);
+        state.u16(self.a12_low_dots);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        self.ctrl = PpuCtrl::from_bits_retain(state.u8()?);
+        self.mask = PpuMask::from_bits_retain(state.u8()?);
+        self.status = PpuStatus::from_bits_retain(state.u8()?);
+        self.scanline = state.u16()? as i16;
+        self.dot = state.u16()?;
+        self.io_latch = state.u8()?;
+        self.nmi_pending = state.bool()?;
+        self.frame = state.u64()?;
+        state.bytes(&mut self.framebuffer)?;
+        state.bytes(&mut self.nametables)?;
+        state.bytes(&mut self.palette)?;
+        self.vram_addr = state.u16()?;
+        self.temp_addr = state.u16()?;
+        self.fine_x = state.u8()? & 0x07;
+        self.write_toggle = state.bool()?;
+        self.read_buffer = state.u8()?;
+        state.bytes(&mut self.oam)?;
+        self.oam_addr = state.u8()?;
+        self.load_render_state(state)?;
+        self.a12_low_dots = state.u16()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cart with 16KB of PRG-ROM and 8KB of CHR-ROM whose bytes are the low byte of their
+    /// address, with the given flags 6 (mirroring, four-screen, low nibble of the mapper number)
+    fn cart(flags6: u8) -> RefCell<Cart> {
+        let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 1, flags6, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        rom.resize(16 + 0x4000, 0);
+        rom.extend((0..0x2000).map(|address| address as u8));
+        RefCell::new(Cart::from_rom(&rom).unwrap())
+    }
+
+    fn set_address(ppu: &mut PPU, address: u16, cart: Option<&RefCell<Cart>>) {
+        ppu.write(0x2006, (address >> 8) as u8, cart);
+        ppu.write(0x2006, address as u8, cart);
+    }
+
+    /// Ticks until the PPU is at the given scanline and dot
+    fn run_to(ppu: &mut PPU, scanline: i16, dot: u16, cart: Option<&RefCell<Cart>>) {
+        while (ppu.scanline(), ppu.dot()) != (scanline, dot) {
+            ppu.tick(cart);
+        }
+    }
+
+    #[test]
+    fn four_screen_nametables_are_distinct() {
+        let cart = cart(0x08);
+        let cart = Some(&cart);
+        let mut ppu = PPU::new();
+        let nametables = [0x2000, 0x2400, 0x2800, 0x2C00];
+        for (i, &address) in nametables.iter().enumerate() {
+            set_address(&mut ppu, address, cart);
+            ppu.write(0x2007, 0x10 + i as u8, cart);
+        }
+        for (i, &address) in nametables.iter().enumerate() {
+            set_address(&mut ppu, address, cart);
+            ppu.read(0x2007, cart);
+            assert_eq!(ppu.read(0x2007, cart), 0x10 + i as u8);
+        }
+    }
+
+    #[test]
+    fn ppudata_reads_chr_rom() {
+        let cart = cart(0x00);
+        let cart = Some(&cart);
+        let mut ppu = PPU::new();
+        set_address(&mut ppu, 0x0010, cart);
+        ppu.read(0x2007, cart);
+        assert_eq!(ppu.read(0x2007, cart), 0x10);
+        assert_eq!(ppu.read(0x2007, cart), 0x11);
+    }
+
+    #[test]
+    fn vblank_starts_at_scanline_241_dot_1() {
+        let mut ppu = PPU::new();
+        ppu.write(0x2000, PpuCtrl::NMI_ENABLE.bits(), None);
+        while (ppu.scanline(), ppu.dot()) != (241, 0) {
+            ppu.tick(None);
+        }
+        assert!(!ppu.in_vblank());
+        assert!(!ppu.take_nmi());
+        ppu.tick(None);
+        assert_eq!((ppu.scanline(), ppu.dot()), (241, 1));
+        assert!(ppu.in_vblank());
+        assert!(ppu.take_nmi());
+        // Acknowledged
+        assert!(!ppu.take_nmi());
+    }
+
+    #[test]
+    fn ppudata_reads_are_buffered_below_the_palettes() {
+        let cart = cart(0x00);
+        let cart = Some(&cart);
+        let mut ppu = PPU::new();
+        set_address(&mut ppu, 0x2400, cart);
+        ppu.write(0x2007, 0x11, cart);
+        ppu.write(0x2007, 0x22, cart);
+        set_address(&mut ppu, 0x2F05, cart);
+        ppu.write(0x2007, 0x77, cart);
+        set_address(&mut ppu, 0x3F05, cart);
+        ppu.write(0x2007, 0x2A, cart);
+
+        // Each read returns what the previous one fetched
+        set_address(&mut ppu, 0x2400, cart);
+        assert_eq!(ppu.read(0x2007, cart), 0x00);
+        assert_eq!(ppu.read(0x2007, cart), 0x11);
+        assert_eq!(ppu.read(0x2007, cart), 0x22);
+
+        // Palette reads come back right away, and fill the buffer from the nametable underneath
+        set_address(&mut ppu, 0x3F05, cart);
+        assert_eq!(ppu.read(0x2007, cart), 0x2A);
+        set_address(&mut ppu, 0x2000, cart);
+        assert_eq!(ppu.read(0x2007, cart), 0x77);
+    }
+
+    #[test]
+    fn scroll_and_address_writes_fill_the_internal_registers() {
+        // The example sequence from the nesdev wiki's "PPU scrolling" page
+        let mut ppu = PPU::new();
+        ppu.write(0x2000, 0x00, None);
+        ppu.read(0x2002, None);
+        assert!(!ppu.write_toggle);
+        ppu.write(0x2005, 0x7D, None);
+        assert_eq!((ppu.temp_addr, ppu.fine_x, ppu.write_toggle), (0x000F, 5, true));
+        ppu.write(0x2005, 0x5E, None);
+        assert_eq!((ppu.temp_addr, ppu.fine_x, ppu.write_toggle), (0x616F, 5, false));
+        ppu.write(0x2006, 0x3D, None);
+        assert_eq!((ppu.temp_addr, ppu.write_toggle), (0x3D6F, true));
+        assert_eq!(ppu.vram_addr, 0x0000);
+        ppu.write(0x2006, 0xF0, None);
+        assert_eq!((ppu.temp_addr, ppu.vram_addr, ppu.write_toggle), (0x3DF0, 0x3DF0, false));
+    }
+
+    #[test]
+    fn rendering_disabled_shows_the_backdrop() {
+        let cart = cart(0x00);
+        let cart = Some(&cart);
+        let mut ppu = PPU::new();
+        set_address(&mut ppu, 0x3F00, cart);
+        ppu.write(0x2007, 0x21, cart);
+        // Out of the palettes, or that entry would show instead
+        set_address(&mut ppu, 0x2000, cart);
+        run_to(&mut ppu, 241, 1, cart);
+        assert!(ppu.framebuffer().iter().all(|&color| color == 0x21));
+    }
+
+    #[test]
+    fn sprite_zero_hit_is_set_at_the_first_overlapping_pixel() {
+        let cart = cart(0x00);
+        let cart = Some(&cart);
+        let mut ppu = PPU::new();
+        // Sprite 0 at (100, 29), drawn from line 30, using tile $FF: its top row is opaque in
+        // columns 0-3. On line 30, the background (all tile 0) is opaque in columns 4-6 of each
+        // tile, so the first overlap is at x = 100.
+        ppu.write(0x2003, 0, cart);
+        for byte in [29, 0xFF, 0x00, 100] {
+            ppu.write(0x2004, byte, cart);
+        }
+        ppu.write(0x2001, (PpuMask::SHOW_BG | PpuMask::SHOW_SPRITES | PpuMask::SHOW_BG_LEFT | PpuMask::SHOW_SPRITES_LEFT).bits(), cart);
+
+        let sprite_zero_hit = |ppu: &mut PPU| ppu.read(0x2002, cart) & PpuStatus::SPRITE_0_HIT.bits() != 0;
+        run_to(&mut ppu, 30, 100, cart);
+        assert!(!sprite_zero_hit(&mut ppu));
+        // Pixel x is drawn at dot x + 1
+        ppu.tick(cart);
+        assert!(sprite_zero_hit(&mut ppu));
+        // Until the pre-render line
+        run_to(&mut ppu, -1, 1, cart);
+        assert!(!sprite_zero_hit(&mut ppu));
+    }
+
+    #[test]
+    fn nametables_follow_mirroring_changes() {
+        // MMC3, whose $A000 register selects vertical (0) or horizontal (1) mirroring
+        let mmc3 = cart(0x40);
+        let cart = Some(&mmc3);
+        let mut ppu = PPU::new();
+        mmc3.borrow_mut().write(0xA000, 0);
+        set_address(&mut ppu, 0x2000, cart);
+        ppu.write(0x2007, 0x55, cart);
+        assert_eq!((ppu.read_memory(0x2400, cart), ppu.read_memory(0x2800, cart)), (0x00, 0x55));
+
+        mmc3.borrow_mut().write(0xA000, 1);
+        assert_eq!((ppu.read_memory(0x2400, cart), ppu.read_memory(0x2800, cart)), (0x55, 0x00));
+    }
+
+    #[test]
+    fn debug_views_draw_known_tiles() {
+        // Tile 1's top row is $10 (low plane) and $18 (high plane): value 3 in column 3, 2 in
+        // column 4 and 0 elsewhere
+        let cart = cart(0x00);
+        let cart = Some(&cart);
+        let mut ppu = PPU::new();
+        set_address(&mut ppu, 0x3F00, cart);
+        for color in [0x0F, 0, 0, 0, 0, 0x11, 0x12, 0x13, 0, 0x21, 0x22, 0x23] {
+            ppu.write(0x2007, color, cart);
+        }
+
+        let sheet = ppu.render_pattern_table(0, 1, cart);
+        assert_eq!(sheet.len(), 128 * 128);
+        assert_eq!(sheet[8..16], [0x0F, 0x0F, 0x0F, 0x13, 0x12, 0x0F, 0x0F, 0x0F]);
+
+        // Tile 1 at column 2, row 1 of the first nametable, with attribute palette 2
+        set_address(&mut ppu, 0x2022, cart);
+        ppu.write(0x2007, 0x01, cart);
+        set_address(&mut ppu, 0x23C0, cart);
+        ppu.write(0x2007, 0b0000_1000, cart);
+        let picture = ppu.render_nametable(0, cart);
+        let row = 8 * SCREEN_WIDTH;
+        assert_eq!(picture[row + 16..row + 24], [0x0F, 0x0F, 0x0F, 0x23, 0x22, 0x0F, 0x0F, 0x0F]);
+    }
+}
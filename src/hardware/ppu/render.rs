@@ -0,0 +1,288 @@
+use std::cell::RefCell;
+use crate::RustNesError;
+use crate::hardware::Cart;
+use crate::save_state::{StateReader, StateWriter};
+use super::{PpuCtrl, PpuMask, PpuStatus, PPU, SCREEN_WIDTH};
+
+/// Most sprites the PPU can show on one scanline.
+pub(super) const SPRITES_PER_LINE: usize = 8;
+
+/// A sprite picked for a scanline, with its row of pattern data already fetched (and flipped, if
+/// the sprite is flipped horizontally).
+#[derive(Clone, Copy, Default)]
+pub(super) struct LineSprite {
+    x: u8,
+    low: u8,
+    high: u8,
+    attributes: u8,
+}
+
+impl LineSprite {
+    /// The sprite's pixel value (0-3, 0 being transparent) at column `x` of the picture.
+    fn pixel(&self, x: usize) -> u8 {
+        match x.checked_sub(self.x as usize) {
+            Some(column) if column < 8 => {
+                let shift = 7 - column;
+                (self.low >> shift & 1) | (self.high >> shift & 1) << 1
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// A background tile's nametable, attribute and pattern data, fetched for the shifters to load.
+#[derive(Clone, Copy, Default)]
+pub(super) struct BackgroundTile {
+    low: u8,
+    high: u8,
+    palette: u8,
+}
+
+/// The background shift registers: two tiles' worth of pattern and palette bits, the tile being
+/// drawn in the high byte and the next one in the low byte. They shift left once a pixel, and
+/// fine X picks which bit is drawn.
+#[derive(Clone, Copy, Default)]
+pub(super) struct BackgroundShifters {
+    pattern_low: u16,
+    pattern_high: u16,
+    palette_low: u16,
+    palette_high: u16,
+}
+
+impl BackgroundShifters {
+    fn shift(&mut self) {
+        self.pattern_low <<= 1;
+        self.pattern_high <<= 1;
+        self.palette_low <<= 1;
+        self.palette_high <<= 1;
+    }
+
+    /// Puts `tile` in the low byte, behind the one being drawn.
+    fn load(&mut self, tile: BackgroundTile) {
+        // Palette bits are the same across the tile
+        let spread = |bit: u8| if tile.palette & bit != 0 { 0x00FF } else { 0x0000 };
+        self.pattern_low = self.pattern_low & 0xFF00 | tile.low as u16;
+        self.pattern_high = self.pattern_high & 0xFF00 | tile.high as u16;
+        self.palette_low = self.palette_low & 0xFF00 | spread(0x01);
+        self.palette_high = self.palette_high & 0xFF00 | spread(0x02);
+    }
+}
+
+impl PPU {
+    /// Whether PPUMASK enables drawing the background or sprites. With both off, the PPU stops
+    /// fetching and shows a flat color.
+    pub(super) fn rendering_enabled(&self) -> bool {
+        self.mask.intersects(PpuMask::SHOW_BG | PpuMask::SHOW_SPRITES)
+    }
+
+    /// Outputs the pixel for the current dot, on a visible scanline.
+    pub(super) fn render_pixel(&mut self) {
+        let x = self.dot as usize - 1;
+        let y = self.scanline as usize;
+        let address = if !self.rendering_enabled() {
+            // The backdrop color, unless the VRAM address points into the palettes, in which case
+            // that entry shows instead
+            if self.vram_addr & 0x3F00 == 0x3F00 { self.vram_addr } else { 0x3F00 }
+        } else {
+            let background = self.background_pixel(x);
+            if background & 0x03 != 0 && self.sprite_zero_hits(x) {
+                self.status.insert(PpuStatus::SPRITE_0_HIT);
+            }
+            let entry = match self.sprite_pixel(x) {
+                Some((sprite, behind)) if !behind || background & 0x03 == 0 => sprite,
+                _ => background,
+            };
+            0x3F00 | entry as u16
+        };
+        self.framebuffer[y * SCREEN_WIDTH + x] = self.palette[Self::palette_index(address)];
+    }
+
+    /// Does the background's work for the current dot, on the pre-render line or a visible one
+    /// with rendering enabled: shifting and reloading the shifters, fetching tiles from where `v`
+    /// points and moving `v` along.
+    ///
+    /// Tiles are fetched at the end of each 8-dot group (in one go, rather than over its 8 dots),
+    /// and coarse X is incremented after each, for the 32 tiles of the line (dots 8-256) and the
+    /// first two of the next (328 and 336). Fine Y is incremented at dot 256, the horizontal bits
+    /// are copied from `t` at dot 257, and on the pre-render line the vertical bits are copied
+    /// during dots 280-304.
+    pub(super) fn render_background(&mut self, cart: Option<&RefCell<Cart>>) {
+        if matches!(self.dot, 2..=257 | 322..=337) {
+            self.background_shifters.shift();
+            if self.dot % 8 == 1 {
+                self.background_shifters.load(self.background_tile);
+            }
+        }
+        match self.dot {
+            dot @ (8..=256 | 328 | 336) if dot.is_multiple_of(8) => {
+                self.fetch_background_tile(cart);
+                self.increment_coarse_x();
+                if dot == 256 {
+                    self.increment_fine_y();
+                }
+            }
+            257 => self.vram_addr = self.vram_addr & !0x041F | self.temp_addr & 0x041F,
+            280..=304 if self.scanline == -1 => {
+                self.vram_addr = self.vram_addr & !0x7BE0 | self.temp_addr & 0x7BE0;
+            }
+            _ => {}
+        }
+    }
+
+    /// Reads the nametable, attribute and pattern bytes for the background tile `v` points at.
+    fn fetch_background_tile(&mut self, cart: Option<&RefCell<Cart>>) {
+        let v = self.vram_addr;
+        let tile = self.read_memory(0x2000 | v & 0x0FFF, cart);
+        // Each attribute byte covers 4x4 tiles, 2 bits for each 2x2 quarter
+        let attribute = self.read_memory(0x23C0 | v & 0x0C00 | (v >> 4) & 0x38 | (v >> 2) & 0x07, cart);
+        let table = if self.ctrl.contains(PpuCtrl::BG_TABLE) { 0x1000 } else { 0x0000 };
+        let address = table + tile as u16 * 16 + (v >> 12);
+        self.background_tile = BackgroundTile {
+            low: self.read_memory(address, cart),
+            high: self.read_memory(address + 8, cart),
+            palette: attribute >> ((v >> 4) & 0x04 | v & 0x02) & 0x03,
+        };
+    }
+
+    /// Moves `v` to the next tile across, into the next nametable horizontally past the 32nd.
+    fn increment_coarse_x(&mut self) {
+        if self.vram_addr & 0x001F == 31 {
+            self.vram_addr = (self.vram_addr & !0x001F) ^ 0x0400;
+        } else {
+            self.vram_addr += 1;
+        }
+    }
+
+    /// Moves `v` down a pixel, into the next tile row after the 8th pixel, and into the next
+    /// nametable vertically past row 29. Rows 30 and 31 (the attribute tables) wrap to row 0 of
+    /// the same nametable instead.
+    fn increment_fine_y(&mut self) {
+        if self.vram_addr & 0x7000 != 0x7000 {
+            self.vram_addr += 0x1000;
+            return;
+        }
+        self.vram_addr &= !0x7000;
+        let coarse_y = match (self.vram_addr & 0x03E0) >> 5 {
+            29 => {
+                self.vram_addr ^= 0x0800;
+                0
+            }
+            31 => 0,
+            coarse_y => coarse_y + 1,
+        };
+        self.vram_addr = self.vram_addr & !0x03E0 | coarse_y << 5;
+    }
+
+    /// The palette entry (0-15) of the background at column `x`, where 0-3 within each palette is
+    /// transparent. Hidden backgrounds are transparent throughout.
+    fn background_pixel(&self, x: usize) -> u8 {
+        if !self.mask.contains(PpuMask::SHOW_BG) || (x < 8 && !self.mask.contains(PpuMask::SHOW_BG_LEFT)) {
+            return 0;
+        }
+        let shifters = self.background_shifters;
+        let shift = 15 - self.fine_x;
+        let value = (shifters.pattern_low >> shift & 1) | (shifters.pattern_high >> shift & 1) << 1;
+        let palette = (shifters.palette_low >> shift & 1) | (shifters.palette_high >> shift & 1) << 1;
+        if value == 0 { 0 } else { (palette << 2 | value) as u8 }
+    }
+
+    /// The palette entry (16-31) of the frontmost opaque sprite at column `x`, and whether it's
+    /// behind the background, if there is one.
+    fn sprite_pixel(&self, x: usize) -> Option<(u8, bool)> {
+        if !self.mask.contains(PpuMask::SHOW_SPRITES) || (x < 8 && !self.mask.contains(PpuMask::SHOW_SPRITES_LEFT)) {
+            return None;
+        }
+        self.line_sprites[..self.line_sprite_count].iter().find_map(|sprite| {
+            let value = sprite.pixel(x);
+            (value != 0).then_some((0x10 | (sprite.attributes & 0x03) << 2 | value, sprite.attributes & 0x20 != 0))
+        })
+    }
+
+    /// Whether sprite 0 has an opaque pixel at column `x`, visible enough to register a hit with
+    /// an opaque background pixel there. Priority doesn't matter, but both the background and
+    /// sprites have to be shown there, and the rightmost column never hits.
+    fn sprite_zero_hits(&self, x: usize) -> bool {
+        let left_clipped = !self.mask.contains(PpuMask::SHOW_BG_LEFT | PpuMask::SHOW_SPRITES_LEFT);
+        self.sprite_zero_in_line
+            && x != SCREEN_WIDTH - 1
+            && !(x < 8 && left_clipped)
+            && self.mask.contains(PpuMask::SHOW_BG | PpuMask::SHOW_SPRITES)
+            && self.line_sprites[0].pixel(x) != 0
+    }
+
+    /// Picks the sprites that cover `line` out of OAM (the first 8, in OAM order) and fetches
+    /// their pattern data. Sprites are drawn one line below their Y coordinate. More than 8
+    /// sprites on a line sets the sprite overflow flag.
+    pub(super) fn evaluate_sprites(&mut self, line: i16, cart: Option<&RefCell<Cart>>) {
+        let height = if self.ctrl.contains(PpuCtrl::SPRITE_SIZE) { 16 } else { 8 };
+        self.line_sprite_count = 0;
+        self.sprite_zero_in_line = false;
+        for sprite in 0..64 {
+            let [y, tile, attributes, x] = [0, 1, 2, 3].map(|byte| self.oam[sprite * 4 + byte]);
+            let row = line - 1 - y as i16;
+            if !(0..height).contains(&row) {
+                continue;
+            }
+            if self.line_sprite_count == SPRITES_PER_LINE {
+                self.status.insert(PpuStatus::SPRITE_OVERFLOW);
+                break;
+            }
+            let row = if attributes & 0x80 != 0 { height - 1 - row } else { row } as u16;
+            let address = if height == 16 {
+                // 8x16 sprites pick their pattern table with bit 0 of the tile number
+                (tile as u16 & 0x01) << 12 | (tile as u16 & 0xFE) << 4 | (row & 0x08) << 1 | row & 0x07
+            } else {
+                let table = if self.ctrl.contains(PpuCtrl::SPRITE_TABLE) { 0x1000 } else { 0x0000 };
+                table | (tile as u16) << 4 | row
+            };
+            let (mut low, mut high) = (self.read_memory(address, cart), self.read_memory(address + 8, cart));
+            if attributes & 0x40 != 0 {
+                (low, high) = (low.reverse_bits(), high.reverse_bits());
+            }
+            self.line_sprites[self.line_sprite_count] = LineSprite { x, low, high, attributes };
+            self.line_sprite_count += 1;
+            // Being first in OAM, sprite 0 always lands in the first slot
+            self.sprite_zero_in_line |= sprite == 0;
+        }
+    }
+
+    /// Saves what the renderer is partway through: the fetched background tile, the shifters and
+    /// the sprites picked for the line.
+    pub(super) fn save_render_state(&self, state: &mut StateWriter) {
+        let tile = self.background_tile;
+        state.bytes(&[tile.low, tile.high, tile.palette]);
+        let shifters = self.background_shifters;
+        for shifter in [shifters.pattern_low, shifters.pattern_high, shifters.palette_low, shifters.palette_high] {
+            state.u16(shifter);
+        }
+        state.u8(self.line_sprite_count as u8);
+        state.bool(self.sprite_zero_in_line);
+        for sprite in &self.line_sprites {
+            state.bytes(&[sprite.x, sprite.low, sprite.high, sprite.attributes]);
+        }
+    }
+
+    /// Restores what `save_render_state` saved.
+    pub(super) fn load_render_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        let mut tile = [0; 3];
+        state.bytes(&mut tile)?;
+        self.background_tile = BackgroundTile { low: tile[0], high: tile[1], palette: tile[2] };
+        self.background_shifters = BackgroundShifters {
+            pattern_low: state.u16()?,
+            pattern_high: state.u16()?,
+            palette_low: state.u16()?,
+            palette_high: state.u16()?,
+        };
+        self.line_sprite_count = state.u8()? as usize;
+        if self.line_sprite_count > SPRITES_PER_LINE {
+            return Err(RustNesError::InvalidSaveState);
+        }
+        self.sprite_zero_in_line = state.bool()?;
+        for sprite in &mut self.line_sprites {
+            let mut bytes = [0; 4];
+            state.bytes(&mut bytes)?;
+            *sprite = LineSprite { x: bytes[0], low: bytes[1], high: bytes[2], attributes: bytes[3] };
+        }
+        Ok(())
+    }
+}
@@ -1,21 +1,130 @@
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter, Stateful};
 
+/// Size of the console's internal RAM.
+pub const RAM_SIZE: usize = 0x0800;
+
+/// What RAM holds at power-on. Real RAM comes up indeterminate, often in patterns that depend on
+/// the console, so which one suits depends on what's being reproduced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RamInit {
+    /// Every byte $00
+    #[default]
+    Zeroed,
+    /// Every byte $FF
+    Ones,
+    /// Every byte the given value
+    Pattern(u8),
+    /// Pseudo-random bytes from the given seed (see `WorkMemory::new_randomized`)
+    Seeded(u64),
+}
+
+/// The console's 2KB of internal RAM. It's mapped to $0000-$07FF and mirrored three more times up
+/// to $1FFF, so only the low 11 bits of an address matter.
 pub struct WorkMemory {
-    memory: [u8; 0x2000],
+    memory: [u8; RAM_SIZE],
+}
+
+impl Default for WorkMemory {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl WorkMemory {
     pub fn new() -> Self {
         Self {
-            memory: [0; 0x2000],
+            memory: [0; RAM_SIZE],
         }
     }
 
+    /// Creates RAM filled the given way.
+    pub fn with_fill(init: RamInit) -> Self {
+        match init {
+            RamInit::Zeroed => Self::new(),
+            RamInit::Ones => Self { memory: [0xFF; RAM_SIZE] },
+            RamInit::Pattern(value) => Self { memory: [value; RAM_SIZE] },
+            RamInit::Seeded(seed) => Self::new_randomized(seed),
+        }
+    }
+
+    /// Fills RAM with pseudo-random bytes generated from `seed`, standing in for the indeterminate
+    /// contents real RAM powers on with. The same seed always gives the same contents.
+    pub fn new_randomized(seed: u64) -> Self {
+        let mut memory = [0; RAM_SIZE];
+        let mut state = seed;
+        for chunk in memory.chunks_exact_mut(8) {
+            chunk.copy_from_slice(&splitmix64(&mut state).to_le_bytes());
+        }
+        Self { memory }
+    }
+
+    /// Reads a byte. Addresses past the first 2KB wrap around, as the mirrors do.
     pub fn read(&self, addr: u16) -> u8 {
-        self.memory[addr as usize]
+        self.memory[addr as usize % RAM_SIZE]
     }
 
+    /// Writes a byte. Addresses past the first 2KB wrap around, as the mirrors do.
     pub fn write(&mut self, addr: u16, value: u8) {
-        self.memory[addr as usize] = value;
+        self.memory[addr as usize % RAM_SIZE] = value;
+    }
+
+    /// Copies out `len` bytes starting at `start`, wrapping around like `read`.
+    pub fn dump_range(&self, start: u16, len: usize) -> Vec<u8> {
+        (0..len).map(|offset| self.memory[(start as usize + offset) % RAM_SIZE]).collect()
     }
 }
 
+impl Stateful for WorkMemory {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.memory);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        state.bytes(&mut self.memory)
+    }
+}
+
+/// Advances a SplitMix64 generator and returns its next output. Good enough to scramble RAM, and
+/// every seed (0 included) works.
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_show_up_in_every_mirror() {
+        let mut ram = WorkMemory::new();
+        ram.write(0x0000, 0x42);
+        for address in [0x0000, 0x0800, 0x1000, 0x1800] {
+            assert_eq!(ram.read(address), 0x42);
+        }
+        ram.write(0x1FFF, 0x24);
+        assert_eq!(ram.read(0x07FF), 0x24);
+    }
+
+    #[test]
+    fn randomized_contents_depend_only_on_the_seed() {
+        let contents = |seed| WorkMemory::new_randomized(seed).dump_range(0, RAM_SIZE);
+        assert_eq!(contents(1), contents(1));
+        assert_ne!(contents(1), contents(2));
+        // Seed 0 isn't degenerate either
+        assert!(contents(0).iter().any(|&byte| byte != 0));
+    }
+
+    #[test]
+    fn fill_modes() {
+        let contents = |init| WorkMemory::with_fill(init).dump_range(0, RAM_SIZE);
+        assert_eq!(contents(RamInit::Zeroed), [0x00; RAM_SIZE]);
+        assert_eq!(contents(RamInit::Ones), [0xFF; RAM_SIZE]);
+        assert_eq!(contents(RamInit::Pattern(0x5A)), [0x5A; RAM_SIZE]);
+        assert_eq!(contents(RamInit::Seeded(7)), WorkMemory::new_randomized(7).dump_range(0, RAM_SIZE));
+    }
+}
@@ -0,0 +1,94 @@
+use crate::hardware::ppu::DOTS_PER_SCANLINE;
+use crate::header::TimingMode;
+
+/// The TV system a console was built for. Besides the picture format, this decides how the master
+/// clock is divided between the CPU and PPU, how many scanlines make up a frame, and the rate of
+/// the APU's frame sequencer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Region {
+    /// NTSC (North America, Japan): 262 scanlines, 3 dots per CPU cycle
+    #[default]
+    Ntsc,
+    /// PAL (Europe, Australia): 312 scanlines, 3.2 dots per CPU cycle
+    Pal,
+    /// Dendy (PAL famiclones): 312 scanlines like PAL, but 3 dots per CPU cycle and a later vblank
+    Dendy,
+}
+
+impl Region {
+    /// Master clock frequency, in Hz.
+    pub fn master_clock(self) -> f64 {
+        match self {
+            Region::Ntsc => 21_477_272.0,
+            Region::Pal | Region::Dendy => 26_601_712.0,
+        }
+    }
+
+    /// Master clock cycles per CPU cycle.
+    pub fn cpu_divider(self) -> u32 {
+        match self {
+            Region::Ntsc => 12,
+            Region::Pal => 16,
+            Region::Dendy => 15,
+        }
+    }
+
+    /// Master clock cycles per PPU dot.
+    pub fn ppu_divider(self) -> u32 {
+        match self {
+            Region::Ntsc => 4,
+            Region::Pal | Region::Dendy => 5,
+        }
+    }
+
+    /// CPU clock frequency, in Hz.
+    pub fn cpu_clock_speed(self) -> f64 {
+        self.master_clock() / self.cpu_divider() as f64
+    }
+
+    /// PPU dots per frame: 262 scanlines of 341 dots on NTSC, 312 on PAL and Dendy.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_nes::hardware::Region;
+    ///
+    /// assert_eq!(Region::Ntsc.dots_per_frame(), 89342);
+    /// assert_eq!(Region::Pal.dots_per_frame(), 106392);
+    /// ```
+    pub fn dots_per_frame(self) -> u32 {
+        DOTS_PER_SCANLINE as u32 * (self.last_scanline() + 2) as u32
+    }
+
+    /// Frames per second: about 60.1 on NTSC, 50.0 on PAL and Dendy.
+    pub fn frame_rate(self) -> f64 {
+        self.master_clock() / (self.ppu_divider() * self.dots_per_frame()) as f64
+    }
+
+    /// Scanline on which the vblank flag gets set.
+    pub fn vblank_scanline(self) -> i16 {
+        match self {
+            Region::Ntsc | Region::Pal => 241,
+            Region::Dendy => 291,
+        }
+    }
+
+    /// The last scanline of a frame. The following one is the pre-render line (-1).
+    pub fn last_scanline(self) -> i16 {
+        match self {
+            Region::Ntsc => 260,
+            Region::Pal | Region::Dendy => 310,
+        }
+    }
+}
+
+impl From<&TimingMode> for Region {
+    /// Multi-region games run as NTSC, the most common console.
+    fn from(timing_mode: &TimingMode) -> Self {
+        match timing_mode {
+            TimingMode::NTSC | TimingMode::Multi => Region::Ntsc,
+            TimingMode::PAL => Region::Pal,
+            TimingMode::Dendy => Region::Dendy,
+        }
+    }
+}
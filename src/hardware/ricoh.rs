@@ -1,15 +1,166 @@
-use std::sync::{Arc, Mutex};
-use crate::MOS6502;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+use crate::hardware::*;
 
+/// How many CPU cycles to run between checks against the wall clock, when pacing to real time.
+const PACING_INTERVAL: u64 = 1024;
+
+/// Cycles OAM DMA halts the CPU for: one while the CPU stops, then a read and a write for each
+/// of the 256 bytes. Starting on an odd cycle costs one more, to line the reads up with even
+/// cycles.
+const OAM_DMA_CYCLES: u16 = 513;
+
+/// Ricoh 2A03: the NES's CPU package. Wraps a `MOS6502` core and owns the memory bus, dividing
+/// the master clock between the CPU, PPU and APU.
 pub struct Ricoh2A03 {
     core: MOS6502,
-    //apu:
-    clock_speed: f64,
-    memory: Arc<Mutex<Vec<u8>>>,
+    bus: Rc<RefCell<Bus>>,
+    /// Decides how the master clock is divided between the CPU and PPU
+    region: Region,
+    /// Master clock cycles run by the CPU that the PPU hasn't caught up on yet
+    ppu_debt: u32,
+    /// When pacing, the moment (and cycle count) execution started being timed
+    pacing: Option<(Instant, u64)>,
+    cycles: u64,
 }
 
 impl Ricoh2A03 {
-    pub fn new(clock_speed: f64) -> {
+    /// Constructs a 2A03 with a fresh bus, PPU and APU, and no cartridge inserted, timed for the
+    /// given region.
+    pub fn new(region: Region) -> Self {
+        let bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+        let mut ricoh = Self {
+            core: MOS6502::new(bus.clone()),
+            bus,
+            region,
+            ppu_debt: 0,
+            pacing: None,
+            cycles: 0,
+        };
+        ricoh.set_region(region);
+        ricoh
+    }
+
+    /// Advances the system by one CPU cycle, clocking the PPU and APU alongside it. The PPU gets
+    /// as many dots as fit in the master clock cycles elapsed, which on PAL alternates between 3
+    /// and 4. DMA requested during the cycle (OAM DMA or a DMC sample fetch) halts the CPU for as
+    /// long as it would take.
+    ///
+    /// When pacing to real time, this sleeps as needed to keep the CPU at the region's clock speed.
+    pub fn tick(&mut self) -> Result<(), RustNesError> {
+        self.core.step()?;
+        let stall = {
+            let bus = self.bus.borrow();
+            self.ppu_debt += self.region.cpu_divider();
+            while self.ppu_debt >= self.region.ppu_divider() {
+                self.ppu_debt -= self.region.ppu_divider();
+                bus.tick_ppu();
+            }
+            let mut stall = bus.tick_apu();
+            if let Some(page) = bus.take_oam_dma() {
+                // The copy is done all at once. Only the CPU can notice it's not spread out, and
+                // it's halted throughout.
+                for offset in 0..=0xFF {
+                    let value = bus.read(u16::from_be_bytes([page, offset]));
+                    bus.write(0x2004, value);
+                }
+                stall += OAM_DMA_CYCLES + (self.core.cycle_count() % 2) as u16;
+            }
+            stall
+        };
+        self.core.stall(stall);
+        self.cycles += 1;
+
+        if let Some((start, start_cycles)) = self.pacing {
+            if self.cycles.is_multiple_of(PACING_INTERVAL) {
+                let target = Duration::from_secs_f64((self.cycles - start_cycles) as f64 / self.region.cpu_clock_speed());
+                if let Some(ahead) = target.checked_sub(start.elapsed()) {
+                    thread::sleep(ahead);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Presses the reset button: the CPU restarts at the reset vector (see `MOS6502::soft_reset`),
+    /// the APU goes silent and PPUCTRL/PPUMASK are cleared. RAM keeps its contents.
+    pub fn soft_reset(&mut self) -> Result<(), RustNesError> {
+        {
+            let bus = self.bus.borrow();
+            bus.ppu().borrow_mut().reset();
+            bus.apu().borrow_mut().reset();
+        }
+        self.core.soft_reset()
+    }
+
+    /// The region the system is timed for.
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Switches the CPU, PPU and APU to the timing of the given region.
+    pub fn set_region(&mut self, region: Region) {
+        self.region = region;
+        self.ppu_debt = 0;
+        let bus = self.bus.borrow();
+        bus.ppu().borrow_mut().set_region(region);
+        bus.apu().borrow_mut().set_region(region);
+        if self.pacing.is_some() {
+            // Start timing afresh, as the clock speed changed
+            self.pacing = Some((Instant::now(), self.cycles));
+        }
+    }
+
+    /// Enables or disables pacing execution to real time.
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.pacing = realtime.then(|| (Instant::now(), self.cycles));
+    }
+
+    /// The CPU core.
+    pub fn core(&self) -> &MOS6502 {
+        &self.core
+    }
+
+    /// The CPU core, mutably.
+    pub fn core_mut(&mut self) -> &mut MOS6502 {
+        &mut self.core
+    }
+
+    /// The memory bus.
+    pub fn bus(&self) -> &Rc<RefCell<Bus>> {
+        &self.bus
     }
 }
 
+impl Stateful for Ricoh2A03 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.region as u8);
+        state.u32(self.ppu_debt);
+        state.u64(self.cycles);
+        self.core.save_state(state);
+        self.bus.borrow().save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        let region = match state.u8()? {
+            0 => Region::Ntsc,
+            1 => Region::Pal,
+            2 => Region::Dendy,
+            _ => return Err(RustNesError::InvalidSaveState),
+        };
+        self.set_region(region);
+        self.ppu_debt = state.u32()?;
+        self.cycles = state.u64()?;
+        self.core.load_state(state)?;
+        self.bus.borrow_mut().load_state(state)?;
+        if self.pacing.is_some() {
+            // Don't try to catch up on (or wait out) the cycles skipped over
+            self.set_realtime(true);
+        }
+        Ok(())
+    }
+}
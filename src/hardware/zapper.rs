@@ -0,0 +1,70 @@
+use crate::RustNesError;
+use crate::save_state::{StateReader, StateWriter, Stateful};
+
+/// The Zapper light gun, read through $4017 in place of the second controller (see
+/// `ControllerPort::Zapper`).
+///
+/// It reports two bits: bit 3 is low while its photodiode sees light, which is a bright pixel
+/// where it's aimed, drawn within the last few scanlines (see `PPU::light_sensed`), and bit 4 is
+/// high while the trigger is pulled.
+#[derive(Default)]
+pub struct Zapper {
+    /// The pixel aimed at, if on screen
+    target: Option<(u16, u16)>,
+    trigger: bool,
+}
+
+impl Zapper {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Aims at pixel (`x`, `y`) of the picture. Aiming outside the 256x240 picture points the
+    /// Zapper off screen, where it never senses light.
+    pub fn aim(&mut self, x: u16, y: u16) {
+        self.target = Some((x, y));
+    }
+
+    /// Points the Zapper away from the screen.
+    pub fn aim_off_screen(&mut self) {
+        self.target = None;
+    }
+
+    /// The pixel aimed at, if any.
+    pub fn target(&self) -> Option<(u16, u16)> {
+        self.target
+    }
+
+    /// Pulls the trigger, which stays pulled until `release_trigger`.
+    pub fn pull_trigger(&mut self) {
+        self.trigger = true;
+    }
+
+    /// Lets go of the trigger.
+    pub fn release_trigger(&mut self) {
+        self.trigger = false;
+    }
+
+    /// The value read from $4017, given whether the PPU is lighting up the pixel aimed at.
+    pub fn read(&self, light_sensed: bool) -> u8 {
+        (!light_sensed as u8) << 3 | (self.trigger as u8) << 4
+    }
+}
+
+impl Stateful for Zapper {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.target.is_some());
+        let (x, y) = self.target.unwrap_or_default();
+        state.u16(x);
+        state.u16(y);
+        state.bool(self.trigger);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError> {
+        let on_screen = state.bool()?;
+        let target = (state.u16()?, state.u16()?);
+        self.target = on_screen.then_some(target);
+        self.trigger = state.bool()?;
+        Ok(())
+    }
+}
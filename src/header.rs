@@ -1,8 +1,22 @@
+use crate::hardware::cart::TRAINER_SIZE;
+use crate::hardware::mapper::{Mirroring, CHR_BANK_SIZE, PRG_BANK_SIZE};
+
 
 pub struct NESHeader {
+    /// Size of PRG-ROM, in bytes
     pub prg_size: usize,
+    /// Size of CHR-ROM, in bytes. 0 means the cartridge uses CHR-RAM.
     pub chr_size: usize,
+    /// Size of volatile PRG-RAM, in bytes (NES 2.0 only, otherwise 0)
+    pub prg_ram_size: usize,
+    /// Size of battery-backed PRG-RAM, in bytes (NES 2.0 only, otherwise 0)
+    pub prg_nvram_size: usize,
+    /// Size of volatile CHR-RAM, in bytes (NES 2.0 only, otherwise 0)
+    pub chr_ram_size: usize,
+    /// Size of battery-backed CHR-RAM, in bytes (NES 2.0 only, otherwise 0)
+    pub chr_nvram_size: usize,
     pub mapper_number: usize,
+    pub submapper: u8,
     pub nes2: bool,
     pub battery: bool,
     pub trainer: bool,
@@ -13,23 +27,41 @@ pub struct NESHeader {
 }
 
 impl NESHeader {
+    /// Size of the iNES header, in bytes.
+    pub const SIZE: usize = 16;
+
     /// Creates a `NESHeader` given the first 16 bytes found at the beginning of the ROM.
+    ///
+    /// Returns `None` if fewer than 16 bytes are supplied or the magic number is missing.
     pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < Self::SIZE {
+            return None;
+        }
         if !(bytes[0] == b'N' && bytes[1] == b'E' && bytes[2] == b'S' && bytes[3] == 0x1A) {
             return None;
         }
-        let prg_size: usize = if bytes[9] & 0x0F == 0x0F {
-            2_i32.pow(bytes[4] as u32 >> 2) as usize * ((bytes[4] as usize & 3) * 2 + 1)
+        let nes2 = (bytes[7] & 0x0C) == 0x08;
+        // Bytes 8-15 are only meaningful in NES 2.0; iNES 1.0 dumps often have garbage there.
+        let (size_msb, mapper_msb, submapper) = if nes2 {
+            (bytes[9], bytes[8] & 0x0F, bytes[8] >> 4)
         } else {
-            bytes[4] as usize | bytes[9] as usize & 0x0F << 8
+            (0, 0, 0)
         };
-        let chr_size: usize = if bytes[9] & 0xF0 == 0xF0 {
-            2_i32.pow(bytes[5] as u32 >> 2) as usize * ((bytes[5] as usize & 3) * 2 + 1)
+        let prg_size = Self::rom_size(bytes[4], size_msb & 0x0F, PRG_BANK_SIZE)?;
+        let chr_size = Self::rom_size(bytes[5], size_msb >> 4, CHR_BANK_SIZE)?;
+        let (prg_ram_size, prg_nvram_size, chr_ram_size, chr_nvram_size) = if nes2 {
+            (
+                Self::ram_size(bytes[10] & 0x0F),
+                Self::ram_size(bytes[10] >> 4),
+                Self::ram_size(bytes[11] & 0x0F),
+                Self::ram_size(bytes[11] >> 4),
+            )
         } else {
-            bytes[5] as usize | bytes[9] as usize & 0xF0 << 4
+            (0, 0, 0, 0)
         };
-        let nes2 = (bytes[7] & 0x0C) == 0x08;
-        let mapper_number = bytes[6] as usize >> 4 | bytes[7] as usize & 0b11110000 | bytes[8] as usize & 0b1111 << 8;
+        let mapper_number = ((mapper_msb as usize) << 8)
+            | (bytes[7] as usize & 0xF0)
+            | (bytes[6] as usize >> 4);
         let battery = bytes[6] & 2 == 2;
         let trainer = bytes[6] & 4 == 4;
         let alt_nametables = bytes[6] & 8 == 8;
@@ -45,7 +77,9 @@ impl NESHeader {
             3 => ConsoleType::Extended(bytes[13] & 0x0F),
             _ => unreachable!(),
         };
-        let timing_mode = match bytes[12] & 3 {
+        // iNES 1.0 has no reliable timing field, so those ROMs are assumed to be NTSC
+        let timing = if nes2 { bytes[12] & 3 } else { 0 };
+        let timing_mode = match timing {
             0 => TimingMode::NTSC,
             1 => TimingMode::PAL,
             2 => TimingMode::Multi,
@@ -56,7 +90,12 @@ impl NESHeader {
         Some(Self {
             prg_size,
             chr_size,
+            prg_ram_size,
+            prg_nvram_size,
+            chr_ram_size,
+            chr_nvram_size,
             mapper_number,
+            submapper,
             nes2,
             battery,
             trainer,
@@ -66,6 +105,46 @@ impl NESHeader {
             timing_mode,
         })
     }
+
+    /// Length of a ROM file with this header: the header itself, the trainer (if any), PRG-ROM and
+    /// CHR-ROM. Saturates instead of overflowing, as no file can be that large anyway.
+    pub fn file_size(&self) -> usize {
+        let trainer_size = if self.trainer { TRAINER_SIZE } else { 0 };
+        (Self::SIZE + trainer_size)
+            .saturating_add(self.prg_size)
+            .saturating_add(self.chr_size)
+    }
+
+    /// The nametable mirroring the cartridge is wired for. Mappers that control mirroring
+    /// themselves only start out with it.
+    pub fn mirroring(&self) -> Mirroring {
+        if self.alt_nametables {
+            return Mirroring::FourScreen;
+        }
+        match self.nametable_layout {
+            NameTableLayout::Vertical => Mirroring::Horizontal,
+            NameTableLayout::Horizontal => Mirroring::Vertical,
+        }
+    }
+
+    /// Decodes a PRG-ROM or CHR-ROM size, in bytes, from its LSB and the matching MSB nibble.
+    ///
+    /// Normally the size is a count of `bank_size` banks, 12 bits wide. An MSB nibble of $F (only
+    /// possible in NES 2.0) means the LSB is instead `EEEEEEMM`, giving a size of
+    /// 2^E * (MM * 2 + 1) bytes. Returns `None` if that doesn't fit in a `usize`.
+    fn rom_size(lsb: u8, msb: u8, bank_size: usize) -> Option<usize> {
+        if msb == 0x0F {
+            let multiplier = (lsb as usize & 3) * 2 + 1;
+            2_usize.checked_pow(lsb as u32 >> 2)?.checked_mul(multiplier)
+        } else {
+            Some(((msb as usize) << 8 | lsb as usize) * bank_size)
+        }
+    }
+
+    /// Decodes a NES 2.0 RAM shift count into a size in bytes: 0 means none, otherwise 64 << shift.
+    fn ram_size(shift: u8) -> usize {
+        if shift == 0 { 0 } else { 64 << shift }
+    }
 }
 
 /// `Vertical`: "mirrored horizontally", or mapper-controlled
@@ -89,3 +168,67 @@ pub enum TimingMode {
     Dendy,
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RustNesError;
+    use crate::hardware::Cart;
+
+    /// An iNES 1.0 header: 2 PRG-ROM banks, 1 CHR-ROM bank, mapper 1, vertical mirroring
+    const INES: [u8; 16] = [b'N', b'E', b'S', 0x1A, 2, 1, 0x11, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    #[test]
+    fn truncated_header_is_rejected() {
+        assert!(NESHeader::from_bytes(&INES).is_some());
+        assert!(NESHeader::from_bytes(&INES[..15]).is_none());
+        assert_eq!(Cart::from_rom(&INES[..15]).err(), Some(RustNesError::InvalidHeader));
+    }
+
+    #[test]
+    fn ines_header() {
+        let header = NESHeader::from_bytes(&INES).unwrap();
+        assert!(!header.nes2);
+        assert_eq!((header.mapper_number, header.submapper), (1, 0));
+        assert_eq!((header.prg_size, header.chr_size), (2 * 16384, 8192));
+
+        // Mapper 4 split across flags 6 and 7, with the garbage old dumps have in bytes 8-15
+        let mut bytes = INES;
+        bytes[6] = 0x41;
+        bytes[7] = 0x00;
+        bytes[8..16].copy_from_slice(b"DiskDude");
+        let header = NESHeader::from_bytes(&bytes).unwrap();
+        assert_eq!((header.mapper_number, header.submapper), (4, 0));
+        assert_eq!((header.prg_size, header.chr_size), (2 * 16384, 8192));
+    }
+
+    #[test]
+    fn nes2_header() {
+        let mut bytes = INES;
+        bytes[6] = 0x40;
+        bytes[7] = 0x08;
+        // Submapper 2, mapper bits 8-11 = 1
+        bytes[8] = 0x21;
+        // Size MSBs: CHR-ROM 2, PRG-ROM 1
+        bytes[9] = 0x21;
+        let header = NESHeader::from_bytes(&bytes).unwrap();
+        assert!(header.nes2);
+        assert_eq!((header.mapper_number, header.submapper), (260, 2));
+        assert_eq!((header.prg_size, header.chr_size), (0x102 * 16384, 0x201 * 8192));
+    }
+
+    #[test]
+    fn nes2_sizes() {
+        let mut bytes = INES;
+        bytes[7] = 0x08;
+        // PRG-ROM in exponent form: 2^1 * (3 * 2 + 1) bytes. CHR-ROM in standard form: 1 bank
+        bytes[4] = 0x07;
+        bytes[9] = 0x0F;
+        // PRG-RAM and CHR-NVRAM shift counts of 7: 64 << 7 bytes
+        bytes[10] = 0x07;
+        bytes[11] = 0x70;
+        let header = NESHeader::from_bytes(&bytes).unwrap();
+        assert_eq!((header.prg_size, header.chr_size), (14, 8192));
+        assert_eq!((header.prg_ram_size, header.prg_nvram_size), (8192, 0));
+        assert_eq!((header.chr_ram_size, header.chr_nvram_size), (0, 8192));
+    }
+}
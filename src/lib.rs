@@ -0,0 +1,15 @@
+pub mod hardware;
+pub mod disasm;
+pub mod header;
+pub mod error;
+pub mod palette;
+pub mod nes;
+pub mod save_state;
+pub mod movie;
+pub mod testing;
+pub use crate::header::NESHeader;
+pub use crate::error::RustNesError;
+pub use crate::hardware::*;
+pub use crate::nes::{Nes, StopReason};
+pub use crate::save_state::SaveState;
+pub use crate::movie::InputMovie;
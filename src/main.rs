@@ -1,13 +1,9 @@
-pub mod hardware;
-pub mod header;
-pub mod error;
-use crate::header::NESHeader;
-use crate::error::RustNesError;
-use crate::hardware::*;
+use rust_nes::Nes;
+use rust_nes::error::RustNesError;
+use rust_nes::testing;
 
-use std::cell::RefCell;
-use std::rc::Rc;
 use std::fs;
+use std::path::Path;
 use clap::Parser;
 
 #[derive(Parser)]
@@ -16,49 +12,92 @@ struct Cli {
     /// Path to search in
     #[arg()]
     file: String,
+    /// Run a blargg-style test ROM headlessly and report the result it writes to $6000
+    #[arg(long)]
+    test_rom: bool,
+    /// Run nestest.nes in automated mode and compare against nestest.log (next to the ROM, or
+    /// wherever the NESTEST_LOG environment variable points)
+    #[arg(long)]
+    nestest: bool,
 }
 
+/// How long a test ROM gets to finish (about a minute of emulated time)
+const TEST_ROM_MAX_FRAMES: u32 = 3600;
+
 fn main() -> Result<(), RustNesError> {
     let args = Cli::parse();
 
-    // Load Cartridge
-    let cart = {
-        let rom_file = match fs::read(&args.file) {
-            Ok(data) => data,
+    let save_path = Path::new(&args.file).with_extension("sav");
+
+    let rom_file = match fs::read(&args.file) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("Error: can't open file '{}': {}", args.file, err);
+            std::process::exit(0x02);
+        }
+    };
+
+    if args.test_rom {
+        let result = testing::run_test_rom(&rom_file, TEST_ROM_MAX_FRAMES)?;
+        println!("{}", result.message.trim_end());
+        if !result.passed() {
+            eprintln!("Test ROM failed with status {:#04x}", result.status);
+            std::process::exit(0x01);
+        }
+        return Ok(());
+    }
+
+    if args.nestest {
+        let log_path = std::env::var("NESTEST_LOG")
+            .unwrap_or_else(|_| Path::new(&args.file).with_extension("log").display().to_string());
+        let log = match fs::read_to_string(&log_path) {
+            Ok(log) => log,
             Err(err) => {
-                eprintln!("Error: can't open file '{}': {}", args.file, err);
+                eprintln!("Error: can't open log '{}': {}", log_path, err);
                 std::process::exit(0x02);
             }
         };
-        let header = NESHeader::from_bytes(&rom_file[0..15]).ok_or(RustNesError::InvalidHeader)?;
-        RefCell::new(Cart::new(header, &rom_file[16..]))
-    };
-
-    // Initialize Hardware
-    let my_ppu = RefCell::new(PPU::new());
-    let my_apu = RefCell::new(APU::new());
-    let my_bus = Rc::new(RefCell::new(Bus::new(my_ppu, my_apu)));
-    let mut my_cpu = MOS6502::new(my_bus.clone());
+        match testing::run_nestest(&rom_file, &log) {
+            Ok(lines) => println!("nestest passed ({} lines matched)", lines),
+            Err(mismatch) => {
+                eprintln!("nestest diverged at line {}:", mismatch.line);
+                eprintln!("  expected: {}", mismatch.expected);
+                eprintln!("  actual:   {}", mismatch.actual);
+                std::process::exit(0x01);
+            }
+        }
+        return Ok(());
+    }
 
-    // Input cart
-    my_bus.borrow_mut().load_cart(cart);
+    // Load Cartridge
+    let mut nes = Nes::new();
+    nes.load_rom(&rom_file)?;
+    if let Some(cart) = nes.bus().borrow().cart() {
+        let mut cart = cart.borrow_mut();
+        if cart.header().battery {
+            if let Ok(save) = fs::read(&save_path) {
+                if let Err(err) = cart.load_save_ram(&save) {
+                    eprintln!("Warning: ignoring save file '{}': {}", save_path.display(), err);
+                }
+            }
+        }
+    }
 
-    // Just go through the sample code in the cart, make sure it all works
-    my_cpu.reset()?;
-    println!("Program counter is now 0x{:x}", my_cpu.program_counter);
-    my_cpu.step()?;
-    my_cpu.step()?;
-    my_cpu.step()?;
-    my_cpu.step()?;
-    my_cpu.step()?;
+    // Run the first frame of the cart, make sure it all works
+    nes.reset()?;
+    println!("Program counter is now 0x{:x}", nes.cpu().pc());
+    let stop_reason = nes.run_frame()?;
+    println!("Ran the first frame ({:?}), program counter is now 0x{:x}", stop_reason, nes.cpu().pc());
 
-    {
-        let bus_access = my_bus.borrow();
-        println!("The value at the address 0x00 is: {}", bus_access.read(0x00));
+    // Persist battery-backed RAM
+    if let Some(cart) = nes.bus().borrow().cart() {
+        if let Some(ram) = cart.borrow().save_ram() {
+            if let Err(err) = fs::write(&save_path, ram) {
+                eprintln!("Error: can't write save file '{}': {}", save_path.display(), err);
+            }
+        }
     }
 
-    assert_eq!(my_cpu.step(), Err(RustNesError::InvalidOpcode(0)));
-
     Ok(())
 }
 
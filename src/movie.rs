@@ -0,0 +1,75 @@
+use crate::RustNesError;
+use crate::hardware::Buttons;
+
+/// Identifies input movie blobs, followed by `VERSION`
+const MAGIC: &[u8; 4] = b"RNMV";
+/// Bumped whenever the layout changes, so stale blobs get rejected instead of misread
+const VERSION: u8 = 1;
+/// Controllers recorded each frame, as many as a Four Score takes
+const PORTS: usize = 4;
+
+/// The buttons pressed on each controller, frame by frame, recorded with `Nes::start_recording`
+/// and replayed with `Nes::play_movie`. Since the console is deterministic (see `Nes::with_seed`),
+/// replaying a movie on the same ROM and seed reproduces the run exactly.
+///
+/// Serialized, a movie is `RNMV`, a version byte, then 4 bytes per frame: the `Buttons` of
+/// controllers 1 to 4. The Zapper isn't recorded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InputMovie {
+    data: Vec<u8>,
+}
+
+impl Default for InputMovie {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InputMovie {
+    /// Constructs a movie with no frames.
+    pub fn new() -> Self {
+        let mut data = MAGIC.to_vec();
+        data.push(VERSION);
+        Self { data }
+    }
+
+    /// The serialized movie, for writing to a file.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Wraps a previously serialized movie.
+    ///
+    /// Returns `RustNesError::InvalidMovie` if the data wasn't made by this version of the
+    /// emulator, or ends partway through a frame.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, RustNesError> {
+        let header = MAGIC.len() + 1;
+        if data.len() < header || &data[..MAGIC.len()] != MAGIC || data[MAGIC.len()] != VERSION
+            || !(data.len() - header).is_multiple_of(PORTS)
+        {
+            return Err(RustNesError::InvalidMovie);
+        }
+        Ok(Self { data })
+    }
+
+    /// Number of frames recorded.
+    pub fn len(&self) -> usize {
+        (self.data.len() - MAGIC.len() - 1) / PORTS
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The buttons pressed on controllers 1 to 4 during the given frame, counting from 0.
+    pub fn frame(&self, index: usize) -> Option<[Buttons; PORTS]> {
+        let start = MAGIC.len() + 1 + index * PORTS;
+        let bytes = self.data.get(start..start + PORTS)?;
+        Some(std::array::from_fn(|port| Buttons::from_bits_retain(bytes[port])))
+    }
+
+    /// Appends a frame, with the buttons pressed on controllers 1 to 4.
+    pub fn push_frame(&mut self, buttons: [Buttons; PORTS]) {
+        self.data.extend(buttons.map(|buttons| buttons.bits()));
+    }
+}
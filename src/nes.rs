@@ -0,0 +1,761 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::{disasm, InputMovie, RustNesError, SaveState};
+use crate::hardware::*;
+use crate::hardware::ppu::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use crate::palette::Palette;
+
+/// The whole console: CPU, PPU and APU wired together through the memory bus, plus whatever
+/// cartridge is inserted.
+///
+/// Nothing here panics or exits on bad input; errors are returned as `RustNesError`, and
+/// controller ports that don't exist are ignored. Memory is only allocated by `new`, `load_rom`,
+/// `save_state`, `load_state`, `state_hash`, `framebuffer_rgb`, `disassemble_range` and the
+/// `render_*` debug views (and when adding watchpoints or callbacks, or recording a movie), so
+/// running frames is safe for frontends that can't afford allocations mid-frame.
+///
+/// # Examples
+///
+/// ```rust,no_run
+/// use rust_nes::Nes;
+///
+/// let rom = std::fs::read("game.nes").unwrap();
+/// let mut nes = Nes::new();
+/// nes.load_rom(&rom).unwrap();
+/// nes.reset().unwrap();
+/// nes.run_frame().unwrap();
+/// let picture = nes.framebuffer();
+/// ```
+pub struct Nes {
+    ricoh: Ricoh2A03,
+    /// Copy of the last frame the PPU completed
+    framebuffer: Vec<u8>,
+    /// What RAM holds at power-on
+    ram_init: RamInit,
+    /// Region to run as regardless of what cartridges ask for
+    region_override: Option<Region>,
+    /// Inputs recorded so far, while recording
+    recording: Option<InputMovie>,
+    /// The movie being played back, and the next frame of it to play
+    playback: Option<(InputMovie, usize)>,
+    /// Whether `run_frame` stopped partway through a frame, whose inputs are already in
+    frame_in_progress: bool,
+    /// Called with each completed frame
+    on_frame: Option<FrameCallback>,
+    /// Called with the audio generated during each frame
+    on_audio: Option<AudioCallback>,
+    /// Where audio gets pulled into for `on_audio`
+    audio_chunk: Vec<f32>,
+}
+
+type FrameCallback = Box<dyn FnMut(&[u8])>;
+type AudioCallback = Box<dyn FnMut(&[f32])>;
+
+/// Most audio samples handed to the `Nes::on_audio` callback at once.
+const AUDIO_CHUNK_SIZE: usize = 1024;
+
+/// Why `Nes::run_frame` returned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    /// The PPU completed a frame
+    FrameComplete,
+    /// The CPU is about to execute the instruction at a breakpoint
+    Breakpoint(u16),
+    /// A watched address was accessed. The access has already happened.
+    Watchpoint(u16, BusAccess),
+}
+
+impl Default for Nes {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Nes {
+    /// Constructs a console with no cartridge inserted.
+    pub fn new() -> Self {
+        Self {
+            ricoh: Ricoh2A03::new(Region::default()),
+            framebuffer: vec![0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            ram_init: RamInit::default(),
+            region_override: None,
+            recording: None,
+            playback: None,
+            frame_in_progress: false,
+            on_frame: None,
+            on_audio: None,
+            audio_chunk: Vec::new(),
+        }
+    }
+
+    /// Constructs a console with no cartridge inserted, whose RAM powers on filled the given way
+    /// instead of with zeroes. Real RAM starts out indeterminate, and some games and tests notice.
+    pub fn with_ram_init(ram_init: RamInit) -> Self {
+        let mut nes = Self::new();
+        nes.ram_init = ram_init;
+        nes.fill_ram();
+        nes
+    }
+
+    /// Constructs a console with no cartridge inserted, whose RAM powers on with pseudo-random
+    /// contents generated from `seed` (see `RamInit::Seeded`). That's the only thing not fixed
+    /// at power-on, so with the same seed, ROM and inputs, every run ends up in exactly the same
+    /// state. Realtime pacing (`set_realtime`) only affects how fast that happens.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_nes::Nes;
+    ///
+    /// // An NROM cartridge that copies $0000 to $0300, then loops forever
+    /// let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    /// let mut prg = vec![0; 0x4000];
+    /// prg[0..9].copy_from_slice(&[0xAD, 0x00, 0x00, 0x8D, 0x00, 0x03, 0x4C, 0x06, 0x80]);
+    /// prg[0x3FFD] = 0x80;
+    /// rom.extend(prg);
+    /// rom.extend(vec![0; 0x2000]);
+    ///
+    /// let run = |seed| {
+    ///     let mut nes = Nes::with_seed(seed);
+    ///     nes.load_rom(&rom).unwrap();
+    ///     nes.reset().unwrap();
+    ///     for _ in 0..3 {
+    ///         nes.next_frame().unwrap();
+    ///     }
+    ///     nes.save_state()
+    /// };
+    /// assert_eq!(run(42), run(42));
+    /// assert_ne!(run(42), run(43));
+    /// ```
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_ram_init(RamInit::Seeded(seed))
+    }
+
+    /// Changes what RAM is filled with when a cartridge is inserted with `load_rom`.
+    pub fn set_ram_init(&mut self, ram_init: RamInit) {
+        self.ram_init = ram_init;
+    }
+
+    /// Power-cycles RAM, filling it according to `ram_init`.
+    fn fill_ram(&self) {
+        *self.ricoh.bus().borrow().work_memory().borrow_mut() = WorkMemory::with_fill(self.ram_init);
+    }
+
+    /// Inserts a cartridge, given the contents of an iNES ROM file. The console switches to the
+    /// region the header asks for (unless overridden, see `set_region_override`), and RAM is
+    /// refilled as if the console had been powered off and on (see `set_ram_init`).
+    pub fn load_rom(&mut self, bytes: &[u8]) -> Result<(), RustNesError> {
+        let cart = Cart::from_rom(bytes)?;
+        self.ricoh.set_region(self.region_override.unwrap_or(Region::from(&cart.header().timing_mode)));
+        self.ricoh.bus().borrow_mut().load_cart(RefCell::new(cart));
+        self.fill_ram();
+        Ok(())
+    }
+
+    /// Puts the CPU into its power-on state and starts execution at the cartridge's reset vector.
+    /// Call this once after `load_rom`.
+    pub fn reset(&mut self) -> Result<(), RustNesError> {
+        self.ricoh.core_mut().reset()
+    }
+
+    /// Presses the reset button. Unlike `reset`, the CPU's registers mostly keep their values (see
+    /// `MOS6502::soft_reset`); the APU is silenced and PPUCTRL/PPUMASK are cleared. RAM is left
+    /// untouched, which some games check to tell a reset from a power-on.
+    pub fn soft_reset(&mut self) -> Result<(), RustNesError> {
+        self.ricoh.soft_reset()
+    }
+
+    /// Steps the console by one CPU cycle (3 PPU dots).
+    pub fn step(&mut self) -> Result<(), RustNesError> {
+        self.ricoh.tick()
+    }
+
+    /// Steps the console until the CPU finishes its current instruction, and returns the number of
+    /// CPU cycles that took. That includes any time the CPU spent halted by DMA, so a write to
+    /// $4014 takes over 500.
+    pub fn step_instruction(&mut self) -> Result<u16, RustNesError> {
+        let mut cycles = 0;
+        loop {
+            self.step()?;
+            cycles += 1;
+            if self.cpu().pending_u_ops().next().is_none() {
+                return Ok(cycles);
+            }
+        }
+    }
+
+    /// Runs the console until the PPU completes a frame. How many cycles that takes depends on the
+    /// region (about 29781 CPU cycles on NTSC, 33248 on PAL).
+    ///
+    /// Stops early if a breakpoint or watchpoint is hit, in which case calling this again resumes
+    /// the frame where it left off.
+    pub fn run_frame(&mut self) -> Result<StopReason, RustNesError> {
+        if !self.frame_in_progress {
+            self.latch_movie_input();
+        }
+        self.frame_in_progress = true;
+        let frame = self.ricoh.bus().borrow().ppu().borrow().frame_count();
+        while self.ricoh.bus().borrow().ppu().borrow().frame_count() == frame {
+            self.step()?;
+            if let Some((address, access)) = self.ricoh.bus().borrow().take_watchpoint_hit() {
+                return Ok(StopReason::Watchpoint(address, access));
+            }
+            if self.cpu().at_breakpoint() {
+                return Ok(StopReason::Breakpoint(self.cpu().pc()));
+            }
+        }
+        self.frame_in_progress = false;
+        self.framebuffer.copy_from_slice(self.ricoh.bus().borrow().ppu().borrow().framebuffer());
+        if let Some(on_frame) = &mut self.on_frame {
+            on_frame(&self.framebuffer);
+        }
+        if let Some(on_audio) = &mut self.on_audio {
+            loop {
+                let count = self.ricoh.bus().borrow().apu().borrow_mut().pull_samples(&mut self.audio_chunk);
+                if count == 0 {
+                    break;
+                }
+                on_audio(&self.audio_chunk[..count]);
+            }
+        }
+        Ok(StopReason::FrameComplete)
+    }
+
+    /// Makes `run_frame` (and `next_frame`) call `callback` whenever a frame completes, with the
+    /// picture: 256x240 NES color numbers, row by row (see `framebuffer`). Replaces any previous
+    /// frame callback.
+    pub fn on_frame(&mut self, callback: impl FnMut(&[u8]) + 'static) {
+        self.on_frame = Some(Box::new(callback));
+    }
+
+    /// Makes `run_frame` (and `next_frame`) call `callback` whenever a frame completes, with the
+    /// audio generated during it: mono samples from 0.0 to 1.0 at the APU's sample rate (44100 Hz
+    /// unless changed), oldest first, in chunks of up to 1024. Samples handed over this way are no
+    /// longer available to `pull_samples`. Replaces any previous audio callback.
+    pub fn on_audio(&mut self, callback: impl FnMut(&[f32]) + 'static) {
+        self.on_audio = Some(Box::new(callback));
+        self.audio_chunk.resize(AUDIO_CHUNK_SIZE, 0.0);
+    }
+
+    /// Runs the console until the PPU completes a frame, and returns it (see `framebuffer`).
+    ///
+    /// Unlike `run_frame`, this doesn't stop at breakpoints or watchpoints, which suits frontends
+    /// that just want the next picture. Doesn't allocate.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_nes::Nes;
+    ///
+    /// // An NROM cartridge whose program is an endless JMP to itself, at the reset vector ($8000)
+    /// let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    /// let mut prg = vec![0; 0x4000];
+    /// prg[0..3].copy_from_slice(&[0x4C, 0x00, 0x80]);
+    /// prg[0x3FFD] = 0x80;
+    /// rom.extend(prg);
+    /// rom.extend(vec![0; 0x2000]);
+    ///
+    /// let mut nes = Nes::new();
+    /// nes.load_rom(&rom).unwrap();
+    /// nes.reset().unwrap();
+    /// for _ in 0..2 {
+    ///     assert_eq!(nes.next_frame().unwrap().len(), 256 * 240);
+    /// }
+    /// ```
+    pub fn next_frame(&mut self) -> Result<&[u8], RustNesError> {
+        while self.run_frame()? != StopReason::FrameComplete {}
+        Ok(&self.framebuffer)
+    }
+
+    /// Makes `run_frame` stop once the CPU is about to execute the instruction at `address`.
+    pub fn add_breakpoint(&mut self, address: u16) {
+        self.ricoh.core_mut().add_breakpoint(address)
+    }
+
+    /// Removes a breakpoint added with `add_breakpoint`.
+    pub fn remove_breakpoint(&mut self, address: u16) {
+        self.ricoh.core_mut().remove_breakpoint(address)
+    }
+
+    /// Makes `run_frame` stop right after the given kind of access to `address` (by the CPU or
+    /// DMA).
+    pub fn add_watchpoint(&mut self, address: u16, access: BusAccess) {
+        self.ricoh.bus().borrow_mut().add_watchpoint(address, access)
+    }
+
+    /// Removes a watchpoint added with `add_watchpoint`.
+    pub fn remove_watchpoint(&mut self, address: u16, access: BusAccess) {
+        self.ricoh.bus().borrow_mut().remove_watchpoint(address, access)
+    }
+
+    /// Reads a byte from the CPU's address space without any side effects (see `Bus::peek`).
+    pub fn peek(&self, address: u16) -> u8 {
+        self.ricoh.bus().borrow().peek(address)
+    }
+
+    /// Writes a byte into RAM or the cartridge, without triggering watchpoints. Writes to the I/O
+    /// registers are ignored (see `Bus::poke`).
+    pub fn poke(&mut self, address: u16, value: u8) {
+        self.ricoh.bus().borrow().poke(address, value)
+    }
+
+    /// Disassembles the instructions starting from `start` to `end` (inclusive) of the CPU's
+    /// address space, as `(address, text, length)`. Memory is read with `peek`, so registers
+    /// aren't disturbed. See `disasm::disassemble_range`.
+    pub fn disassemble_range(&self, start: u16, end: u16) -> impl Iterator<Item = (u16, String, u16)> + '_ {
+        let bus = self.ricoh.bus();
+        disasm::disassemble_range(move |address| bus.borrow().peek(address), start, end)
+    }
+
+    /// The last completed frame, as 256x240 NES color numbers (see `palette` for converting them
+    /// to RGB).
+    pub fn framebuffer(&self) -> &[u8] {
+        &self.framebuffer
+    }
+
+    /// The last completed frame, converted to RGB with the given palette and the PPU's current
+    /// grayscale and emphasis settings.
+    pub fn framebuffer_rgb(&self, palette: &Palette) -> Vec<[u8; 3]> {
+        let mask = self.ricoh.bus().borrow().ppu().borrow().mask();
+        self.framebuffer
+            .iter()
+            .map(|&index| {
+                let (r, g, b) = palette.rgb_masked(index, mask);
+                [r, g, b]
+            })
+            .collect()
+    }
+
+    /// Moves buffered audio samples (mono, 0.0-1.0, 44100 Hz by default) into `out`. Returns how
+    /// many samples were written.
+    pub fn pull_samples(&mut self, out: &mut [f32]) -> usize {
+        self.ricoh.bus().borrow().apu().borrow_mut().pull_samples(out)
+    }
+
+    /// Sets the buttons held on the controller plugged into the given port (0 or 1, or 2 and 3 for
+    /// the third and fourth controllers on a Four Score). Other ports are ignored.
+    pub fn set_buttons(&mut self, port: usize, buttons: Buttons) {
+        if let Some(controller) = self.ricoh.bus().borrow().controller(port) {
+            controller.borrow_mut().set_buttons(buttons)
+        }
+    }
+
+    /// Draws one of the four nametables (0-3) as a 256x240 picture of NES color numbers, for
+    /// debugging. See `PPU::render_nametable`.
+    pub fn render_nametable(&self, index: u8) -> Vec<u8> {
+        let bus = self.ricoh.bus().borrow();
+        let picture = bus.ppu().borrow().render_nametable(index, bus.cart());
+        picture
+    }
+
+    /// Draws a pattern table (0 or 1) as a 128x128 tile sheet of NES color numbers, using one of
+    /// the eight palettes, for debugging. See `PPU::render_pattern_table`.
+    pub fn render_pattern_table(&self, table: u8, palette: u8) -> Vec<u8> {
+        let bus = self.ricoh.bus().borrow();
+        let picture = bus.ppu().borrow().render_pattern_table(table, palette, bus.cart());
+        picture
+    }
+
+    /// Gives buttons on the controller plugged into the given port (0-3, see `set_buttons`) turbo,
+    /// pressing and releasing them `hz` times a second while they're held. Rates are rounded to a
+    /// whole number of frames per press, and anything of 0 or below turns turbo off.
+    pub fn set_turbo(&mut self, port: usize, buttons: Buttons, hz: f64) {
+        let frames = if hz > 0.0 {
+            (self.region().frame_rate() / (2.0 * hz)).round().max(1.0) as u32
+        } else {
+            0
+        };
+        if let Some(controller) = self.ricoh.bus().borrow().controller(port) {
+            controller.borrow_mut().set_turbo(buttons, frames)
+        }
+    }
+
+    /// Changes what's plugged into the controller ports: a controller in each (the default), a
+    /// Four Score for four players, or a controller and a Zapper.
+    pub fn set_controller_port(&mut self, controller_port: ControllerPort) {
+        self.ricoh.bus().borrow_mut().set_controller_port(controller_port)
+    }
+
+    /// Aims the Zapper at pixel (`x`, `y`) of the picture, or off screen with `None`.
+    pub fn aim_zapper(&mut self, target: Option<(u16, u16)>) {
+        let bus = self.ricoh.bus().borrow();
+        let mut zapper = bus.zapper().borrow_mut();
+        match target {
+            Some((x, y)) => zapper.aim(x, y),
+            None => zapper.aim_off_screen(),
+        }
+    }
+
+    /// Pulls (or releases) the Zapper's trigger.
+    pub fn set_zapper_trigger(&mut self, pulled: bool) {
+        let bus = self.ricoh.bus().borrow();
+        let mut zapper = bus.zapper().borrow_mut();
+        if pulled { zapper.pull_trigger() } else { zapper.release_trigger() }
+    }
+
+    /// The scanline the PPU is on (see `PPU::scanline`).
+    pub fn scanline(&self) -> i16 {
+        self.ricoh.bus().borrow().ppu().borrow().scanline()
+    }
+
+    /// The dot the PPU is on within its scanline, which advances by 3 every CPU cycle (see
+    /// `PPU::dot`).
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_nes::Nes;
+    ///
+    /// // An NROM cartridge whose program is an endless JMP to itself, at the reset vector ($8000)
+    /// let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    /// let mut prg = vec![0; 0x4000];
+    /// prg[0..3].copy_from_slice(&[0x4C, 0x00, 0x80]);
+    /// prg[0x3FFD] = 0x80;
+    /// rom.extend(prg);
+    /// rom.extend(vec![0; 0x2000]);
+    ///
+    /// let mut nes = Nes::new();
+    /// nes.load_rom(&rom).unwrap();
+    /// nes.reset().unwrap();
+    /// // Into the middle of the picture
+    /// for _ in 0..10_000 {
+    ///     nes.step().unwrap();
+    /// }
+    /// let position = |nes: &Nes| nes.scanline() as i32 * 341 + nes.dot() as i32;
+    /// let start = position(&nes);
+    /// for _ in 0..100 {
+    ///     nes.step().unwrap();
+    /// }
+    /// assert!((0..240).contains(&nes.scanline()));
+    /// assert_eq!(position(&nes) - start, 300);
+    /// ```
+    pub fn dot(&self) -> u16 {
+        self.ricoh.bus().borrow().ppu().borrow().dot()
+    }
+
+    /// Starts recording the buttons pressed on each controller, once per frame run with
+    /// `run_frame` (or `next_frame`), starting with the next one. Turbo is recorded as it comes
+    /// out, so the movie plays back the same without it. Discards any recording in progress.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(InputMovie::new());
+    }
+
+    /// Stops recording, and returns the frames recorded (none if `start_recording` wasn't called).
+    pub fn stop_recording(&mut self) -> InputMovie {
+        self.recording.take().unwrap_or_default()
+    }
+
+    /// Plays back a movie: starting with the next frame, each frame run with `run_frame` (or
+    /// `next_frame`) sets the buttons held on every controller to the movie's, overriding
+    /// `set_buttons`. Once the movie runs out, the buttons of its last frame stay held. Replaces
+    /// any movie already playing.
+    ///
+    /// To reproduce a run, play its movie from the same state: same ROM, seed (see `with_seed`)
+    /// and controller ports, just after `reset` or `load_state`. Turn turbo off first, as the
+    /// movie already has it baked in.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_nes::{Buttons, InputMovie, Nes};
+    ///
+    /// // An NROM cartridge that counts the frames A is held on controller 1 in $00
+    /// let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    /// let mut prg = vec![0; 0x4000];
+    /// prg[0..27].copy_from_slice(&[
+    ///     0xA9, 0x01, 0x8D, 0x16, 0x40, // LDA #$01, STA $4016
+    ///     0xA9, 0x00, 0x8D, 0x16, 0x40, // LDA #$00, STA $4016
+    ///     0xAD, 0x16, 0x40, 0x29, 0x01, // LDA $4016, AND #$01
+    ///     0x18, 0x65, 0x00, 0x85, 0x00, // CLC, ADC $00, STA $00
+    ///     0x2C, 0x02, 0x20,             // BIT $2002 (until vblank)
+    ///     0x10, 0xFB,                   // BPL
+    ///     0x30, 0xE5,                   // BMI back to the start
+    /// ]);
+    /// prg[0x3FFD] = 0x80;
+    /// rom.extend(prg);
+    /// rom.extend(vec![0; 0x2000]);
+    ///
+    /// let mut nes = Nes::with_seed(7);
+    /// nes.load_rom(&rom).unwrap();
+    /// nes.reset().unwrap();
+    /// nes.start_recording();
+    /// for frame in 0..6 {
+    ///     nes.set_buttons(0, if frame % 3 == 0 { Buttons::empty() } else { Buttons::A });
+    ///     nes.next_frame().unwrap();
+    /// }
+    /// let movie = nes.stop_recording();
+    /// assert_eq!(movie.len(), 6);
+    ///
+    /// let mut replay = Nes::with_seed(7);
+    /// replay.load_rom(&rom).unwrap();
+    /// replay.reset().unwrap();
+    /// replay.play_movie(InputMovie::from_bytes(movie.as_bytes().to_vec()).unwrap());
+    /// for _ in 0..6 {
+    ///     replay.next_frame().unwrap();
+    /// }
+    /// assert_ne!(replay.peek(0x0000), 0);
+    /// assert_eq!(replay.state_hash(), nes.state_hash());
+    /// ```
+    pub fn play_movie(&mut self, movie: InputMovie) {
+        self.playback = Some((movie, 0));
+    }
+
+    /// Whether a movie is being played back and has frames left.
+    pub fn movie_playing(&self) -> bool {
+        self.playback.as_ref().is_some_and(|(movie, next)| *next < movie.len())
+    }
+
+    /// At the start of a frame: sets the buttons from the movie being played back, then records
+    /// them if recording.
+    fn latch_movie_input(&mut self) {
+        if let Some((movie, next)) = &mut self.playback {
+            match movie.frame(*next) {
+                Some(buttons) => {
+                    *next += 1;
+                    for (port, buttons) in buttons.into_iter().enumerate() {
+                        self.set_buttons(port, buttons);
+                    }
+                }
+                None => self.playback = None,
+            }
+        }
+        if let Some(recording) = &mut self.recording {
+            let bus = self.ricoh.bus().borrow();
+            recording.push_frame(std::array::from_fn(|port| {
+                bus.controller(port).map_or(Buttons::empty(), |controller| controller.borrow().pressed())
+            }));
+        }
+    }
+
+    /// The CPU, for inspecting its state.
+    pub fn cpu(&self) -> &MOS6502 {
+        self.ricoh.core()
+    }
+
+    /// The memory bus, for inspecting the rest of the hardware.
+    pub fn bus(&self) -> &Rc<RefCell<Bus>> {
+        self.ricoh.bus()
+    }
+
+    /// Captures the state of the whole console, to be restored with `load_state`.
+    pub fn save_state(&self) -> SaveState {
+        SaveState::save(&self.ricoh)
+    }
+
+    /// Hashes the state of the whole console (everything `save_state` captures) into a single
+    /// value, for checking that two runs stayed in sync or that a ROM still ends up where it used
+    /// to. See `SaveState::hash`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_nes::Nes;
+    ///
+    /// // An NROM cartridge whose program is an endless JMP to itself, at the reset vector ($8000)
+    /// let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    /// let mut prg = vec![0; 0x4000];
+    /// prg[0..3].copy_from_slice(&[0x4C, 0x00, 0x80]);
+    /// prg[0x3FFD] = 0x80;
+    /// rom.extend(prg);
+    /// rom.extend(vec![0; 0x2000]);
+    ///
+    /// let run = || {
+    ///     let mut nes = Nes::new();
+    ///     nes.load_rom(&rom).unwrap();
+    ///     nes.reset().unwrap();
+    ///     nes.next_frame().unwrap();
+    ///     nes
+    /// };
+    /// let mut nes = run();
+    /// let hash = nes.state_hash();
+    /// assert_eq!(run().state_hash(), hash);
+    ///
+    /// nes.poke(0x0000, 0x42);
+    /// assert_ne!(nes.state_hash(), hash);
+    /// ```
+    pub fn state_hash(&self) -> u64 {
+        self.save_state().hash()
+    }
+
+    /// Restores a state captured by `save_state`, with the same ROM loaded.
+    ///
+    /// Returns `RustNesError::InvalidSaveState` if the state is corrupt or doesn't fit the loaded
+    /// cartridge, in which case the console is left half-restored and should be reset.
+    pub fn load_state(&mut self, state: &SaveState) -> Result<(), RustNesError> {
+        state.load(&mut self.ricoh)?;
+        self.framebuffer.copy_from_slice(self.ricoh.bus().borrow().ppu().borrow().framebuffer());
+        Ok(())
+    }
+
+    /// The region the console is timed for.
+    pub fn region(&self) -> Region {
+        self.ricoh.region()
+    }
+
+    /// Runs the console as `region` from now on, whatever cartridges' headers say, for ROMs
+    /// with a wrong or missing timing mode (or for playing them as another region would). `None`
+    /// goes back to following the header, that of the current cartridge included.
+    pub fn set_region_override(&mut self, region: Option<Region>) {
+        self.region_override = region;
+        let region = region.or_else(|| {
+            let bus = self.ricoh.bus().borrow();
+            bus.cart().map(|cart| Region::from(&cart.borrow().header().timing_mode))
+        });
+        self.ricoh.set_region(region.unwrap_or_default());
+    }
+
+    /// Enables or disables pacing execution to the speed of real hardware.
+    ///
+    /// Pacing reads the system clock and sleeps the thread, so leave it off on targets without
+    /// either (like `wasm32-unknown-unknown`) and pace frames from the host instead.
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.ricoh.set_realtime(realtime)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use super::*;
+    use crate::testing::nrom;
+
+    #[test]
+    fn bad_roms_are_errors() {
+        assert_eq!(Nes::new().load_rom(b"junk"), Err(RustNesError::InvalidHeader));
+        let rom = nrom(&[]);
+        assert_eq!(
+            Nes::new().load_rom(&rom[..100]),
+            Err(RustNesError::RomTooSmall { expected: rom.len(), actual: 100 }),
+        );
+    }
+
+    #[test]
+    fn missing_controller_ports_are_ignored() {
+        let mut nes = Nes::new();
+        nes.set_buttons(4, Buttons::A);
+        nes.set_turbo(usize::MAX, Buttons::B, 10.0);
+    }
+
+    #[test]
+    fn cpu_runs_the_cartridge() {
+        // LDX #$05, loop: TXA, STA $00,X, DEX, BNE loop, then JMP to itself
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&[0xA2, 0x05, 0x8A, 0x95, 0x00, 0xCA, 0xD0, 0xFA, 0x4C, 0x08, 0x80])).unwrap();
+        nes.reset().unwrap();
+        assert_eq!(nes.cpu().pc(), 0x8000);
+        nes.next_frame().unwrap();
+        // Somewhere in the final JMP, as frames end on any cycle
+        assert!((0x8008..=0x800A).contains(&nes.cpu().pc()));
+        assert_eq!((0..=5).map(|address| nes.peek(address)).collect::<Vec<_>>(), [0, 1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn soft_reset_keeps_ram() {
+        // LDA #$5A, STA $0300, then JMP to itself
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&[0xA9, 0x5A, 0x8D, 0x00, 0x03, 0x4C, 0x05, 0x80])).unwrap();
+        nes.reset().unwrap();
+        nes.next_frame().unwrap();
+        assert_ne!(nes.cpu().pc(), 0x8000);
+        nes.soft_reset().unwrap();
+        assert_eq!(nes.cpu().pc(), 0x8000);
+        assert_eq!(nes.peek(0x0300), 0x5A);
+    }
+
+    #[test]
+    fn stepping_never_grows_the_micro_op_queue() {
+        // LDA #$02, STA $4014 (OAM DMA from page 2), then JMP back to the start
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&[0xA9, 0x02, 0x8D, 0x14, 0x40, 0x4C, 0x00, 0x80])).unwrap();
+        nes.reset().unwrap();
+        let capacity = nes.cpu().state.u_op_queue.capacity();
+        let mut cycles = 0;
+        for _ in 0..1000 {
+            cycles += nes.step_instruction().unwrap() as u32;
+        }
+        // Over 300 DMAs
+        assert!(cycles > 300 * 513);
+        assert_eq!(nes.cpu().state.u_op_queue.capacity(), capacity);
+    }
+
+    #[test]
+    fn oam_dma_aligns_to_an_even_cycle() {
+        // LDA #$02, STA $4014, LDA $00, STA $4014, then JMP to itself
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&[0xA9, 0x02, 0x8D, 0x14, 0x40, 0xA5, 0x00, 0x8D, 0x14, 0x40, 0x4C, 0x0A, 0x80])).unwrap();
+        nes.reset().unwrap();
+        nes.step_instruction().unwrap();
+        // The write lands on cycle 13, so the DMA waits an extra cycle to start on an even one
+        assert_eq!(nes.step_instruction(), Ok(4 + 514));
+        nes.step_instruction().unwrap();
+        // This write lands on cycle 13 + 514 + 3 + 4 = 534, already even
+        assert_eq!(nes.step_instruction(), Ok(4 + 513));
+    }
+
+    #[test]
+    fn peeking_ppustatus_leaves_vblank_set() {
+        // JMP to itself
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&[0x4C, 0x00, 0x80])).unwrap();
+        nes.reset().unwrap();
+        while !nes.bus().borrow().ppu().borrow().in_vblank() {
+            nes.step().unwrap();
+        }
+        assert_eq!(nes.peek(0x2002) & 0x80, 0x80);
+        assert_eq!(nes.peek(0x2002) & 0x80, 0x80);
+        assert_eq!(nes.bus().borrow().read(0x2002) & 0x80, 0x80);
+        assert_eq!(nes.peek(0x2002) & 0x80, 0x00);
+    }
+
+    #[test]
+    fn callbacks_run_once_per_frame() {
+        // JMP to itself
+        let mut nes = Nes::new();
+        nes.load_rom(&nrom(&[0x4C, 0x00, 0x80])).unwrap();
+        nes.reset().unwrap();
+        let frames = Rc::new(Cell::new(0));
+        let samples = Rc::new(Cell::new(0));
+        nes.on_frame({
+            let frames = frames.clone();
+            move |picture| {
+                assert_eq!(picture.len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+                frames.set(frames.get() + 1);
+            }
+        });
+        nes.on_audio({
+            let samples = samples.clone();
+            move |chunk| samples.set(samples.get() + chunk.len())
+        });
+
+        assert_eq!(nes.run_frame(), Ok(StopReason::FrameComplete));
+        assert_eq!(frames.get(), 1);
+        assert!(samples.get() > 0);
+        assert_eq!(nes.pull_samples(&mut [0.0; 16]), 0);
+
+        // A frame interrupted by a breakpoint only counts once it completes
+        nes.add_breakpoint(0x8000);
+        assert_eq!(nes.run_frame(), Ok(StopReason::Breakpoint(0x8000)));
+        assert_eq!(frames.get(), 1);
+        nes.remove_breakpoint(0x8000);
+        assert_eq!(nes.run_frame(), Ok(StopReason::FrameComplete));
+        assert_eq!(frames.get(), 2);
+    }
+
+    #[test]
+    fn frames_take_the_region_cpu_budget() {
+        let frame_cycles = |region| {
+            // JMP to itself
+            let mut nes = Nes::new();
+            nes.load_rom(&nrom(&[0x4C, 0x00, 0x80])).unwrap();
+            nes.set_region_override(Some(region));
+            nes.reset().unwrap();
+            nes.next_frame().unwrap();
+            let start = nes.cpu().cycle_count();
+            nes.next_frame().unwrap();
+            nes.cpu().cycle_count() - start
+        };
+        // 262 * 341 dots at 3 per CPU cycle, and 312 * 341 at 3.2
+        assert!((29780..=29781).contains(&frame_cycles(Region::Ntsc)));
+        assert!((33247..=33248).contains(&frame_cycles(Region::Pal)));
+    }
+}
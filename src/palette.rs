@@ -0,0 +1,181 @@
+use crate::RustNesError;
+use crate::hardware::ppu::PpuMask;
+
+/// The standard 2C02 (NTSC) palette, indexed by 6-bit NES color number.
+pub const NTSC_PALETTE: [(u8, u8, u8); 64] = [
+    ( 84,  84,  84), (  0,  30, 116), (  8,  16, 144), ( 48,   0, 136),
+    ( 68,   0, 100), ( 92,   0,  48), ( 84,   4,   0), ( 60,  24,   0),
+    ( 32,  42,   0), (  8,  58,   0), (  0,  64,   0), (  0,  60,   0),
+    (  0,  50,  60), (  0,   0,   0), (  0,   0,   0), (  0,   0,   0),
+
+    (152, 150, 152), (  8,  76, 196), ( 48,  50, 236), ( 92,  30, 228),
+    (136,  20, 176), (160,  20, 100), (152,  34,  32), (120,  60,   0),
+    ( 84,  90,   0), ( 40, 114,   0), (  8, 124,   0), (  0, 118,  40),
+    (  0, 102, 120), (  0,   0,   0), (  0,   0,   0), (  0,   0,   0),
+
+    (236, 238, 236), ( 76, 154, 236), (120, 124, 236), (176,  98, 236),
+    (228,  84, 236), (236,  88, 180), (236, 106, 100), (212, 136,  32),
+    (160, 170,   0), (116, 196,   0), ( 76, 208,  32), ( 56, 204, 108),
+    ( 56, 180, 204), ( 60,  60,  60), (  0,   0,   0), (  0,   0,   0),
+
+    (236, 238, 236), (168, 204, 236), (188, 188, 236), (212, 178, 236),
+    (236, 174, 236), (236, 174, 212), (236, 180, 176), (228, 196, 144),
+    (204, 210, 120), (180, 222, 120), (168, 226, 144), (152, 226, 180),
+    (160, 214, 228), (160, 162, 160), (  0,   0,   0), (  0,   0,   0),
+];
+
+/// How much the non-emphasized channels get dimmed by each active PPUMASK emphasis bit.
+const EMPHASIS_ATTENUATION: f32 = 0.816328;
+
+/// `NTSC_PALETTE` as a `Palette`, so the free functions below don't rebuild it on every call.
+const DEFAULT_PALETTE: Palette = Palette::new(NTSC_PALETTE);
+
+/// A table of the RGB color each of the 64 NES color numbers displays as. How NES colors look
+/// depends on the TV, so frontends may want to offer several; `.pal` files are the usual way to
+/// share them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Palette {
+    colors: [(u8, u8, u8); 64],
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        DEFAULT_PALETTE
+    }
+}
+
+impl Palette {
+    /// Size of a `.pal` file: 64 RGB triples.
+    pub const PAL_FILE_SIZE: usize = 64 * 3;
+
+    pub const fn new(colors: [(u8, u8, u8); 64]) -> Self {
+        Self { colors }
+    }
+
+    /// Loads the contents of a `.pal` file: 64 colors, 3 bytes each, in RGB order.
+    ///
+    /// Returns `RustNesError::InvalidPalette` if the file isn't exactly 192 bytes. (Files with
+    /// precomputed emphasis variants aren't supported; emphasis is applied on the fly.)
+    pub fn from_pal(bytes: &[u8]) -> Result<Self, RustNesError> {
+        if bytes.len() != Self::PAL_FILE_SIZE {
+            return Err(RustNesError::InvalidPalette(bytes.len()));
+        }
+        let mut colors = [(0, 0, 0); 64];
+        for (color, rgb) in colors.iter_mut().zip(bytes.chunks_exact(3)) {
+            *color = (rgb[0], rgb[1], rgb[2]);
+        }
+        Ok(Self::new(colors))
+    }
+
+    /// Converts a 6-bit NES color number into RGB. The upper two bits of `index` are ignored.
+    pub fn rgb(&self, index: u8) -> (u8, u8, u8) {
+        self.colors[(index & 0x3F) as usize]
+    }
+
+    /// Converts a 6-bit NES color number into RGB, applying the grayscale and color emphasis bits
+    /// of the given PPUMASK value.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use rust_nes::palette::Palette;
+    ///
+    /// let palette = Palette::default();
+    /// assert_eq!(palette.rgb_masked(0x16, 0x00), (152, 34, 32));
+    /// // Grayscale keeps only the brightness column
+    /// assert_eq!(palette.rgb_masked(0x16, 0x01), (152, 150, 152));
+    /// ```
+    pub fn rgb_masked(&self, index: u8, mask: u8) -> (u8, u8, u8) {
+        let mask = PpuMask::from_bits_retain(mask);
+        let index = if mask.contains(PpuMask::GRAYSCALE) { index & 0x30 } else { index };
+        let (r, g, b) = self.rgb(index);
+        let (mut r, mut g, mut b) = (r as f32, g as f32, b as f32);
+        if mask.contains(PpuMask::EMPHASIZE_RED) {
+            g *= EMPHASIS_ATTENUATION;
+            b *= EMPHASIS_ATTENUATION;
+        }
+        if mask.contains(PpuMask::EMPHASIZE_GREEN) {
+            r *= EMPHASIS_ATTENUATION;
+            b *= EMPHASIS_ATTENUATION;
+        }
+        if mask.contains(PpuMask::EMPHASIZE_BLUE) {
+            r *= EMPHASIS_ATTENUATION;
+            g *= EMPHASIS_ATTENUATION;
+        }
+        (r as u8, g as u8, b as u8)
+    }
+
+    /// Converts a framebuffer of NES color numbers into an RGBA buffer (4 bytes per pixel, alpha
+    /// always 0xFF), applying the given PPUMASK value.
+    pub fn framebuffer_to_rgba(&self, framebuffer: &[u8], mask: u8) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(framebuffer.len() * 4);
+        for &index in framebuffer {
+            let (r, g, b) = self.rgb_masked(index, mask);
+            rgba.extend_from_slice(&[r, g, b, 0xFF]);
+        }
+        rgba
+    }
+}
+
+/// Converts a 6-bit NES color number into RGB, using the NTSC palette. The upper two bits of
+/// `index` are ignored. Shorthand for `Palette::default().rgb(index)`.
+pub fn nes_color_to_rgb(index: u8) -> (u8, u8, u8) {
+    DEFAULT_PALETTE.rgb(index)
+}
+
+/// Converts a 6-bit NES color number into RGB using the NTSC palette, applying the grayscale and
+/// color emphasis bits of the given PPUMASK value. Shorthand for
+/// `Palette::default().rgb_masked(index, mask)`.
+pub fn nes_color_to_rgb_masked(index: u8, mask: u8) -> (u8, u8, u8) {
+    DEFAULT_PALETTE.rgb_masked(index, mask)
+}
+
+/// Converts a framebuffer of NES color numbers into an RGBA buffer using the NTSC palette.
+/// Shorthand for `Palette::default().framebuffer_to_rgba(framebuffer, mask)`.
+pub fn framebuffer_to_rgba(framebuffer: &[u8], mask: u8) -> Vec<u8> {
+    DEFAULT_PALETTE.framebuffer_to_rgba(framebuffer, mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_palette_matches_ntsc_table() {
+        let palette = Palette::default();
+        assert_eq!(palette.rgb(0x00), (84, 84, 84));
+        assert_eq!(palette.rgb(0x16), (152, 34, 32));
+        assert_eq!(palette.rgb(0x30), (236, 238, 236));
+        assert_eq!(palette.rgb(0x3F), (0, 0, 0));
+        // Only the low six bits select a color
+        assert_eq!(palette.rgb(0xD6), palette.rgb(0x16));
+    }
+
+    #[test]
+    fn mask_applies_grayscale_and_emphasis() {
+        let palette = Palette::default();
+        assert_eq!(palette.rgb_masked(0x21, 0x00), (76, 154, 236));
+        assert_eq!(palette.rgb_masked(0x21, 0x01), (236, 238, 236));
+        // Red emphasis dims green and blue
+        assert_eq!(palette.rgb_masked(0x30, 0x20), (236, 194, 192));
+        // All three emphasis bits dim every channel twice
+        assert_eq!(palette.rgb_masked(0x30, 0xE0), (157, 158, 157));
+    }
+
+    #[test]
+    fn framebuffer_to_rgba_converts_each_pixel() {
+        let rgba = framebuffer_to_rgba(&[0x16, 0x30], 0x01);
+        assert_eq!(rgba, [152, 150, 152, 0xFF, 236, 238, 236, 0xFF]);
+    }
+
+    #[test]
+    fn free_functions_follow_the_default_palette() {
+        let palette = Palette::default();
+        for index in 0..=0xFF {
+            assert_eq!(nes_color_to_rgb(index), palette.rgb(index));
+            assert_eq!(nes_color_to_rgb_masked(index, 0xE1), palette.rgb_masked(index, 0xE1));
+        }
+        let custom = Palette::new([(1, 2, 3); 64]);
+        assert_eq!(custom.framebuffer_to_rgba(&[0x0F], 0x00), [1, 2, 3, 0xFF]);
+    }
+}
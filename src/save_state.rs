@@ -0,0 +1,180 @@
+use crate::RustNesError;
+
+/// Identifies save state blobs, followed by `VERSION`
+const MAGIC: &[u8; 4] = b"RNSS";
+/// Bumped whenever the layout changes, so stale blobs get rejected instead of misread
+const VERSION: u8 = 14;
+/// Parameters of the 64-bit FNV-1a hash
+const FNV_OFFSET_BASIS: u64 = 0xCBF2_9CE4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01B3;
+
+/// A snapshot of the entire console (CPU, RAM, PPU, APU, controllers and cartridge), which can be
+/// written to a file and loaded back later to resume from the exact same cycle.
+///
+/// Save states are only compatible with the ROM they were made with. The audio waiting to be
+/// pulled isn't included.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SaveState {
+    data: Vec<u8>,
+}
+
+impl SaveState {
+    /// The serialized state, for writing to a file.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// A 64-bit FNV-1a hash of the serialized state. It's the same on every run and platform,
+    /// though not across emulator versions whose save state layout differs.
+    pub fn hash(&self) -> u64 {
+        self.data.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME))
+    }
+
+    /// Wraps a previously serialized state.
+    ///
+    /// Returns `RustNesError::InvalidSaveState` if the data wasn't made by this version of the
+    /// emulator. Whether the rest of it is intact is only checked upon loading.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, RustNesError> {
+        if data.len() < MAGIC.len() + 1 || &data[..MAGIC.len()] != MAGIC || data[MAGIC.len()] != VERSION {
+            return Err(RustNesError::InvalidSaveState);
+        }
+        Ok(Self { data })
+    }
+
+    pub(crate) fn save(component: &impl Stateful) -> Self {
+        let mut writer = StateWriter { data: MAGIC.to_vec() };
+        writer.u8(VERSION);
+        component.save_state(&mut writer);
+        Self { data: writer.data }
+    }
+
+    pub(crate) fn load(&self, component: &mut impl Stateful) -> Result<(), RustNesError> {
+        let mut reader = StateReader { data: &self.data[MAGIC.len() + 1..] };
+        component.load_state(&mut reader)?;
+        if !reader.data.is_empty() {
+            return Err(RustNesError::InvalidSaveState);
+        }
+        Ok(())
+    }
+}
+
+/// Hardware whose state can be saved into (and loaded from) a `SaveState`.
+///
+/// `load_state` must read back exactly what `save_state` wrote, in the same order.
+pub trait Stateful {
+    fn save_state(&self, state: &mut StateWriter);
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), RustNesError>;
+}
+
+/// Appends values to a save state, in little-endian order.
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn u8(&mut self, value: u8) {
+        self.data.push(value);
+    }
+    pub fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+    pub fn u16(&mut self, value: u16) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn u32(&mut self, value: u32) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+    pub fn u64(&mut self, value: u64) {
+        self.data.extend_from_slice(&value.to_le_bytes());
+    }
+    /// A block of memory, prefixed with its length.
+    pub fn bytes(&mut self, value: &[u8]) {
+        self.u32(value.len() as u32);
+        self.data.extend_from_slice(value);
+    }
+}
+
+/// Reads values back out of a save state. Running out of data means the state is corrupt, and
+/// gives `RustNesError::InvalidSaveState`.
+pub struct StateReader<'a> {
+    data: &'a [u8],
+}
+
+impl StateReader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], RustNesError> {
+        let (bytes, rest) = self.data.split_first_chunk::<N>().ok_or(RustNesError::InvalidSaveState)?;
+        self.data = rest;
+        Ok(*bytes)
+    }
+    pub fn u8(&mut self) -> Result<u8, RustNesError> {
+        Ok(self.take::<1>()?[0])
+    }
+    pub fn bool(&mut self) -> Result<bool, RustNesError> {
+        Ok(self.u8()? != 0)
+    }
+    pub fn u16(&mut self) -> Result<u16, RustNesError> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+    pub fn u32(&mut self) -> Result<u32, RustNesError> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+    pub fn u64(&mut self) -> Result<u64, RustNesError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+    /// A block of memory written by `StateWriter::bytes`, copied into `out`. The length must match,
+    /// as the memory being restored has a fixed size.
+    pub fn bytes(&mut self, out: &mut [u8]) -> Result<(), RustNesError> {
+        if self.u32()? as usize != out.len() || self.data.len() < out.len() {
+            return Err(RustNesError::InvalidSaveState);
+        }
+        let (bytes, rest) = self.data.split_at(out.len());
+        out.copy_from_slice(bytes);
+        self.data = rest;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Nes;
+    use crate::testing::nrom;
+
+    /// Counts up in $00 and keeps writing the count to the backdrop color, so RAM, the PPU and
+    /// the picture all change every frame
+    const PROGRAM: [u8; 19] = [
+        0xE6, 0x00,       // INC $00
+        0xA9, 0x3F,       // LDA #$3F
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA9, 0x00,       // LDA #$00
+        0x8D, 0x06, 0x20, // STA $2006
+        0xA5, 0x00,       // LDA $00
+        0x8D, 0x07, 0x20, // STA $2007
+        0x50, 0xED,       // BVC back to the start
+    ];
+
+    fn run_frames(nes: &mut Nes, frames: u32) {
+        for _ in 0..frames {
+            nes.next_frame().unwrap();
+        }
+    }
+
+    #[test]
+    fn load_state_resumes_identically() {
+        let mut nes = Nes::with_seed(1);
+        nes.load_rom(&nrom(&PROGRAM)).unwrap();
+        nes.reset().unwrap();
+        run_frames(&mut nes, 3);
+        let state = nes.save_state();
+
+        run_frames(&mut nes, 2);
+        let hash = nes.state_hash();
+        let framebuffer = nes.framebuffer().to_vec();
+        assert_ne!(hash, state.hash());
+
+        nes.load_state(&state).unwrap();
+        assert_eq!(nes.state_hash(), state.hash());
+        run_frames(&mut nes, 2);
+        assert_eq!(nes.state_hash(), hash);
+        assert_eq!(nes.framebuffer(), framebuffer);
+    }
+}
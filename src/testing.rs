@@ -0,0 +1,244 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use crate::{Nes, RustNesError};
+use crate::hardware::*;
+
+/// Address of the status byte blargg's test ROMs report through
+const STATUS_ADDR: u16 = 0x6000;
+/// Address of the signature that marks the status byte as valid
+const SIGNATURE_ADDR: u16 = 0x6001;
+/// Address of the null-terminated result message
+const MESSAGE_ADDR: u16 = 0x6004;
+const SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+
+/// Status values below this are final result codes
+const STATUS_RUNNING: u8 = 0x80;
+/// The ROM asks for the reset button to be pressed
+const STATUS_NEEDS_RESET: u8 = 0x81;
+/// Frames to wait before pressing reset (the ROMs want at least 100ms)
+const RESET_DELAY_FRAMES: u32 = 8;
+
+/// What a test ROM reported once it finished (or gave up waiting on it).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestRomResult {
+    /// Result code: 0 means every test passed, anything else is the number of the failing test.
+    /// `0x80` if the ROM never finished.
+    pub status: u8,
+    /// The text the ROM printed
+    pub message: String,
+}
+
+impl TestRomResult {
+    pub fn passed(&self) -> bool {
+        self.status == 0
+    }
+}
+
+/// Runs one of blargg's test ROMs (`instr_test-v5`, `cpu_instrs`, ...) headlessly, for at most
+/// `max_frames` frames.
+///
+/// These ROMs report through PRG-RAM: $6001-$6003 hold `DE B0 61` once $6000 is meaningful,
+/// $6000 is `$80` while running, `$81` when the reset button should be pressed, and the final
+/// result code otherwise. The message is a null-terminated string from $6004. These are read with
+/// `Nes::peek`, so polling them doesn't disturb the ROM.
+pub fn run_test_rom(rom: &[u8], max_frames: u32) -> Result<TestRomResult, RustNesError> {
+    let mut nes = Nes::new();
+    nes.set_realtime(false);
+    nes.load_rom(rom)?;
+    nes.reset()?;
+
+    let mut reset_countdown = None;
+    let mut status = STATUS_RUNNING;
+    for _ in 0..max_frames {
+        nes.run_frame()?;
+        if !has_signature(&nes) {
+            continue;
+        }
+        status = nes.peek(STATUS_ADDR);
+        match status {
+            STATUS_RUNNING => {}
+            STATUS_NEEDS_RESET => match reset_countdown {
+                None => reset_countdown = Some(RESET_DELAY_FRAMES),
+                Some(0) => {
+                    reset_countdown = None;
+                    nes.soft_reset()?;
+                }
+                Some(frames) => reset_countdown = Some(frames - 1),
+            },
+            _ => break,
+        }
+    }
+    if status >= STATUS_RUNNING {
+        status = STATUS_RUNNING;
+    }
+
+    Ok(TestRomResult { status, message: read_message(&nes) })
+}
+
+fn has_signature(nes: &Nes) -> bool {
+    (0..SIGNATURE.len()).all(|i| nes.peek(SIGNATURE_ADDR + i as u16) == SIGNATURE[i])
+}
+
+fn read_message(nes: &Nes) -> String {
+    let bytes: Vec<u8> = (MESSAGE_ADDR..0x8000)
+        .map(|addr| nes.peek(addr))
+        .take_while(|&byte| byte != 0)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Where nestest's automated mode starts, bypassing the menu
+const NESTEST_ENTRY: u16 = 0xC000;
+
+/// The first line where the CPU's trace diverged from a nestest log.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NestestMismatch {
+    /// 1-based line number in the log
+    pub line: usize,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Runs `nestest.nes` in its automated mode, comparing the CPU state before every instruction
+/// against `log` (the contents of the well-known `nestest.log`).
+///
+/// Each line is reduced to the program counter, registers and cycle count before comparing, e.g.
+/// `C000 A:00 X:00 Y:00 P:24 SP:FD CYC:7`, so the disassembly and PPU columns are ignored.
+/// Returns how many lines matched.
+pub fn run_nestest(rom: &[u8], log: &str) -> Result<usize, NestestMismatch> {
+    let mismatch = |line, expected: &str, actual: String| NestestMismatch { line, expected: expected.into(), actual };
+
+    let mut cpu = nestest_cpu(rom).map_err(|err| mismatch(0, "a loadable ROM", format!("{:?}", err)))?;
+    let mut matched = 0;
+    for (index, line) in log.lines().enumerate() {
+        let Some(expected) = reduce_nestest_line(line) else {
+            continue;
+        };
+        let actual = trace_line(&cpu);
+        if actual != expected {
+            return Err(mismatch(index + 1, &expected, actual));
+        }
+        matched += 1;
+        cpu.step_instruction().map_err(|err| mismatch(index + 2, "the next instruction", format!("{:?}", err)))?;
+    }
+    Ok(matched)
+}
+
+/// A bare CPU and bus with the ROM inserted, positioned at nestest's automated entry point.
+fn nestest_cpu(rom: &[u8]) -> Result<MOS6502, RustNesError> {
+    let cart = Cart::from_rom(rom)?;
+    let bus = Rc::new(RefCell::new(Bus::new(RefCell::new(PPU::new()), RefCell::new(APU::new()))));
+    bus.borrow_mut().load_cart(RefCell::new(cart));
+    let mut cpu = MOS6502::with_config(bus, CpuConfig { start_pc: Some(NESTEST_ENTRY), ..CpuConfig::default() });
+    cpu.reset()?;
+    Ok(cpu)
+}
+
+/// The CPU state in the reduced nestest log format.
+fn trace_line(cpu: &MOS6502) -> String {
+    format!(
+        "{:04X} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} CYC:{}",
+        cpu.program_counter, cpu.a, cpu.x, cpu.y, cpu.status.bits(), cpu.stack_ptr, cpu.cycles,
+    )
+}
+
+/// Reduces a line of `nestest.log` to the format of `trace_line`. Returns `None` for blank lines.
+fn reduce_nestest_line(line: &str) -> Option<String> {
+    let pc = line.get(0..4)?;
+    let registers = &line[line.rfind(" A:")? + 1..];
+    let registers = registers.get(..registers.find(" PPU:").unwrap_or(registers.len()))?;
+    let cycles = &line[line.rfind("CYC:")?..];
+    Some(format!("{} {} {}", pc, registers.trim_end(), cycles.trim_end()))
+}
+
+/// Builds an iNES image of an NROM cartridge (16KB of PRG-ROM, 8KB of CHR-ROM) whose `program`
+/// starts at $8000, where the reset vector points. The rest of PRG-ROM is $00 (`BRK`), and the
+/// CHR-ROM is blank.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_nes::Nes;
+/// use rust_nes::testing::nrom;
+///
+/// // LDA #$42, STA $00, then loop forever
+/// let rom = nrom(&[0xA9, 0x42, 0x85, 0x00, 0x4C, 0x04, 0x80]);
+/// let mut nes = Nes::new();
+/// nes.load_rom(&rom).unwrap();
+/// nes.reset().unwrap();
+/// nes.next_frame().unwrap();
+/// assert_eq!(nes.peek(0x0000), 0x42);
+/// ```
+pub fn nrom(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![b'N', b'E', b'S', 0x1A, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut prg = vec![0; 0x4000];
+    prg[..program.len()].copy_from_slice(program);
+    prg[0x3FFD] = 0x80;
+    rom.extend(prg);
+    rom.extend(vec![0; 0x2000]);
+    rom
+}
+
+/// 64KB of RAM and nothing else, for running bare 6502 code. See `TestProgram`.
+pub struct TestBus {
+    memory: RefCell<Vec<u8>>,
+}
+
+impl TestBus {
+    pub fn new() -> Self {
+        Self { memory: RefCell::new(vec![0; 0x10000]) }
+    }
+
+    /// Copies `bytes` into memory starting at `origin`, wrapping around past $FFFF.
+    pub fn load(&self, origin: u16, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            self.write(origin.wrapping_add(offset as u16), *byte);
+        }
+    }
+}
+
+impl Default for TestBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MemoryBus for TestBus {
+    fn read(&self, address: u16) -> u8 {
+        self.memory.borrow()[address as usize]
+    }
+
+    fn write(&self, address: u16, value: u8) {
+        self.memory.borrow_mut()[address as usize] = value;
+    }
+}
+
+/// A CPU on a `TestBus`, reset into a program, ready to step. Handy for checking what a few
+/// instructions do without building a ROM.
+///
+/// # Examples
+///
+/// ```rust
+/// use rust_nes::testing::TestProgram;
+///
+/// // LDA #$05, TAX
+/// let mut program = TestProgram::new(0x0600, &[0xA9, 0x05, 0xAA]);
+/// program.cpu.run_instructions(2).unwrap();
+/// assert_eq!(program.cpu.x(), 0x05);
+/// ```
+pub struct TestProgram {
+    pub bus: Rc<TestBus>,
+    pub cpu: MOS6502,
+}
+
+impl TestProgram {
+    /// Maps `bytes` at `origin`, points the reset vector there and resets the CPU into it.
+    pub fn new(origin: u16, bytes: &[u8]) -> Self {
+        let bus = Rc::new(TestBus::new());
+        bus.load(origin, bytes);
+        bus.load(0xFFFC, &origin.to_le_bytes());
+        let mut cpu = MOS6502::new(bus.clone());
+        cpu.reset().expect("resetting a bare CPU can't fail");
+        Self { bus, cpu }
+    }
+}
@@ -0,0 +1,44 @@
+//! Runs blargg's test ROMs, which aren't distributed with the repo. Point the environment
+//! variables below at local copies to run them; tests whose ROM is missing are skipped.
+
+use std::fs;
+use rust_nes::RustNesError;
+use rust_nes::testing;
+
+/// Frames a test ROM gets to finish: `cpu_instrs` and `instr_test-v5` take around half a minute
+/// of emulated time
+const MAX_FRAMES: u32 = 3600;
+
+/// Reads the ROM named by the environment variable `var`, or `None` (with a note on stderr) if
+/// it isn't set or the file is missing.
+fn rom_from_env(var: &str) -> Option<Vec<u8>> {
+    let rom = std::env::var(var).ok().and_then(|path| fs::read(path).ok());
+    if rom.is_none() {
+        eprintln!("skipping: set {} to the path of the ROM to run", var);
+    }
+    rom
+}
+
+/// Runs a test ROM to completion and asserts that it reported success.
+fn assert_passes(rom: &[u8]) -> Result<(), RustNesError> {
+    let result = testing::run_test_rom(rom, MAX_FRAMES)?;
+    assert!(result.passed(), "status {:#04x}: {}", result.status, result.message);
+    assert!(result.message.contains("Passed"), "{}", result.message);
+    Ok(())
+}
+
+#[test]
+fn cpu_instrs() -> Result<(), RustNesError> {
+    let Some(rom) = rom_from_env("CPU_INSTRS_ROM") else {
+        return Ok(());
+    };
+    assert_passes(&rom)
+}
+
+#[test]
+fn instr_test_v5() -> Result<(), RustNesError> {
+    let Some(rom) = rom_from_env("INSTR_TEST_ROM") else {
+        return Ok(());
+    };
+    assert_passes(&rom)
+}
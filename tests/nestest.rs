@@ -0,0 +1,28 @@
+//! Compares the CPU against the well-known nestest log. Neither `nestest.nes` nor `nestest.log`
+//! is distributed with the repo: set `NESTEST_ROM` to the ROM, and `NESTEST_LOG` to the log if
+//! it isn't next to the ROM. The test is skipped when either is missing.
+
+use std::fs;
+use std::path::Path;
+use rust_nes::testing;
+
+#[test]
+fn nestest() {
+    let Ok(rom_path) = std::env::var("NESTEST_ROM") else {
+        eprintln!("skipping: set NESTEST_ROM to the path of nestest.nes");
+        return;
+    };
+    let log_path = std::env::var("NESTEST_LOG")
+        .unwrap_or_else(|_| Path::new(&rom_path).with_extension("log").display().to_string());
+    let (Ok(rom), Ok(log)) = (fs::read(&rom_path), fs::read_to_string(&log_path)) else {
+        eprintln!("skipping: can't read {} or {}", rom_path, log_path);
+        return;
+    };
+
+    if let Err(mismatch) = testing::run_nestest(&rom, &log) {
+        panic!(
+            "nestest diverged at line {}:\n  expected: {}\n  actual:   {}",
+            mismatch.line, mismatch.expected, mismatch.actual,
+        );
+    }
+}